@@ -2,28 +2,26 @@
 // SPDX-License-Identifier: MIT
 
 use std::collections::HashSet;
+use std::sync::Mutex;
 
 use anyhow::{bail, Result};
-use tracing::error;
+use chrono::Utc;
+use serde::Deserialize;
+use tracing::{error, info, warn};
 
-use crate::container::{CommandExt, SuricataContainer};
+use crate::container::{timeouts, CommandExt, SuricataContainer};
 use crate::context::Context;
+use crate::journal;
 use crate::ruleindex::RuleIndex;
+use crate::state::{self, State};
 use crate::SURICATA_CONTAINER_NAME;
 use crate::{build_evebox_command, EVEBOX_CONTAINER_NAME};
 
 pub(crate) fn force_suricata_logrotate(context: &Context) {
-    let _ = context
-        .manager
-        .command()
-        .args([
-            "exec",
-            SURICATA_CONTAINER_NAME,
-            "logrotate",
-            "-fv",
-            "/etc/logrotate.d/suricata",
-        ])
-        .status();
+    match crate::logrotate::rotate(context, true) {
+        Ok(()) => info!("Suricata logs rotated"),
+        Err(err) => error!("Failed to rotate Suricata logs: {err}"),
+    }
 }
 
 pub(crate) fn load_rule_index(context: &Context) -> Result<RuleIndex> {
@@ -33,7 +31,7 @@ pub(crate) fn load_rule_index(context: &Context) -> Result<RuleIndex> {
         .rm()
         .args(&["cat", "/var/lib/suricata/update/cache/index.yaml"])
         .build()
-        .status_output()?;
+        .status_output_timeout(timeouts().control)?;
     let index: RuleIndex = serde_yaml::from_slice(&output)?;
     Ok(index)
 }
@@ -45,7 +43,7 @@ pub(crate) fn get_enabled_ruleset(context: &Context) -> Result<HashSet<String>>
         .run()
         .args(&["suricata-update", "list-sources", "--enabled"])
         .build()
-        .output()?;
+        .output_timeout(timeouts().control)?;
     let stdout = String::from_utf8_lossy(&output.stdout);
     let re = regex::Regex::new(r"^[\s]*\-\s*(.*)").unwrap();
     for line in stdout.lines() {
@@ -56,13 +54,25 @@ pub(crate) fn get_enabled_ruleset(context: &Context) -> Result<HashSet<String>>
     Ok(enabled)
 }
 
-pub(crate) fn enable_ruleset(context: &Context, ruleset: &str) -> Result<()> {
+pub(crate) fn enable_ruleset(
+    context: &Context,
+    ruleset: &str,
+    parameters: &[(String, String)],
+) -> Result<()> {
     let container = SuricataContainer::new(context.clone());
+    let mut args = vec![
+        "suricata-update".to_string(),
+        "enable-source".to_string(),
+        ruleset.to_string(),
+    ];
+    for (name, value) in parameters {
+        args.push(format!("{}={}", name, value));
+    }
     container
         .run()
-        .args(&["suricata-update", "enable-source", ruleset])
+        .args(&args)
         .build()
-        .status_ok()?;
+        .status_ok_timeout(timeouts().control)?;
     Ok(())
 }
 
@@ -72,35 +82,40 @@ pub(crate) fn disable_ruleset(context: &Context, ruleset: &str) -> Result<()> {
         .run()
         .args(&["suricata-update", "disable-source", ruleset])
         .build()
-        .status_ok()?;
+        .status_ok_timeout(timeouts().control)?;
     Ok(())
 }
 
-pub(crate) fn update_rules(context: &Context) -> Result<()> {
-    let container = SuricataContainer::new(context.clone());
-
+/// Volumes for the suricata-update configuration files that exist.
+pub(crate) fn suricata_update_volumes() -> Vec<String> {
     let mut volumes = vec![];
-
-    if let Ok(cdir) = std::env::current_dir() {
-        for filename in ["enable.conf", "disable.conf", "modify.conf"] {
-            if cdir.join(filename).exists() {
-                volumes.push(format!(
-                    "{}/{}:/etc/suricata/{}",
-                    cdir.display(),
-                    filename,
-                    filename,
-                ));
-            }
+    for filename in [
+        "enable.conf",
+        "disable.conf",
+        "modify.conf",
+        "drop.conf",
+        "update.yaml",
+    ] {
+        let path = state::file_path(filename);
+        if path.exists() {
+            volumes.push(format!("{}:/etc/suricata/{}", path.display(), filename));
         }
     }
+    volumes
+}
+
+pub(crate) fn update_rules(context: &Context) -> Result<()> {
+    let container = SuricataContainer::new(context.clone());
+    let volumes = suricata_update_volumes();
 
     if let Err(err) = container
         .run()
         .rm()
         .it()
+        .volumes(&volumes)
         .args(&["suricata-update", "update-sources"])
         .build()
-        .status_ok()
+        .status_ok_timeout(timeouts().run)
     {
         error!("Rule source update did not complete successfully: {err}");
     }
@@ -111,20 +126,233 @@ pub(crate) fn update_rules(context: &Context) -> Result<()> {
         .volumes(&volumes)
         .args(&["suricata-update"])
         .build()
-        .status_ok()
+        .status_ok_timeout(timeouts().run)
     {
         error!("Rule update did not complete successfully: {err}");
+        journal::record(journal::Kind::RuleUpdateFailed, err.to_string());
+    } else {
+        journal::record(journal::Kind::RuleUpdate, "Rules updated");
+        if let Err(err) = State::update(|state| state.last_rule_update = Some(Utc::now())) {
+            error!("Failed to record rule update time: {err}");
+        }
     }
     Ok(())
 }
 
+/// Rule loading statistics from the running Suricata.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RuleStats {
+    pub loaded: u64,
+    pub failed: u64,
+}
+
+/// The rule statistics last found, for the container started at
+/// `started_at`, with the logs read up to `read_at`.
+struct RuleStatsCache {
+    started_at: String,
+    read_at: i64,
+    stats: Option<RuleStats>,
+}
+
+static RULE_STATS: Mutex<Option<RuleStatsCache>> = Mutex::new(None);
+
+/// Find the rule loading statistics in the Suricata container output.
+///
+/// As this is shown on every redraw of the menu, only the logs since
+/// the last call are read, unless the container was restarted. Rules
+/// reloaded since are still picked up.
+pub(crate) fn rule_stats(context: &Context) -> Option<RuleStats> {
+    let started_at = context
+        .manager
+        .state(SURICATA_CONTAINER_NAME)
+        .ok()?
+        .started_at
+        .unwrap_or_default();
+    let mut cache = RULE_STATS.lock().unwrap_or_else(|err| err.into_inner());
+    let cache = cache.get_or_insert_with(|| RuleStatsCache {
+        started_at: String::new(),
+        read_at: 0,
+        stats: None,
+    });
+    let incremental = cache.started_at == started_at
+        && !started_at.is_empty()
+        && cache.read_at > 0
+        && context.manager.capabilities().logs_since;
+    if !incremental {
+        cache.started_at = started_at;
+        cache.stats = None;
+    }
+
+    let now = Utc::now().timestamp();
+    let mut command = context.manager.command();
+    command.arg("logs");
+    if incremental {
+        // Overlap by a second, seeing a line twice is harmless.
+        command.arg(format!("--since={}", cache.read_at - 1));
+    }
+    let output = command
+        .arg(SURICATA_CONTAINER_NAME)
+        .output_timeout(timeouts().query)
+        .ok()?;
+    let re = regex::Regex::new(r"(\d+) rules successfully loaded, (\d+) rules failed").unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let found = stdout
+        .lines()
+        .chain(stderr.lines())
+        .filter_map(|line| re.captures(line))
+        .next_back()
+        .and_then(|caps| {
+            Some(RuleStats {
+                loaded: caps[1].parse().ok()?,
+                failed: caps[2].parse().ok()?,
+            })
+        });
+    if output.status.success() {
+        cache.read_at = now;
+    }
+    if found.is_some() {
+        cache.stats = found;
+    }
+    cache.stats
+}
+
+/// Get the enabled rule sources from the running Suricata container.
+///
+/// Cheaper than `get_enabled_ruleset` as it doesn't need to start a new
+/// container.
+pub(crate) fn enabled_sources(context: &Context) -> Option<Vec<String>> {
+    let output = context
+        .manager
+        .command()
+        .args([
+            "exec",
+            SURICATA_CONTAINER_NAME,
+            "ls",
+            "/var/lib/suricata/update/sources",
+        ])
+        .output_timeout(timeouts().query)
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.strip_suffix(".yaml"))
+            .map(|name| name.replacen('-', "/", 1))
+            .collect(),
+    )
+}
+
+/// URL suricata-update downloads the source index from.
+const INDEX_URL: &str = "https://www.openinfosecfoundation.org/rules/index.yaml";
+
+/// URL of the ruleset used when no source is enabled.
+const DEFAULT_SOURCE_URL: &str =
+    "https://rules.emergingthreats.net/open/suricata-%(__version__)s/emerging.rules.tar.gz";
+
+#[derive(Debug, Deserialize)]
+struct EnabledSource {
+    source: Option<String>,
+    url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct UpdateConfig {
+    #[serde(default)]
+    sources: Vec<String>,
+}
+
+/// The URLs suricata-update downloads from: the source index, the
+/// enabled sources, looked up in the index unless added by URL, and the
+/// sources of update.yaml. The URLs may still contain suricata-update
+/// templates such as `%(__version__)s`.
+pub(crate) fn source_urls(context: &Context) -> Result<Vec<String>> {
+    let output = SuricataContainer::new(context.clone())
+        .run()
+        .rm()
+        .args(&[
+            "sh",
+            "-c",
+            "for f in /var/lib/suricata/update/sources/*.yaml; do [ -e \"$f\" ] && echo --- && cat \"$f\"; done; true",
+        ])
+        .build()
+        .status_output_timeout(timeouts().control)?;
+    let mut enabled = vec![];
+    for document in serde_yaml::Deserializer::from_slice(&output) {
+        enabled.push(EnabledSource::deserialize(document)?);
+    }
+
+    let mut urls = vec![INDEX_URL.to_string()];
+    if enabled.is_empty() {
+        urls.push(DEFAULT_SOURCE_URL.to_string());
+    }
+    // The index is only cached after the sources have been updated.
+    let index = if enabled.iter().any(|source| source.url.is_none()) {
+        load_rule_index(context).ok()
+    } else {
+        None
+    };
+    for source in enabled {
+        let url = source.url.or_else(|| {
+            let name = source.source.as_ref()?;
+            index.as_ref()?.sources.get(name)?.url.clone()
+        });
+        urls.extend(url);
+    }
+
+    if let Ok(buf) = std::fs::read_to_string(state::file_path("update.yaml")) {
+        match serde_yaml::from_str::<UpdateConfig>(&buf) {
+            Ok(config) => urls.extend(config.sources),
+            Err(err) => warn!("Failed to parse update.yaml: {err}"),
+        }
+    }
+    Ok(urls)
+}
+
+/// Run a command on the running Suricata over its unix socket.
+pub(crate) fn suricatasc(context: &Context, command: &str) -> Result<serde_json::Value> {
+    let output = context
+        .manager
+        .command()
+        .args(["exec", SURICATA_CONTAINER_NAME, "suricatasc", "-c", command])
+        .output_timeout(timeouts().query)?;
+    if !output.status.success() {
+        bail!(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let response: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    if response["return"] != "OK" {
+        bail!("{}: {}", command, response["message"]);
+    }
+    Ok(response["message"].clone())
+}
+
+/// The interfaces the running Suricata is capturing on.
+pub(crate) fn capture_interfaces(context: &Context) -> Result<Vec<String>> {
+    let message = suricatasc(context, "iface-list")?;
+    Ok(message["ifaces"]
+        .as_array()
+        .map(|ifaces| {
+            ifaces
+                .iter()
+                .filter_map(|iface| iface.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
 pub(crate) fn start_evebox(context: &Context) -> Result<()> {
-    context.manager.quiet_rm(EVEBOX_CONTAINER_NAME);
+    crate::remove_container(context, EVEBOX_CONTAINER_NAME);
+    crate::menu::evebox::bootstrap_admin(context);
     let mut command = build_evebox_command(context, true);
-    let output = command.output()?;
+    let output = command.output_timeout(timeouts().control)?;
     if !output.status.success() {
         bail!(String::from_utf8_lossy(&output.stderr).to_string());
     }
+    if let Err(err) = State::record_start(EVEBOX_CONTAINER_NAME) {
+        error!("Failed to record EveBox start: {err}");
+    }
     Ok(())
 }
 
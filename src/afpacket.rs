@@ -0,0 +1,162 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! The af-packet configuration stub included into the Suricata
+//! configuration, generated from the Simple-IDS configuration.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use serde_yaml::{Mapping, Value};
+use tracing::info;
+
+use crate::{config::SuricataConfig, editor, state};
+
+pub(crate) const FILENAME: &str = "af-packet.yaml";
+
+/// User edits layered on top of the generated stub, in the state
+/// directory.
+pub(crate) const LOCAL_FILENAME: &str = "af-packet.local.yaml";
+
+/// Where the stub is mounted in the Suricata container.
+pub(crate) const CONTAINER_PATH: &str = "/etc/suricata/af-packet.yaml";
+
+/// Ethernet header size, added to the MTU to get the packet size.
+const ETHERNET_HEADER_LEN: u32 = 14;
+
+/// Render the af-packet stub with the user edits from the local file
+/// merged in.
+pub(crate) fn render_with_local(config: &SuricataConfig) -> Result<String> {
+    let rendered = render(config)?;
+    let local = match std::fs::read_to_string(state::file_path(LOCAL_FILENAME)) {
+        Ok(local) => local,
+        Err(_) => return Ok(rendered),
+    };
+    let local: Value = match serde_yaml::from_str(&local) {
+        Ok(local) => local,
+        Err(err) => bail!("Failed to parse {LOCAL_FILENAME}: {err}"),
+    };
+    let mut root: Value = serde_yaml::from_str(&rendered)?;
+    merge(&mut root, local);
+    Ok(format!("%YAML 1.1\n---\n{}", serde_yaml::to_string(&root)?))
+}
+
+/// Merge the user edits into the generated stub. Mappings are merged
+/// recursively, and entries of the af-packet list are merged with the
+/// entry for the same interface, or appended before the default entry.
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(overlay)) => {
+            for entry in overlay {
+                let interface = entry.get("interface").cloned();
+                match base.iter_mut().find(|existing| {
+                    interface.is_some() && existing.get("interface") == interface.as_ref()
+                }) {
+                    Some(existing) => merge(existing, entry),
+                    None => {
+                        // The default entry must stay last.
+                        let at = base.len().saturating_sub(1);
+                        base.insert(at, entry);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Render the af-packet stub.
+///
+/// Suricata replaces list entries by index when including a file, so
+/// the "default" entry is always written last to replace the default
+/// entry of the built-in configuration.
+pub(crate) fn render(config: &SuricataConfig) -> Result<String> {
+    let interfaces = config.capture_interfaces();
+    let mut entries = vec![];
+    for (i, interface) in interfaces.iter().enumerate() {
+        let mut entry = Mapping::new();
+        entry.insert("interface".into(), interface.as_str().into());
+        entry.insert("cluster-id".into(), (99 - i as u64).into());
+        entry.insert("cluster-type".into(), "cluster_flow".into());
+        if let Some(bridge) = &config.bridge {
+            // Copy the packets to the other interface of the pair,
+            // dropping those matching drop rules. Inline mode doesn't
+            // work with defrag or tpacket-v3.
+            let peer = if *interface == bridge.lan {
+                &bridge.wan
+            } else {
+                &bridge.lan
+            };
+            entry.insert("copy-mode".into(), "ips".into());
+            entry.insert("copy-iface".into(), peer.as_str().into());
+            entry.insert("defrag".into(), false.into());
+            entry.insert("use-mmap".into(), true.into());
+            entry.insert("tpacket-v3".into(), false.into());
+        } else {
+            entry.insert("defrag".into(), true.into());
+        }
+        if let Some(threads) = config.tuning.threads {
+            entry.insert("threads".into(), threads.into());
+        }
+        if let Some(ring_size) = config.tuning.ring_size {
+            entry.insert("ring-size".into(), ring_size.into());
+        }
+        if let Some(settings) = config.interface_settings.get(interface) {
+            if let Some(snaplen) = settings.snaplen {
+                entry.insert("snaplen".into(), snaplen.into());
+            }
+        }
+        entries.push(Value::Mapping(entry));
+    }
+    let mut default = Mapping::new();
+    default.insert("interface".into(), "default".into());
+    entries.push(Value::Mapping(default));
+
+    let mut root = Mapping::new();
+    root.insert("af-packet".into(), Value::Sequence(entries));
+
+    // The packet size is global, so use the largest MTU.
+    if let Some(mtu) = interfaces
+        .iter()
+        .filter_map(|interface| config.interface_settings.get(interface)?.mtu)
+        .max()
+    {
+        root.insert(
+            "default-packet-size".into(),
+            (mtu + ETHERNET_HEADER_LEN).into(),
+        );
+    }
+
+    Ok(format!("%YAML 1.1\n---\n{}", serde_yaml::to_string(&root)?))
+}
+
+/// Write the af-packet stub to the state directory, showing what
+/// changed if it was regenerated, and returning its absolute path for
+/// mounting into the container.
+pub(crate) fn write_af_packet_stub(config: &SuricataConfig) -> Result<PathBuf> {
+    let path = state::file_path(FILENAME);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let rendered = render_with_local(config)?;
+    match std::fs::read_to_string(&path) {
+        Ok(previous) if previous == rendered => return Ok(path),
+        Ok(previous) => {
+            info!("Regenerated {}", path.display());
+            editor::print_diff(&previous, &rendered, ("previous", "regenerated"));
+        }
+        Err(_) => {}
+    }
+    std::fs::write(&path, rendered)?;
+    Ok(path)
+}
@@ -0,0 +1,128 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Rule analysis with `suricata --engine-analysis`.
+
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use serde::Deserialize;
+
+use crate::{
+    container::{timeouts, CommandExt, SuricataContainer},
+    context::Context,
+};
+
+/// The rules loaded by Suricata, as written by suricata-update.
+const DEFAULT_RULES: &str = "/var/lib/suricata/rules/suricata.rules";
+
+/// Where a user provided rule file is mounted in the container.
+const CONTAINER_RULES: &str = "/etc/suricata/simple-ids/analyze.rules";
+
+/// A rule from the rules.json written by the engine analysis.
+#[derive(Debug, Deserialize)]
+struct AnalyzedRule {
+    #[serde(default)]
+    id: u64,
+    #[serde(default)]
+    msg: Option<String>,
+    #[serde(default)]
+    raw: String,
+    #[serde(default)]
+    warnings: Vec<String>,
+    #[serde(default)]
+    notes: Vec<String>,
+    /// The fast pattern, missing if the rule doesn't have one.
+    mpm: Option<serde_json::Value>,
+}
+
+impl AnalyzedRule {
+    fn label(&self) -> String {
+        format!("[{}] {}", self.id, self.msg.as_deref().unwrap_or(&self.raw))
+    }
+
+    /// True if the rule has content to match but none is used as the
+    /// fast pattern, so the rule is evaluated against every packet.
+    fn missing_fast_pattern(&self) -> bool {
+        self.mpm.is_none() && self.raw.contains("content:")
+    }
+}
+
+/// Run the engine analysis on the loaded rules, or the rules in `file`,
+/// and print the rules with warnings or fast pattern issues.
+pub(crate) fn analyze(context: &Context, file: Option<&Path>) -> Result<()> {
+    let container = SuricataContainer::new(context.clone());
+    let mut builder = container.run();
+    builder.rm();
+    let rules = match file {
+        Some(file) => {
+            let file = file.canonicalize()?;
+            builder.volumes(&[format!("{}:{}:ro", file.display(), CONTAINER_RULES)]);
+            CONTAINER_RULES
+        }
+        None => DEFAULT_RULES,
+    };
+
+    // The analysis logs go to stderr so only rules.json is on stdout.
+    let script = format!(
+        "mkdir -p /tmp/analysis && \
+         suricata --engine-analysis -l /tmp/analysis -S {rules} 1>&2 && \
+         cat /tmp/analysis/rules.json"
+    );
+    let output = builder
+        .args(&["sh", "-c", &script])
+        .build()
+        .output_timeout(timeouts().run)?;
+    if !output.status.success() {
+        bail!(
+            "Engine analysis failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let mut analyzed = 0;
+    let mut with_warnings = vec![];
+    let mut no_fast_pattern = vec![];
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let rule: AnalyzedRule = match serde_json::from_str(line) {
+            Ok(rule) => rule,
+            Err(_) => continue,
+        };
+        analyzed += 1;
+        if rule.missing_fast_pattern() {
+            no_fast_pattern.push(rule.label());
+        }
+        if !rule.warnings.is_empty() {
+            with_warnings.push(rule);
+        }
+    }
+
+    println!("Rules analyzed: {analyzed}");
+    println!("Rules with warnings: {}", with_warnings.len());
+    println!("Rules without a fast pattern: {}", no_fast_pattern.len());
+
+    if !with_warnings.is_empty() {
+        println!();
+        println!("Warnings:");
+        for rule in &with_warnings {
+            println!("  {}", rule.label());
+            for warning in &rule.warnings {
+                println!("    {}", warning.yellow());
+            }
+            for note in &rule.notes {
+                println!("    {}", note.italic());
+            }
+        }
+    }
+
+    if !no_fast_pattern.is_empty() {
+        println!();
+        println!("Content rules without a fast pattern:");
+        for label in &no_fast_pattern {
+            println!("  {label}");
+        }
+    }
+
+    Ok(())
+}
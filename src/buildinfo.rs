@@ -0,0 +1,130 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! The features the Suricata in the image was built with, read from
+//! `suricata --build-info` and cached by image ID, so unsupported
+//! options are refused or hidden up front instead of failing with an
+//! obscure error when the container starts.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    container::{timeouts, CommandExt, Container, SuricataContainer},
+    context::Context,
+    state,
+};
+
+const FILENAME: &str = "build-info.json";
+
+/// Features that options depend on, named as in the "support" lines of
+/// the build info.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Feature {
+    AfPacket,
+    NfQueue,
+    Ja4,
+}
+
+impl Feature {
+    fn name(&self) -> &'static str {
+        match self {
+            Feature::AfPacket => "AF_PACKET",
+            Feature::NfQueue => "NFQueue",
+            Feature::Ja4 => "JA4",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub(crate) struct BuildInfo {
+    /// The ID of the image the build info was read from.
+    #[serde(rename = "image-id")]
+    image_id: String,
+    pub version: Option<String>,
+    /// The "<name> support: yes/no" lines, by name.
+    features: BTreeMap<String, bool>,
+}
+
+impl BuildInfo {
+    /// Whether Suricata was built with a feature. Features not listed,
+    /// such as by older versions, are assumed to be supported.
+    pub(crate) fn supports(&self, feature: Feature) -> bool {
+        self.features.get(feature.name()).copied().unwrap_or(true)
+    }
+
+    /// Fail with a readable error if a feature is not supported.
+    pub(crate) fn require(&self, feature: Feature, option: &str) -> Result<()> {
+        if !self.supports(feature) {
+            bail!(
+                "{option} requires Suricata built with {} support, which {} was not",
+                feature.name(),
+                self.version
+                    .as_deref()
+                    .unwrap_or("the Suricata in the image")
+            );
+        }
+        Ok(())
+    }
+}
+
+fn parse(output: &str) -> BuildInfo {
+    let mut info = BuildInfo::default();
+    for line in output.lines() {
+        if let Some(version) = line.strip_prefix("This is Suricata version ") {
+            info.version = Some(format!("Suricata {}", version.trim()));
+        } else if let Some((name, value)) = line.split_once(" support:") {
+            info.features
+                .insert(name.trim().to_string(), value.trim() == "yes");
+        }
+    }
+    info
+}
+
+/// Load the build info of the Suricata image, from the cache if the
+/// image hasn't changed.
+pub(crate) fn load(context: &Context) -> Result<BuildInfo> {
+    let image = context.image_name(Container::Suricata);
+    let image_id = match context.manager.image_id(&image) {
+        Some(image_id) => image_id,
+        None => bail!("Image {image} not found"),
+    };
+    let path = state::file_path(FILENAME);
+    if let Some(cached) = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|buf| serde_json::from_str::<BuildInfo>(&buf).ok())
+    {
+        if cached.image_id == image_id {
+            return Ok(cached);
+        }
+    }
+
+    let output = SuricataContainer::new(context.clone())
+        .run()
+        .rm()
+        .args(&["suricata", "--build-info"])
+        .build()
+        .status_output_timeout(timeouts().control)?;
+    let mut info = parse(&String::from_utf8_lossy(&output));
+    info.image_id = image_id;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(&info)?)?;
+    Ok(info)
+}
+
+/// Load the build info, warning and assuming everything is supported if
+/// it can't be read.
+pub(crate) fn load_or_default(context: &Context) -> BuildInfo {
+    match load(context) {
+        Ok(info) => info,
+        Err(err) => {
+            warn!("Failed to read the Suricata build info: {err}");
+            BuildInfo::default()
+        }
+    }
+}
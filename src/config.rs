@@ -1,25 +1,470 @@
 // SPDX-FileCopyrightText: (C) 2021 Jason Ish <jason@codemonkey.net>
 // SPDX-License-Identifier: MIT
 
-use std::io::{Read, Write};
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    os::unix::fs::MetadataExt,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
 
-use crate::prelude::*;
+use crate::{
+    container::{self, Container},
+    context,
+    prelude::*,
+};
 
-const YAML_FILENAME: &str = "simple-ids.yml";
-const TOML_FILENAME: &str = "simple-ids.toml";
+pub(crate) const YAML_FILENAME: &str = "simple-ids.yml";
+pub(crate) const TOML_FILENAME: &str = "simple-ids.toml";
+const TOML_BACKUP_FILENAME: &str = "simple-ids.toml.bak";
 
-#[derive(Debug, Default, Deserialize, Serialize, Clone, Eq, PartialEq)]
+/// The YAML configuration is moved here once replaced by the TOML
+/// configuration, instead of being deleted.
+pub(crate) const YAML_BACKUP_FILENAME: &str = "simple-ids.yml.bak";
+
+/// Set in read-only mode, where saving the configuration is refused.
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_read_only() {
+    READ_ONLY.store(true, Ordering::Relaxed);
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
 pub(crate) struct Config {
+    #[serde(default, skip_serializing_if = "Runtime::is_auto")]
+    pub runtime: Runtime,
+
     pub suricata: SuricataConfig,
 
     #[serde(default)]
     pub evebox: EveBoxConfig,
+
+    /// Restart policy for both containers, such as "unless-stopped" so
+    /// they come back after a crash or reboot, unless a container sets
+    /// its own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RestartPolicy>,
+
+    /// The containers managed, both by default. A central console
+    /// receiving events from remote agents only needs EveBox.
+    #[serde(default, skip_serializing_if = "ServicesConfig::is_default")]
+    pub services: ServicesConfig,
+
+    #[serde(default, skip_serializing_if = "TimeoutConfig::is_default")]
+    pub timeouts: TimeoutConfig,
+
+    /// Remote sensors managed over SSH, by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hosts: BTreeMap<String, String>,
+
+    /// Named groups of interfaces captured on together, such as both
+    /// ports of an aggregation TAP.
+    #[serde(
+        rename = "interface-groups",
+        default,
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub interface_groups: BTreeMap<String, Vec<String>>,
+
+    #[serde(
+        rename = "container-logs",
+        default,
+        skip_serializing_if = "ContainerLogConfig::is_default"
+    )]
+    pub container_logs: ContainerLogConfig,
+
+    #[serde(default, skip_serializing_if = "DisplayConfig::is_default")]
+    pub display: DisplayConfig,
+
+    #[serde(default, skip_serializing_if = "GeoIpConfig::is_default")]
+    pub geoip: GeoIpConfig,
+
+    #[serde(
+        rename = "disk-policy",
+        default,
+        skip_serializing_if = "DiskPolicyConfig::is_default"
+    )]
+    pub disk_policy: DiskPolicyConfig,
+
+    #[serde(default, skip_serializing_if = "UpdatesConfig::is_default")]
+    pub updates: UpdatesConfig,
+
+    #[serde(default, skip_serializing_if = "MaintenanceConfig::is_default")]
+    pub maintenance: MaintenanceConfig,
+
+    #[serde(default, skip_serializing_if = "PrivilegesConfig::is_default")]
+    pub privileges: PrivilegesConfig,
+
+    #[serde(default, skip_serializing_if = "ReportsConfig::is_default")]
+    pub reports: ReportsConfig,
+
+    #[serde(default, skip_serializing_if = "NotificationsConfig::is_default")]
+    pub notifications: NotificationsConfig,
+
+    #[serde(default, skip_serializing_if = "PodmanConfig::is_default")]
+    pub podman: PodmanConfig,
+
+    /// Refuse to run with unknown keys in the configuration file,
+    /// instead of warning about them.
+    #[serde(
+        rename = "strict-config",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub strict_config: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, Eq, PartialEq)]
+#[serde(default)]
+pub(crate) struct ServicesConfig {
+    pub suricata: bool,
+    pub evebox: bool,
+}
+
+impl Default for ServicesConfig {
+    fn default() -> Self {
+        Self {
+            suricata: true,
+            evebox: true,
+        }
+    }
+}
+
+impl ServicesConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub(crate) fn enabled(&self, container: Container) -> bool {
+        match container {
+            Container::Suricata => self.suricata,
+            Container::EveBox => self.evebox,
+        }
+    }
+}
+
+/// Podman specific settings, ignored with Docker.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct PodmanConfig {
+    /// Network mode for the EveBox container, Podman's default if not
+    /// set. Suricata always uses the host network to capture packets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<PodmanNetwork>,
+    /// Options for the network mode, such as "port_handler=slirp4netns"
+    /// for slirp4netns or "--map-gw" for pasta.
+    #[serde(
+        rename = "network-options",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub network_options: Vec<String>,
+}
+
+impl PodmanConfig {
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum PodmanNetwork {
+    /// The bridge network, the default for rootful Podman.
+    Bridge,
+    /// User mode networking with pasta, the rootless default since
+    /// Podman 5.
+    Pasta,
+    /// User mode networking with slirp4netns, the rootless default
+    /// before Podman 5.
+    Slirp4netns,
+    /// The host network, EveBox listens on the host directly.
+    Host,
+}
+
+impl PodmanNetwork {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            PodmanNetwork::Bridge => "bridge",
+            PodmanNetwork::Pasta => "pasta",
+            PodmanNetwork::Slirp4netns => "slirp4netns",
+            PodmanNetwork::Host => "host",
+        }
+    }
+}
+
+/// Both configuration files with different settings.
+pub(crate) struct Conflict {
+    pub toml: Config,
+    pub yaml: Config,
+}
+
+impl Conflict {
+    /// Merge the two configurations. Settings changed from their
+    /// defaults in the TOML configuration take precedence, as that is
+    /// the one that was in use.
+    pub(crate) fn merge(&self) -> Result<Config> {
+        let mut merged = toml::Value::try_from(&self.yaml)?;
+        let default = toml::Value::try_from(Config::default())?;
+        merge_toml(
+            &mut merged,
+            toml::Value::try_from(&self.toml)?,
+            Some(&default),
+        );
+        Ok(merged.try_into()?)
+    }
+}
+
+/// Merge overlay into base, skipping overlay values equal to the
+/// default, as fields that are always serialized can't otherwise be told
+/// apart from ones that were set.
+fn merge_toml(base: &mut toml::Value, overlay: toml::Value, default: Option<&toml::Value>) {
+    if default == Some(&overlay) {
+        return;
+    }
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                let default = default.and_then(|default| default.get(&key));
+                match base.get_mut(&key) {
+                    Some(existing) => merge_toml(existing, value, default),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Scheduled summary reports, for admins who don't check EveBox every
+/// day.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct ReportsConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<ReportSchedule>,
+    /// Address to email the report to, sent with the local sendmail.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// URL to POST the report to as JSON.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+}
+
+impl ReportsConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Push notification channels.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct NotificationsConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ntfy: Option<PushChannel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gotify: Option<PushChannel>,
+}
+
+impl NotificationsConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A push notification server.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct PushChannel {
+    /// For ntfy the topic URL, such as "https://ntfy.sh/my-ids", for
+    /// Gotify the server URL.
+    pub url: String,
+    /// Access token, required by Gotify. Can be a "secret:<key>"
+    /// reference.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Push alerts to this channel, sent by `simple-ids notify alerts`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<AlertNotifications>,
+}
+
+/// Which alerts are pushed to a channel, and how often.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct AlertNotifications {
+    /// Only push alerts of this severity or more severe, 1 being the
+    /// most severe.
+    #[serde(rename = "max-severity", skip_serializing_if = "Option::is_none")]
+    pub max_severity: Option<u8>,
+    /// Minutes during which an alert with the same signature ID and
+    /// source address isn't pushed again, 10 if not set.
+    #[serde(rename = "dedup-minutes", skip_serializing_if = "Option::is_none")]
+    pub dedup_minutes: Option<u32>,
+    /// The most alerts pushed per minute, 5 if not set. The rest are
+    /// counted in the next alert pushed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub burst: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ReportSchedule {
+    Daily,
+    Weekly,
+}
+
+impl ReportSchedule {
+    /// Number of hours covered by each report.
+    pub(crate) fn hours(&self) -> i64 {
+        match self {
+            ReportSchedule::Daily => 24,
+            ReportSchedule::Weekly => 24 * 7,
+        }
+    }
+}
+
+/// Running the menus as an unprivileged user, with the actions that
+/// change the containers run through a privilege helper.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct PrivilegesConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub helper: Option<PrivilegeHelper>,
+}
+
+impl PrivilegesConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PrivilegeHelper {
+    Sudo,
+    Pkexec,
+}
+
+impl std::fmt::Display for PrivilegeHelper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrivilegeHelper::Sudo => write!(f, "sudo"),
+            PrivilegeHelper::Pkexec => write!(f, "pkexec"),
+        }
+    }
+}
+
+/// When disruptive operations, such as rule and image updates, are
+/// allowed to run.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct MaintenanceConfig {
+    /// For example "Sun 03:00-04:00", in local time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<String>,
+}
+
+impl MaintenanceConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Where updates are checked for, for sites with strict egress
+/// policies.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct UpdatesConfig {
+    /// Disable all outbound update checks, for Simple-IDS itself and
+    /// the container images.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub offline: bool,
+    /// Base URL for Simple-IDS self-updates, instead of evebox.org.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Registry for the default images, instead of docker.io.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    /// Trivy image used to scan the images when no scanner is installed,
+    /// such as one pinned by digest.
+    #[serde(rename = "scanner-image", skip_serializing_if = "Option::is_none")]
+    pub scanner_image: Option<String>,
+}
+
+impl UpdatesConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What to do as the disk holding the Suricata logs fills up.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct DiskPolicyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Usage percentage at which log rotation is forced.
+    #[serde(rename = "rotate-percent", skip_serializing_if = "Option::is_none")]
+    pub rotate_percent: Option<u8>,
+    /// Usage percentage at which high volume event types are disabled.
+    #[serde(rename = "reduce-percent", skip_serializing_if = "Option::is_none")]
+    pub reduce_percent: Option<u8>,
+    /// Days of events EveBox keeps while logging is reduced.
+    #[serde(
+        rename = "evebox-retention-days",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub evebox_retention_days: Option<u32>,
+}
+
+impl DiskPolicyConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// MaxMind format GeoIP databases on the host, used to enrich event
+/// output and reports.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct GeoIpConfig {
+    /// Country or City database, such as GeoLite2-Country.mmdb.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<String>,
+    /// ASN database, such as GeoLite2-ASN.mmdb.
+    #[serde(rename = "asn-database", skip_serializing_if = "Option::is_none")]
+    pub asn_database: Option<String>,
+}
+
+impl GeoIpConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// How information is displayed to the user.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct DisplayConfig {
+    /// Timezone for displayed timestamps: "local" (the default), "utc"
+    /// or an IANA name such as "America/Regina".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Run the containers in the host timezone instead of UTC, so
+    /// suricata.log and EveBox timestamps match the host.
+    #[serde(
+        rename = "container-timezone",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub container_timezone: bool,
+}
+
+impl DisplayConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
-#[derive(Debug, Default, Deserialize, Serialize, Clone, Eq, PartialEq)]
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
 pub(crate) struct SuricataConfig {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub interfaces: Vec<String>,
@@ -27,9 +472,227 @@ pub(crate) struct SuricataConfig {
     pub image: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bpf: Option<String>,
+    /// The HOME_NET address group, such as "[192.168.0.0/16,10.0.0.0/8]".
+    #[serde(rename = "home-net", skip_serializing_if = "Option::is_none")]
+    pub home_net: Option<String>,
+    /// Number of -v flags passed to Suricata, defaults to 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verbosity: Option<u8>,
+    /// Additional command line arguments for Suricata.
+    #[serde(
+        rename = "extra-cli-args",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub extra_cli_args: Vec<String>,
+    /// Suricata configuration file on the host, used instead of the
+    /// configuration in the image without any of the Simple-IDS
+    /// adjustments.
+    #[serde(rename = "config-file", skip_serializing_if = "Option::is_none")]
+    pub config_file: Option<String>,
+    /// Suricata settings passed with `--set`, such as
+    /// "stream.memcap=256mb".
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub set: Vec<String>,
+    /// Run in unix-socket mode without live capture, for processing
+    /// pcaps submitted over the socket.
+    #[serde(
+        rename = "unix-socket",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub unix_socket: bool,
+    /// Run inline as an IPS on the packets diverted to NFQUEUE, instead
+    /// of capturing with af-packet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ips: Option<IpsConfig>,
+    /// Run as a transparent inline bridge between two interfaces, with
+    /// af-packet in IPS copy mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bridge: Option<BridgeConfig>,
+    /// Restart policy of the container, overriding the global one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RestartPolicy>,
+    /// Memory limit for the container, such as "4g".
+    #[serde(rename = "memory-limit", skip_serializing_if = "Option::is_none")]
+    pub memory_limit: Option<String>,
+    /// Number of CPUs the container may use, such as "2" or "1.5".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<String>,
+    #[serde(
+        rename = "pcap-log",
+        default,
+        skip_serializing_if = "PcapLogConfig::is_default"
+    )]
+    pub pcap_log: PcapLogConfig,
+    #[serde(default, skip_serializing_if = "StatsConfig::is_default")]
+    pub stats: StatsConfig,
+    /// Capture settings by interface name.
+    #[serde(
+        rename = "interface-settings",
+        default,
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub interface_settings: BTreeMap<String, InterfaceSettings>,
+    #[serde(default, skip_serializing_if = "TuningConfig::is_default")]
+    pub tuning: TuningConfig,
+    /// Rotate the logs from a host cron job in /etc/cron.d, which keeps
+    /// working when the container is restarted outside of Simple-IDS,
+    /// instead of from a loop inside the container.
+    #[serde(
+        rename = "logrotate-cron",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub logrotate_cron: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+impl SuricataConfig {
+    /// Whether Suricata captures packets from the interfaces, rather
+    /// than reading from a unix socket or NFQUEUE.
+    pub(crate) fn live_capture(&self) -> bool {
+        !self.unix_socket && self.ips.is_none()
+    }
+
+    /// The interfaces captured on, the bridged pair in bridge mode.
+    pub(crate) fn capture_interfaces(&self) -> Vec<String> {
+        match &self.bridge {
+            Some(bridge) => vec![bridge.lan.clone(), bridge.wan.clone()],
+            None => self.interfaces.clone(),
+        }
+    }
+}
+
+/// The interface pair of the inline bridge, packets received on one are
+/// copied to the other.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct BridgeConfig {
+    pub lan: String,
+    pub wan: String,
+}
+
+/// Inline (IPS) mode settings.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct IpsConfig {
+    /// The NFQUEUE number Suricata reads from.
+    #[serde(default)]
+    pub queue: u16,
+    /// The LAN side interface of the divert rules.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lan: Option<String>,
+    /// The WAN side interface of the divert rules.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wan: Option<String>,
+}
+
+/// Performance and resource usage tuning, usually set from a preset.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct TuningConfig {
+    /// Suricata runmode, such as "workers" or "autofp".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runmode: Option<String>,
+    /// Capture threads per interface, with the workers runmode each
+    /// thread also does detection.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<u32>,
+    /// Pin the threads to CPUs.
+    #[serde(
+        rename = "cpu-affinity",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub cpu_affinity: bool,
+    /// AF_PACKET ring size, in packets per thread.
+    #[serde(rename = "ring-size", skip_serializing_if = "Option::is_none")]
+    pub ring_size: Option<u32>,
+    /// EVE event types to disable, such as "flow" or "dns".
+    #[serde(
+        rename = "disabled-eve-types",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub disabled_eve_types: Vec<String>,
+    /// Only log these EVE types, stats are left to the stats
+    /// configuration.
+    #[serde(rename = "eve-types", default, skip_serializing_if = "Vec::is_empty")]
+    pub eve_types: Vec<String>,
+    /// Don't load any rules, for a sensor only logging flows.
+    #[serde(
+        rename = "no-rules",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub no_rules: bool,
+    /// Only log alerts to EVE.
+    #[serde(
+        rename = "alerts-only",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub alerts_only: bool,
+    /// Minutes between log rotation checks, defaults to 10.
+    #[serde(rename = "rotate-interval", skip_serializing_if = "Option::is_none")]
+    pub rotate_interval: Option<u32>,
+}
+
+impl TuningConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Capture settings for an interface, for jumbo frames and tunnel
+/// interfaces where the defaults truncate packets.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct InterfaceSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snaplen: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+}
+
+/// Full packet capture to a ring buffer of pcap files.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct PcapLogConfig {
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub enabled: bool,
+    /// Size of each pcap file, such as "100mb".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<String>,
+    /// Number of pcap files to keep, the oldest is removed when a new
+    /// one is started.
+    #[serde(rename = "max-files", skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<u32>,
+}
+
+impl PcapLogConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Suricata stats output. The EVE stats records are a large part of
+/// the log volume on small sensors.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct StatsConfig {
+    /// Seconds between stats updates, Suricata defaults to 8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u32>,
+    /// Write stats to stats.log.
+    #[serde(rename = "stats-log", skip_serializing_if = "Option::is_none")]
+    pub stats_log: Option<bool>,
+    /// Write stats records to eve.json.
+    #[serde(rename = "eve-stats", skip_serializing_if = "Option::is_none")]
+    pub eve_stats: Option<bool>,
+}
+
+impl StatsConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
 pub(crate) struct EveBoxConfig {
     #[serde(rename = "allow-remote")]
     pub allow_remote: bool,
@@ -39,6 +702,193 @@ pub(crate) struct EveBoxConfig {
     pub no_auth: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image: Option<String>,
+    /// EveBox configuration file on the host, for settings such as
+    /// alert notifications and webhooks. It is mounted into the
+    /// container so it survives the container being recreated.
+    #[serde(rename = "config-file", skip_serializing_if = "Option::is_none")]
+    pub config_file: Option<String>,
+    /// Restart policy of the container, overriding the global one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RestartPolicy>,
+    /// Memory limit for the container, such as "4g".
+    #[serde(rename = "memory-limit", skip_serializing_if = "Option::is_none")]
+    pub memory_limit: Option<String>,
+    /// Number of CPUs the container may use, such as "2" or "1.5".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<String>,
+    /// Sensor-only mode: run the EveBox agent forwarding events to a
+    /// remote EveBox server instead of a local server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent: Option<EveBoxAgentConfig>,
+    /// Certificate to serve instead of the self-signed one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<EveBoxTlsConfig>,
+    /// URL EveBox is advertised at, such as in the status and startup
+    /// banner, instead of one guessed from the interfaces.
+    #[serde(rename = "external-url", skip_serializing_if = "Option::is_none")]
+    pub external_url: Option<String>,
+    /// EveBox alerting settings, written to the EveBox configuration
+    /// file so they survive the container being recreated.
+    #[serde(default, skip_serializing_if = "EveBoxAlertingConfig::is_default")]
+    pub alerting: EveBoxAlertingConfig,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct EveBoxAlertingConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<EveBoxWebhook>,
+}
+
+impl EveBoxAlertingConfig {
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A webhook EveBox posts alerts to.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct EveBoxWebhook {
+    /// URL to post to, may be a secret:<key> reference as webhook URLs
+    /// often contain a token.
+    pub url: String,
+    /// Only post alerts of this severity or more severe, 1 being the
+    /// most severe.
+    #[serde(rename = "min-severity", skip_serializing_if = "Option::is_none")]
+    pub min_severity: Option<u8>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct EveBoxTlsConfig {
+    /// Server certificate, in PEM format.
+    pub cert: String,
+    /// Server key, in PEM format.
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct EveBoxAgentConfig {
+    /// URL of the remote EveBox server, for example
+    /// https://evebox.example.com:5636.
+    pub server: String,
+    /// API key for the remote server, may be a secret:<key> reference.
+    #[serde(rename = "api-key", skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+}
+
+/// What happens to a container when it exits, applied by the container
+/// engine to the detached containers and by `start --debug` in the
+/// foreground.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum RestartPolicy {
+    /// Leave the container stopped.
+    #[default]
+    No,
+    /// Restart the container if it exits with an error.
+    OnFailure,
+    /// Restart the container unless stopped with `simple-ids stop`.
+    UnlessStopped,
+    /// Always restart the container, `simple-ids stop` leaves it running
+    /// unless given `--all`.
+    Always,
+}
+
+impl RestartPolicy {
+    /// The value for the `--restart` option of the container engine.
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            RestartPolicy::No => "no",
+            RestartPolicy::OnFailure => "on-failure",
+            RestartPolicy::UnlessStopped => "unless-stopped",
+            RestartPolicy::Always => "always",
+        }
+    }
+
+    /// Whether the container engine brings the container back after a
+    /// reboot.
+    pub(crate) fn persists(&self) -> bool {
+        matches!(self, RestartPolicy::UnlessStopped | RestartPolicy::Always)
+    }
+
+    /// Whether a container that exited, successfully or not, should be
+    /// restarted.
+    pub(crate) fn restarts(&self, success: bool) -> bool {
+        match self {
+            RestartPolicy::No => false,
+            RestartPolicy::OnFailure => !success,
+            RestartPolicy::UnlessStopped | RestartPolicy::Always => true,
+        }
+    }
+}
+
+/// The container runtime to use.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Runtime {
+    /// Docker if found, otherwise Podman.
+    #[default]
+    Auto,
+    Docker,
+    Podman,
+}
+
+impl Runtime {
+    fn is_auto(&self) -> bool {
+        *self == Runtime::Auto
+    }
+}
+
+impl std::fmt::Display for Runtime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Runtime::Auto => "auto",
+            Runtime::Docker => "docker",
+            Runtime::Podman => "podman",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Overrides for the container engine command timeouts, in seconds.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct TimeoutConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub control: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pull: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run: Option<u64>,
+}
+
+impl TimeoutConfig {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Logging options for the Suricata and EveBox containers.
+///
+/// Without any options the container logs are limited in size, as the
+/// default json-file logs are never rotated.
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema, Clone, Eq, PartialEq)]
+pub(crate) struct ContainerLogConfig {
+    /// Log driver, for example "json-file" or "journald".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub driver: Option<String>,
+    /// Maximum size of a log file before it is rotated, for example "50m".
+    #[serde(rename = "max-size", skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+    /// Number of rotated log files to keep. Docker only.
+    #[serde(rename = "max-file", skip_serializing_if = "Option::is_none")]
+    pub max_file: Option<u32>,
+}
+
+impl ContainerLogConfig {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
 }
 
 impl Default for EveBoxConfig {
@@ -48,6 +898,14 @@ impl Default for EveBoxConfig {
             no_tls: true,
             no_auth: true,
             image: None,
+            config_file: None,
+            restart: None,
+            memory_limit: None,
+            cpus: None,
+            agent: None,
+            tls: None,
+            external_url: None,
+            alerting: EveBoxAlertingConfig::default(),
         }
     }
 }
@@ -75,19 +933,233 @@ impl Config {
         Self::default()
     }
 
+    /// Save the configuration.
+    ///
+    /// The configuration is written to a temporary file that replaces
+    /// the current file once fully written, so a crash can't leave a
+    /// partially written config behind. The previous version is kept
+    /// as a backup.
     pub(crate) fn save(&self) -> Result<()> {
-        let mut file = std::fs::File::create(TOML_FILENAME)?;
+        if READ_ONLY.load(Ordering::Relaxed) {
+            bail!("Not saving the configuration in read-only mode");
+        }
         let config = toml::to_string(self)?;
+        let mut file = tempfile::NamedTempFile::new_in(".")?;
         file.write_all(config.as_bytes())?;
+        file.as_file().sync_all()?;
+
+        if let Ok(metadata) = std::fs::metadata(TOML_FILENAME) {
+            // Keep the mode and ownership of the existing file, such as
+            // a configuration only readable by root, as the temporary
+            // file is created with its own.
+            std::fs::set_permissions(file.path(), metadata.permissions())?;
+            let created = file.as_file().metadata()?;
+            if created.uid() != metadata.uid() || created.gid() != metadata.gid() {
+                if let Err(err) = std::os::unix::fs::fchown(
+                    file.as_file(),
+                    Some(metadata.uid()),
+                    Some(metadata.gid()),
+                ) {
+                    warn!("Failed to keep the owner of {TOML_FILENAME}: {err}");
+                }
+            }
+            std::fs::copy(TOML_FILENAME, TOML_BACKUP_FILENAME)?;
+        }
+        file.persist(TOML_FILENAME)?;
+        if let Ok(dir) = std::fs::File::open(".") {
+            let _ = dir.sync_all();
+        }
 
-        // Delete YAML_FILENAME if exists.
+        // The YAML configuration is no longer used, keep it aside in
+        // case it had settings the TOML configuration doesn't.
         if std::fs::metadata(YAML_FILENAME).is_ok() {
-            std::fs::remove_file(YAML_FILENAME)?;
+            std::fs::rename(YAML_FILENAME, YAML_BACKUP_FILENAME)?;
         }
 
         Ok(())
     }
 
+    /// Check if both a TOML and a YAML configuration exist with
+    /// different settings, where only the TOML configuration is used.
+    pub(crate) fn conflict() -> Option<Conflict> {
+        let toml = Self::parse_toml(&Self::read_file(TOML_FILENAME).ok()?).ok()?;
+        let yaml = Self::parse_yaml(&Self::read_file(YAML_FILENAME).ok()?).ok()?;
+        if toml == yaml {
+            None
+        } else {
+            Some(Conflict { toml, yaml })
+        }
+    }
+
+    /// Convert the YAML configuration to TOML, keeping the YAML file as
+    /// a backup. Returns false if there is no YAML configuration.
+    pub(crate) fn migrate() -> Result<bool> {
+        let buf = match Self::read_file(YAML_FILENAME) {
+            Ok(buf) => buf,
+            Err(_) => return Ok(false),
+        };
+        Self::parse_yaml(&buf)?.save()?;
+        Ok(true)
+    }
+
+    /// The restart policy of a container, its own if set, otherwise the
+    /// global one.
+    pub(crate) fn restart_policy(&self, container: Container) -> RestartPolicy {
+        match container {
+            Container::Suricata => self.suricata.restart,
+            Container::EveBox => self.evebox.restart,
+        }
+        .or(self.restart)
+        .unwrap_or_default()
+    }
+
+    /// Return a fingerprint of the parts of the configuration that are
+    /// used to create a container. A running container with a different
+    /// fingerprint needs to be restarted to pick up changes.
+    ///
+    /// Settings are hashed in their serialized form, which leaves out
+    /// the unset ones, so adding a setting doesn't change the
+    /// fingerprint of a configuration that doesn't use it.
+    pub(crate) fn fingerprint(&self, container: Container) -> String {
+        fn serialized<T: Serialize>(value: &T) -> String {
+            serde_json::to_string(value).unwrap_or_default()
+        }
+
+        let mut buf = match container {
+            Container::Suricata => {
+                let contents = self
+                    .suricata
+                    .config_file
+                    .as_ref()
+                    .and_then(|path| std::fs::read_to_string(path).ok());
+                format!(
+                    "{}{}{:?}",
+                    serialized(&self.suricata),
+                    serialized(&self.container_logs),
+                    contents
+                )
+            }
+            Container::EveBox => {
+                // Include the contents of the EveBox configuration file
+                // so edits to it are picked up as requiring a restart.
+                let contents = self
+                    .evebox
+                    .config_file
+                    .as_ref()
+                    .and_then(|path| std::fs::read_to_string(path).ok());
+                // The advertised URL doesn't change the container.
+                let evebox = EveBoxConfig {
+                    external_url: None,
+                    ..self.evebox.clone()
+                };
+                let mut buf = format!(
+                    "{}{}{:?}",
+                    serialized(&evebox),
+                    serialized(&self.container_logs),
+                    contents
+                );
+                if !self.podman.is_default() {
+                    buf.push_str(&serialized(&self.podman));
+                }
+                buf
+            }
+        };
+        if let Some(restart) = self.restart {
+            buf.push_str(&serialized(&restart));
+        }
+        if self.display.container_timezone {
+            buf.push_str("container-timezone");
+        }
+        let hash = Sha256::digest(buf.as_bytes());
+        format!("{:x}", hash)[..16].to_string()
+    }
+
+    /// JSON Schema for the configuration file. Unknown keys are not
+    /// allowed by the schema so editors and CI catch typos, such as
+    /// `allow_remote` for `allow-remote`.
+    pub(crate) fn schema() -> serde_json::Value {
+        fn deny_unknown(value: &mut serde_json::Value) {
+            match value {
+                serde_json::Value::Object(object) => {
+                    if object.contains_key("properties") {
+                        object
+                            .entry("additionalProperties")
+                            .or_insert(serde_json::Value::Bool(false));
+                    }
+                    object.values_mut().for_each(deny_unknown);
+                }
+                serde_json::Value::Array(array) => array.iter_mut().for_each(deny_unknown),
+                _ => {}
+            }
+        }
+        let mut schema = serde_json::to_value(schemars::schema_for!(Config)).unwrap_or_default();
+        deny_unknown(&mut schema);
+        schema
+    }
+
+    /// Read the contents of the configuration file in use.
+    pub(crate) fn read_current() -> Option<String> {
+        Self::read_file(TOML_FILENAME)
+            .or_else(|_| Self::read_file(YAML_FILENAME))
+            .ok()
+    }
+
+    /// Return true if a configuration file exists.
+    pub(crate) fn exists() -> bool {
+        std::fs::metadata(TOML_FILENAME).is_ok() || std::fs::metadata(YAML_FILENAME).is_ok()
+    }
+
+    /// Load a configuration from a file or an http(s) URL.
+    ///
+    /// TOML is tried first, then YAML.
+    pub(crate) fn load(source: &str) -> Result<Config> {
+        let buf = if source.starts_with("http://") || source.starts_with("https://") {
+            let response = reqwest::blocking::get(source)?;
+            if !response.status().is_success() {
+                bail!("HTTP status code {}", response.status());
+            }
+            response.text()?
+        } else {
+            Self::read_file(source)?
+        };
+        match Self::parse_toml(&buf) {
+            Ok(config) => Ok(config),
+            Err(toml_err) => match Self::parse_yaml(&buf) {
+                Ok(config) => Ok(config),
+                Err(_) => Err(toml_err),
+            },
+        }
+    }
+
+    /// Return a copy of the configuration with the defaults filled in,
+    /// showing exactly what will be used.
+    pub(crate) fn with_defaults(&self) -> Config {
+        let mut config = self.clone();
+        config.suricata.image = Some(context::image_name(self, Container::Suricata));
+        config.evebox.image = Some(context::image_name(self, Container::EveBox));
+
+        let timeouts = container::timeouts();
+        config
+            .timeouts
+            .query
+            .get_or_insert(timeouts.query.as_secs());
+        config
+            .timeouts
+            .control
+            .get_or_insert(timeouts.control.as_secs());
+        config.timeouts.pull.get_or_insert(timeouts.pull.as_secs());
+
+        config
+            .container_logs
+            .max_size
+            .get_or_insert_with(|| container::DEFAULT_LOG_MAX_SIZE.to_string());
+        config
+            .container_logs
+            .max_file
+            .get_or_insert(container::DEFAULT_LOG_MAX_FILE);
+        config
+    }
+
     fn read_file(filename: &str) -> Result<String> {
         let mut file = std::fs::File::open(filename)?;
         let mut buffer = String::new();
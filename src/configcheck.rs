@@ -0,0 +1,140 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Find unknown keys in the configuration file, which serde otherwise
+//! silently ignores, by checking the file against the JSON Schema.
+
+use serde_json::Value;
+
+use crate::config::Config;
+
+/// An unknown key, with the dotted path to it.
+#[derive(Debug)]
+pub(crate) struct UnknownKey {
+    pub path: String,
+    pub suggestion: Option<String>,
+}
+
+impl std::fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown configuration key {}", self.path)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", did you mean {suggestion}?")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a TOML or YAML configuration into a generic value.
+fn parse(buf: &str) -> Option<Value> {
+    if let Ok(value) = toml::from_str::<toml::Value>(buf) {
+        return serde_json::to_value(value).ok();
+    }
+    serde_yaml::from_str::<Value>(buf).ok()
+}
+
+/// Return the unknown keys in a configuration file.
+pub(crate) fn unknown_keys(buf: &str) -> Vec<UnknownKey> {
+    let mut unknown = vec![];
+    if let Some(value) = parse(buf) {
+        let schema = Config::schema();
+        walk(&value, &schema, &schema, "", &mut unknown);
+    }
+    unknown
+}
+
+/// Resolve references and the wrappers schemars uses for optional and
+/// documented fields to the schema describing an object, if any.
+fn resolve<'a>(node: &'a Value, root: &'a Value) -> &'a Value {
+    if let Some(reference) = node["$ref"].as_str() {
+        if let Some(name) = reference.strip_prefix("#/definitions/") {
+            return resolve(&root["definitions"][name], root);
+        }
+    }
+    for key in ["allOf", "anyOf", "oneOf"] {
+        if let Some(options) = node[key].as_array() {
+            for option in options {
+                let option = resolve(option, root);
+                if option.get("properties").is_some() || option["additionalProperties"].is_object()
+                {
+                    return option;
+                }
+            }
+        }
+    }
+    node
+}
+
+fn walk(value: &Value, node: &Value, root: &Value, path: &str, unknown: &mut Vec<UnknownKey>) {
+    let node = resolve(node, root);
+    match value {
+        Value::Object(object) => {
+            let properties = node["properties"].as_object();
+            let additional = node["additionalProperties"].is_object();
+            for (key, value) in object {
+                let key_path = if path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{path}.{key}")
+                };
+                if let Some(child) = properties.and_then(|properties| properties.get(key)) {
+                    walk(value, child, root, &key_path, unknown);
+                } else if additional {
+                    walk(
+                        value,
+                        &node["additionalProperties"],
+                        root,
+                        &key_path,
+                        unknown,
+                    );
+                } else if let Some(properties) = properties {
+                    unknown.push(UnknownKey {
+                        path: key_path,
+                        suggestion: suggest(key, properties.keys()),
+                    });
+                }
+            }
+        }
+        Value::Array(array) => {
+            for (i, value) in array.iter().enumerate() {
+                walk(
+                    value,
+                    &node["items"],
+                    root,
+                    &format!("{path}[{i}]"),
+                    unknown,
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Find the known key closest to an unknown key.
+fn suggest<'a>(key: &str, known: impl Iterator<Item = &'a String>) -> Option<String> {
+    let normalized = key.to_lowercase().replace('_', "-");
+    known
+        .map(|candidate| (distance(&normalized, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+/// Levenshtein distance between two strings.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current.push(
+                (previous[j] + cost)
+                    .min(previous[j + 1] + 1)
+                    .min(current[j] + 1),
+            );
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,178 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Watch the configuration file while running, applying the changes
+//! that are safe to apply and letting the user know about the ones that
+//! need a restart. Containers started with an older configuration show
+//! as "restart required" in the status until restarted.
+
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{self, Read},
+    os::fd::FromRawFd,
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::{self, Config},
+    container::Container,
+    context::Context,
+    journal, notify,
+};
+
+/// Editors and the menus write the file in more than one step, wait for
+/// this long after a change for the writes to settle.
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// What differs between the running and the reloaded configuration.
+#[derive(Debug, Default)]
+pub(crate) struct Changes {
+    pub suricata: bool,
+    pub evebox: bool,
+    pub notifications: bool,
+}
+
+/// Watch the directory holding the configuration file with inotify,
+/// sending on the returned channel whenever the file changes. The
+/// directory is watched rather than the file, as the file is replaced
+/// on save.
+pub(crate) fn watch() -> Result<Receiver<()>> {
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        bail!(
+            "Failed to initialize inotify: {}",
+            io::Error::last_os_error()
+        );
+    }
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    let dir = CString::new(".")?;
+    let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO;
+    if unsafe { libc::inotify_add_watch(fd, dir.as_ptr(), mask) } < 0 {
+        bail!(
+            "Failed to watch the configuration directory: {}",
+            io::Error::last_os_error()
+        );
+    }
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = match file.read(&mut buf) {
+                Ok(n) => n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    error!("Failed to read configuration file changes: {err}");
+                    return;
+                }
+            };
+            if is_config_changed(&buf[..n]) && tx.send(()).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(rx)
+}
+
+/// Return true if any of the inotify events are for the configuration
+/// file.
+fn is_config_changed(mut events: &[u8]) -> bool {
+    let header = std::mem::size_of::<libc::inotify_event>();
+    let mut changed = false;
+    while events.len() >= header {
+        let event: libc::inotify_event =
+            unsafe { std::ptr::read_unaligned(events.as_ptr().cast()) };
+        let end = (header + event.len as usize).min(events.len());
+        let name = events[header..end]
+            .split(|b| *b == 0)
+            .next()
+            .unwrap_or_default();
+        if name == config::TOML_FILENAME.as_bytes() || name == config::YAML_FILENAME.as_bytes() {
+            changed = true;
+        }
+        events = &events[end..];
+    }
+    changed
+}
+
+/// Wait for the writes to settle after a change, dropping the change
+/// notifications that arrive in the meantime.
+pub(crate) fn settle(changes: &Receiver<()>) {
+    thread::sleep(SETTLE);
+    while changes.try_recv().is_ok() {}
+}
+
+/// Reload the configuration into the context, returning what changed.
+/// Reloaded notification settings take effect right away.
+pub(crate) fn reload(context: &mut Context) -> Changes {
+    let config = Config::new();
+    let changes = Changes {
+        suricata: config.fingerprint(Container::Suricata)
+            != context.config.fingerprint(Container::Suricata),
+        evebox: config.fingerprint(Container::EveBox)
+            != context.config.fingerprint(Container::EveBox),
+        notifications: config.notifications != context.config.notifications,
+    };
+    debug!("Configuration file changed: {changes:?}");
+    if changes.notifications {
+        notify::reload(&config.notifications);
+        info!("Reloaded the notification settings");
+    }
+    *context = Context::new(config, context.manager.clone());
+    changes
+}
+
+/// Apply a changed configuration to detached containers. EveBox is
+/// restarted with the new configuration, as that only interrupts the
+/// web interface; a restart of Suricata stops the capture, so is left
+/// to the user.
+pub(crate) fn apply_detached(context: &mut Context) {
+    let changes = reload(context);
+    if changes.suricata {
+        warn!("Configuration changed, Suricata must be restarted to apply it");
+    }
+    if changes.evebox && context.manager.state(crate::EVEBOX_CONTAINER_NAME).is_ok() {
+        let message = match crate::start_evebox_detached(context) {
+            Ok(()) => {
+                info!("Configuration changed, restarted EveBox to apply it");
+                "EveBox restarted to apply a configuration change".to_string()
+            }
+            Err(err) => {
+                error!("Failed to restart EveBox to apply a configuration change: {err}");
+                format!("EveBox failed to restart to apply a configuration change: {err}")
+            }
+        };
+        journal::record(journal::Kind::ConfigReload, message);
+    }
+}
+
+/// Let the user know about changes to the configuration while running
+/// in the foreground, where the containers are supervised with the
+/// configuration they were started with.
+pub(crate) fn watch_foreground(context: &Context) {
+    let changes = match watch() {
+        Ok(changes) => changes,
+        Err(err) => {
+            warn!("Not watching the configuration file: {err}");
+            return;
+        }
+    };
+    let mut context = context.clone();
+    thread::spawn(move || {
+        while changes.recv().is_ok() {
+            settle(&changes);
+            let changed = reload(&mut context);
+            for (service, changed) in [("Suricata", changed.suricata), ("EveBox", changed.evebox)] {
+                if changed {
+                    warn!("Configuration changed, {service} must be restarted to apply it");
+                }
+            }
+        }
+    });
+}
@@ -3,18 +3,151 @@
 
 use anyhow::{bail, Result};
 use serde::Deserialize;
-use std::process::Command;
-use tracing::{debug, error, info};
+use std::{
+    collections::HashMap,
+    future::Future,
+    process::{Command, ExitStatus, Output, Stdio},
+    sync::OnceLock,
+    task::Poll,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt},
+    runtime::Handle,
+    sync::watch,
+    task::JoinHandle,
+};
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    context::Context, EVEBOX_VOLUME_LIB, SURICATA_VOLUME_LIB, SURICATA_VOLUME_LOG,
-    SURICATA_VOLUME_RUN,
+    config::{ContainerLogConfig, PodmanConfig, RestartPolicy, Runtime, TimeoutConfig},
+    context::Context,
+    EVEBOX_CONTAINER_NAME, EVEBOX_VOLUME_LIB, SURICATA_CONTAINER_NAME, SURICATA_VOLUME_LIB,
+    SURICATA_VOLUME_LOG, SURICATA_VOLUME_RUN,
 };
 
 pub const DEFAULT_SURICATA_IMAGE: &str = "docker.io/jasonish/suricata:latest";
 pub const DEFAULT_EVEBOX_IMAGE: &str = "docker.io/jasonish/evebox:master";
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+static TIMEOUTS: OnceLock<Timeouts> = OnceLock::new();
+
+/// Timeouts for container engine commands, so a hung daemon doesn't
+/// hang Simple-IDS forever.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Timeouts {
+    /// Quick queries like version and inspect.
+    pub query: Duration,
+    /// Starting, stopping and removing containers.
+    pub control: Duration,
+    /// Pulling images.
+    pub pull: Duration,
+    /// One-off containers, such as a rule update or reading a pcap,
+    /// which may have to pull the image first.
+    pub run: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            query: Duration::from_secs(15),
+            control: Duration::from_secs(60),
+            pull: Duration::from_secs(900),
+            run: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl Timeouts {
+    fn from_config(config: &TimeoutConfig) -> Self {
+        let default = Self::default();
+        Self {
+            query: config
+                .query
+                .map(Duration::from_secs)
+                .unwrap_or(default.query),
+            control: config
+                .control
+                .map(Duration::from_secs)
+                .unwrap_or(default.control),
+            pull: config.pull.map(Duration::from_secs).unwrap_or(default.pull),
+            run: config.run.map(Duration::from_secs).unwrap_or(default.run),
+        }
+    }
+}
+
+/// Set the container engine timeouts from the configuration.
+///
+/// Only the first call has any effect.
+pub(crate) fn set_timeouts(config: &TimeoutConfig) {
+    let _ = TIMEOUTS.set(Timeouts::from_config(config));
+}
+
+pub(crate) fn timeouts() -> Timeouts {
+    TIMEOUTS.get().copied().unwrap_or_default()
+}
+
+/// Features that vary by container engine and version, so they can be
+/// skipped with a clear message instead of failing with an engine
+/// error.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub(crate) struct Capabilities {
+    /// Custom signals with `stop --signal`.
+    pub stop_signal: bool,
+    /// `logs --since`.
+    pub logs_since: bool,
+    /// The `max-file` log option.
+    pub log_max_file: bool,
+}
+
+impl Capabilities {
+    /// Capabilities assumed when the version is unknown.
+    fn minimal() -> Self {
+        Self {
+            stop_signal: false,
+            logs_since: true,
+            log_max_file: false,
+        }
+    }
+
+    fn docker(version: &semver::Version) -> Self {
+        Self {
+            stop_signal: version.major >= 23,
+            logs_since: true,
+            log_max_file: true,
+        }
+    }
+
+    fn podman(_version: &semver::Version) -> Self {
+        Self {
+            // Podman stop uses the stop signal of the container.
+            stop_signal: false,
+            logs_since: true,
+            // The k8s-file driver doesn't limit the number of files.
+            log_max_file: false,
+        }
+    }
+}
+
+/// Minimum supported Podman version.
+const PODMAN_MIN_VERSION: semver::Version = semver::Version::new(4, 6, 0);
+
+/// Parse an engine version, allowing for distribution suffixes like
+/// "20.10.21+dfsg1" or "4.9.3-dev" and missing components.
+fn parse_version(version: &str) -> Option<semver::Version> {
+    if let Ok(version) = semver::Version::parse(version) {
+        return Some(version);
+    }
+    let mut parts = version
+        .split(|c: char| !c.is_ascii_digit())
+        .take(3)
+        .map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().flatten().unwrap_or(0);
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some(semver::Version::new(major, minor, patch))
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub(crate) enum ContainerManager {
     Docker(DockerManager),
     Podman(PodmanManager),
@@ -26,13 +159,30 @@ impl std::fmt::Display for ContainerManager {
             ContainerManager::Docker(_) => "Docker",
             ContainerManager::Podman(_) => "Podman",
         };
-        write!(f, "{name}")
+        match self.host() {
+            Some(host) => write!(f, "{name} on {host}"),
+            None => write!(f, "{name}"),
+        }
     }
 }
 
 impl ContainerManager {
     pub(crate) fn command(&self) -> Command {
-        Command::new(self.bin())
+        let mut command = Command::new(self.bin());
+        match self {
+            Self::Docker(docker) => command.args(docker.remote_args()),
+            Self::Podman(podman) => command.args(podman.remote_args()),
+        };
+        command
+    }
+
+    /// The remote host the container engine is being accessed on over
+    /// SSH, None if local.
+    pub(crate) fn host(&self) -> Option<&str> {
+        match self {
+            Self::Docker(docker) => docker.host.as_deref(),
+            Self::Podman(podman) => podman.host.as_deref(),
+        }
     }
 
     pub(crate) fn bin(&self) -> &str {
@@ -45,27 +195,66 @@ impl ContainerManager {
     /// Test if a container manager exists.
     pub(crate) fn exists(&self) -> bool {
         Command::new(self.bin())
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
+            .output_timeout(timeouts().query)
             .is_ok()
     }
 
+    pub(crate) fn capabilities(&self) -> Capabilities {
+        match self {
+            Self::Docker(docker) => docker.capabilities,
+            Self::Podman(podman) => podman.capabilities,
+        }
+    }
+
+    fn set_capabilities(&mut self, capabilities: Capabilities) {
+        match self {
+            Self::Docker(docker) => docker.capabilities = capabilities,
+            Self::Podman(podman) => podman.capabilities = capabilities,
+        }
+    }
+
+    /// The log driver containers get when none is given, None if it
+    /// can't be found.
+    pub(crate) fn default_log_driver(&self) -> Option<String> {
+        let format = if self.is_podman() {
+            "{{.Host.LogDriver}}"
+        } else {
+            "{{.LoggingDriver}}"
+        };
+        let output = self
+            .command()
+            .args(["info", "--format", format])
+            .output_timeout(timeouts().query)
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let driver = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (!driver.is_empty()).then_some(driver)
+    }
+
+    /// The value for the `--restart` option of a policy. Podman has no
+    /// daemon and its podman-restart service only starts containers with
+    /// the always policy on boot, so unless-stopped is run as always.
+    /// Stopped containers are removed, so they still stay stopped.
+    pub(crate) fn restart_arg(&self, policy: RestartPolicy) -> &'static str {
+        if self.is_podman() && policy == RestartPolicy::UnlessStopped {
+            RestartPolicy::Always.as_str()
+        } else {
+            policy.as_str()
+        }
+    }
+
     /// Return true if the container manager is Podman.
     pub(crate) fn is_podman(&self) -> bool {
         matches!(self, ContainerManager::Podman(_))
     }
 
-    /// Return true if the container manager is Docker.
-    pub(crate) fn is_docker(&self) -> bool {
-        matches!(self, ContainerManager::Docker(_))
-    }
-
     pub(crate) fn version(&self) -> Result<String> {
         let output = self
             .command()
             .args(["version", "--format", "{{json . }}"])
-            .output()?;
+            .output_timeout(timeouts().query)?;
         if !output.status.success() {
             bail!(String::from_utf8_lossy(&output.stderr).to_string());
         } else if let Ok(json) = serde_json::from_slice::<serde_json::Value>(&output.stdout) {
@@ -93,32 +282,151 @@ impl ContainerManager {
         }
 
         args.push(name);
-        let _ = self.command().args(&args).output();
+        let _ = self
+            .command()
+            .args(&args)
+            .output_timeout(timeouts().control);
     }
 
     pub(crate) fn stop(&self, name: &str, signal: Option<&str>) -> Result<()> {
         let mut cmd = self.command();
         cmd.arg("stop");
 
-        // Custom stop signals are not supported on Podman.
-        if self.is_docker() {
+        if self.capabilities().stop_signal {
             cmd.args(["--signal", signal.unwrap_or("SIGTERM")]);
+        } else if let Some(signal) = signal {
+            debug!("{self} does not support stop signals, not sending {signal} to {name}");
         }
         cmd.arg(name);
-        let output = cmd.output()?;
+        let output = cmd.output_timeout(timeouts().control)?;
         if !output.status.success() {
             bail!(String::from_utf8_lossy(&output.stderr).to_string());
         }
         Ok(())
     }
 
-    pub(crate) fn pull(&self, image: &str) -> Result<()> {
-        let status = self.command().args(["pull", image]).status()?;
-        if status.success() {
-            Ok(())
-        } else {
-            bail!("Pull did not exit successfully")
+    /// Freeze the processes of a container, keeping the container and
+    /// its logs.
+    pub(crate) fn pause(&self, name: &str) -> Result<()> {
+        self.control(&["pause", name])
+    }
+
+    pub(crate) fn unpause(&self, name: &str) -> Result<()> {
+        self.control(&["unpause", name])
+    }
+
+    fn control(&self, args: &[&str]) -> Result<()> {
+        let output = self
+            .command()
+            .args(args)
+            .output_timeout(timeouts().control)?;
+        if !output.status.success() {
+            bail!(String::from_utf8_lossy(&output.stderr).trim().to_string());
         }
+        Ok(())
+    }
+
+    /// Whether a container exists and is paused.
+    pub(crate) fn is_paused(&self, name: &str) -> bool {
+        self.state(name)
+            .map(|state| state.status == "paused")
+            .unwrap_or(false)
+    }
+
+    /// Remove a volume, it is not an error if the volume doesn't exist.
+    pub(crate) fn remove_volume(&self, name: &str) -> Result<()> {
+        let output = self
+            .command()
+            .args(["volume", "rm", name])
+            .output_timeout(timeouts().control)?;
+        if !output.status.success() && self.volume_exists(name) {
+            bail!(String::from_utf8_lossy(&output.stderr).to_string());
+        }
+        Ok(())
+    }
+
+    /// Return the size in bytes of each of the given volumes that
+    /// exist, measured with `du` in a temporary container from the given
+    /// image.
+    pub(crate) fn volume_sizes(&self, image: &str, volumes: &[&str]) -> Result<Vec<(String, u64)>> {
+        let existing: Vec<&str> = volumes
+            .iter()
+            .copied()
+            .filter(|volume| self.volume_exists(volume))
+            .collect();
+        if existing.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut command = self.command();
+        command.args(["run", "--rm", "--entrypoint", "du"]);
+        for volume in &existing {
+            command.arg(format!("--volume={volume}:/volumes/{volume}:ro"));
+        }
+        command.args([image, "-sb"]);
+        for volume in &existing {
+            command.arg(format!("/volumes/{volume}"));
+        }
+        let output = command.output_timeout(timeouts().control)?;
+        let sizes: Vec<(String, u64)> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let (size, path) = line.split_once('\t')?;
+                let name = path.strip_prefix("/volumes/")?;
+                Some((name.to_string(), size.parse().ok()?))
+            })
+            .collect();
+        if sizes.is_empty() {
+            bail!(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(sizes)
+    }
+
+    fn volume_exists(&self, name: &str) -> bool {
+        self.command()
+            .args(["volume", "inspect", name])
+            .output_timeout(timeouts().query)
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
+    /// Remove an image, it is not an error if the image doesn't exist.
+    pub(crate) fn remove_image(&self, name: &str) -> Result<()> {
+        let output = self
+            .command()
+            .args(["rmi", name])
+            .output_timeout(timeouts().control)?;
+        if !output.status.success() && self.has_image(name) {
+            bail!(String::from_utf8_lossy(&output.stderr).to_string());
+        }
+        Ok(())
+    }
+
+    /// Pull an image, showing the progress with each line prefixed by
+    /// the image name so multiple pulls can run at the same time.
+    pub(crate) async fn pull(&self, image: &str) -> Result<()> {
+        let mut command = self.command();
+        command.args(["pull", image]);
+        let describe = describe(&command);
+        let mut command = take_command(&mut command);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let name = image.rsplit('/').next().unwrap_or(image);
+        let (status, stderr) = guard(&describe, timeouts().pull, async {
+            let mut child = command.spawn()?;
+            // Docker writes the progress to stdout, Podman to stderr.
+            let stdout = tokio::spawn(print_lines(child.stdout.take(), name.to_string()));
+            let stderr = tokio::spawn(print_lines(child.stderr.take(), name.to_string()));
+            let status = child.wait().await?;
+            let _ = stdout.await;
+            Ok((status, stderr.await.unwrap_or_default()))
+        })
+        .await?;
+        if !status.success() {
+            bail!(stderr.join("\n"));
+        }
+        Ok(())
     }
 
     pub(crate) fn inspect_first(&self, name: &str) -> Result<InspectEntry> {
@@ -153,57 +461,167 @@ impl ContainerManager {
         }
     }
 
+    /// Return the number of times the container engine has restarted a
+    /// container.
+    pub(crate) fn restart_count(&self, name: &str) -> Option<u64> {
+        self.inspect_first(name).ok()?.restart_count
+    }
+
+    /// How Podman networks the containers, None for Docker or if it
+    /// can't be found.
+    pub(crate) fn podman_network_info(&self) -> Option<PodmanNetworkInfo> {
+        if !self.is_podman() {
+            return None;
+        }
+        let output = self
+            .command()
+            .args(["info", "--format", "json"])
+            .output_timeout(timeouts().query)
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+        let host = &json["host"];
+        Some(PodmanNetworkInfo {
+            rootless: host["security"]["rootless"].as_bool().unwrap_or(false),
+            backend: host["networkBackend"].as_str().map(String::from),
+            rootless_cmd: host["rootlessNetworkCmd"].as_str().map(String::from),
+        })
+    }
+
+    /// Return the ID of an image, which changes when it is updated.
+    pub(crate) fn image_id(&self, image: &str) -> Option<String> {
+        Some(self.inspect_first(image).ok()?.id)
+    }
+
+    /// Return the value of a label on a container or image.
+    pub(crate) fn label(&self, name: &str, label: &str) -> Option<String> {
+        self.inspect_first(name).ok()?.config?.labels?.remove(label)
+    }
+
+    /// Return the Inspect.State for multiple containers with a single
+    /// inspect command.
+    ///
+    /// Containers that don't exist are left out of the returned map.
+    pub(crate) fn states(&self, names: &[&str]) -> HashMap<String, InspectState> {
+        let mut states = HashMap::new();
+        let output = match self
+            .command()
+            .arg("inspect")
+            .args(names)
+            .output_timeout(timeouts().query)
+        {
+            Ok(output) => output,
+            Err(err) => {
+                debug!("Failed to inspect containers: {err}");
+                return states;
+            }
+        };
+
+        // A missing container results in a non-zero exit code, but
+        // the containers that were found are still output.
+        let entries: Vec<InspectEntry> = serde_json::from_slice(&output.stdout).unwrap_or_default();
+        for entry in entries {
+            if let (Some(name), Some(state)) = (entry.name, entry.state) {
+                states.insert(name.trim_start_matches('/').to_string(), state);
+            }
+        }
+        states
+    }
+
     /// Test if a container exists.
     ///
     /// Any failure results in false.
     pub(crate) fn container_exists(&self, name: &str) -> bool {
-        if let Ok(output) = self.command().args(["inspect", name]).output() {
+        if let Ok(output) = self
+            .command()
+            .args(["inspect", name])
+            .output_timeout(timeouts().query)
+        {
             return output.status.success();
         }
         false
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) struct PodmanManager {}
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct PodmanManager {
+    host: Option<String>,
+    capabilities: Capabilities,
+}
 
 impl PodmanManager {
-    pub(crate) fn new() -> Self {
-        Self {}
+    pub(crate) fn new(host: Option<String>) -> Self {
+        Self {
+            host,
+            capabilities: Capabilities::minimal(),
+        }
     }
 
     pub(crate) fn bin(&self) -> &str {
         "podman"
     }
+
+    /// Podman connects to the rootful Podman socket on the remote host.
+    fn remote_args(&self) -> Vec<String> {
+        match &self.host {
+            Some(host) => vec![format!("--url=ssh://{}/run/podman/podman.sock", host)],
+            None => vec![],
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) struct DockerManager {}
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct DockerManager {
+    host: Option<String>,
+    capabilities: Capabilities,
+}
 
 impl DockerManager {
-    pub(crate) fn new() -> Self {
-        Self {}
+    pub(crate) fn new(host: Option<String>) -> Self {
+        Self {
+            host,
+            capabilities: Capabilities::minimal(),
+        }
     }
 
     pub(crate) fn bin(&self) -> &str {
         "docker"
     }
+
+    fn remote_args(&self) -> Vec<String> {
+        match &self.host {
+            Some(host) => vec![format!("--host=ssh://{}", host)],
+            None => vec![],
+        }
+    }
 }
 
 /// Command extensions useful for containers.
 pub(crate) trait CommandExt {
     /// Like `Command::output`, but return an error on command failure
-    /// as well as non-successful exit code.
-    fn status_output(&mut self) -> anyhow::Result<Vec<u8>>;
+    /// as well as non-successful exit code, or if the command doesn't
+    /// complete within the timeout.
+    fn status_output_timeout(&mut self, timeout: Duration) -> anyhow::Result<Vec<u8>>;
 
     /// Like `Command::status` but will also fail if the command did
-    /// not exit successfully.
-    fn status_ok(&mut self) -> Result<()>;
+    /// not exit successfully within the timeout. The standard streams
+    /// are inherited, for interactive commands.
+    fn status_ok_timeout(&mut self, timeout: Duration) -> Result<()>;
+
+    /// Like `status_ok_timeout`, but writing `input` to the standard
+    /// input of the command, such as a password.
+    fn status_ok_input_timeout(&mut self, input: &[u8], timeout: Duration) -> Result<()>;
+
+    /// Like `Command::output`, but kill the command and return an error
+    /// if it doesn't complete within the timeout.
+    fn output_timeout(&mut self, timeout: Duration) -> Result<Output>;
 }
 
 impl CommandExt for std::process::Command {
-    fn status_output(&mut self) -> Result<Vec<u8>> {
-        let output = self.output()?;
+    fn status_output_timeout(&mut self, timeout: Duration) -> Result<Vec<u8>> {
+        let output = self.output_timeout(timeout)?;
         if output.status.success() {
             Ok(output.stdout)
         } else {
@@ -211,20 +629,205 @@ impl CommandExt for std::process::Command {
         }
     }
 
-    fn status_ok(&mut self) -> Result<()> {
-        let status = self.status()?;
+    fn status_ok_timeout(&mut self, timeout: Duration) -> Result<()> {
+        let describe = describe(self);
+        let mut command = take_command(self);
+        let status = block_on(guard(&describe, timeout, async {
+            let mut child = command.spawn()?;
+            Ok(child.wait().await?)
+        }))?;
         if status.success() {
             Ok(())
         } else {
             bail!("Failed with exit code {:?}", status.code())
         }
     }
+
+    fn status_ok_input_timeout(&mut self, input: &[u8], timeout: Duration) -> Result<()> {
+        let describe = describe(self);
+        let mut command = take_command(self);
+        command.stdin(Stdio::piped());
+        let status = block_on(guard(&describe, timeout, async {
+            let mut child = command.spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(input).await?;
+            }
+            Ok(child.wait().await?)
+        }))?;
+        if status.success() {
+            Ok(())
+        } else {
+            bail!("Failed with exit code {:?}", status.code())
+        }
+    }
+
+    fn output_timeout(&mut self, timeout: Duration) -> Result<Output> {
+        let describe = describe(self);
+        let mut command = take_command(self);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        block_on(guard(&describe, timeout, async {
+            Ok(command.output().await?)
+        }))
+    }
+}
+
+/// The runtime the container engine commands run on, so several can be
+/// waited on at the same time, and with timeouts and cancellation.
+fn runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .expect("failed to create the async runtime")
+    })
+}
+
+/// Run a future to completion on the runtime from synchronous code,
+/// including from blocking tasks running on the runtime.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    match Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(future)),
+        Err(_) => runtime().block_on(future),
+    }
+}
+
+/// Run a future on the runtime, concurrently with others.
+pub(crate) fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    runtime().spawn(future)
+}
+
+/// Run a blocking operation, such as a sequence of engine commands, on
+/// the runtime so it can run concurrently with others.
+pub(crate) fn spawn_blocking<T, F>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    runtime().spawn_blocking(f)
+}
+
+/// Signalled when the user cancels, such as with Ctrl-C in the menus,
+/// so the commands being waited on at the time are killed instead of
+/// the menus exiting.
+fn cancellations() -> &'static watch::Sender<u64> {
+    static CANCELLATIONS: OnceLock<watch::Sender<u64>> = OnceLock::new();
+    CANCELLATIONS.get_or_init(|| watch::channel(0).0)
+}
+
+/// Cancel the commands being waited on.
+pub(crate) fn cancel() {
+    cancellations().send_modify(|count| *count += 1);
+}
+
+/// Await a command future, failing if it takes longer than the timeout
+/// or is cancelled. The child is killed as the future is dropped.
+async fn guard<T>(
+    describe: &str,
+    timeout: Duration,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let mut cancelled = cancellations().subscribe();
+    let mut future = std::pin::pin!(tokio::time::timeout(timeout, future));
+    let mut changed = std::pin::pin!(cancelled.changed());
+    let result = std::future::poll_fn(|cx| {
+        if let Poll::Ready(result) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(result));
+        }
+        if changed.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    })
+    .await;
+    match result {
+        Some(Ok(result)) => result,
+        Some(Err(_)) => bail!(
+            "Container engine not responding: {describe} timed out after {} seconds",
+            timeout.as_secs()
+        ),
+        None => bail!("Cancelled {describe}"),
+    }
+}
+
+/// The program and first argument of a command, for messages.
+fn describe(command: &Command) -> String {
+    format!(
+        "{} {}",
+        command.get_program().to_string_lossy(),
+        command
+            .get_args()
+            .next()
+            .map(|arg| arg.to_string_lossy())
+            .unwrap_or_default()
+    )
+}
+
+/// Take a command to run on the runtime, killed if its future is
+/// dropped. The commands are only run once, so an empty command is
+/// left in its place.
+fn take_command(command: &mut Command) -> tokio::process::Command {
+    let program = command.get_program().to_owned();
+    let mut command =
+        tokio::process::Command::from(std::mem::replace(command, Command::new(program)));
+    command.kill_on_drop(true);
+    command
+}
+
+/// Spawn a command passing each line of its output to `f`, killing it
+/// if it doesn't complete within the timeout or is cancelled. Without a
+/// timeout, such as when following a file, it runs until it exits or is
+/// cancelled.
+pub(crate) fn spawn_lines(
+    command: &mut Command,
+    timeout: Option<Duration>,
+    mut f: impl FnMut(&str),
+) -> Result<ExitStatus> {
+    let describe = describe(command);
+    let mut command = take_command(command);
+    command.stdout(Stdio::piped());
+    block_on(guard(&describe, timeout.unwrap_or(Duration::MAX), async {
+        let mut child = command.spawn()?;
+        if let Some(stdout) = child.stdout.take() {
+            let mut lines = tokio::io::BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                f(&line);
+            }
+        }
+        Ok(child.wait().await?)
+    }))
+}
+
+/// Print the lines of a reader prefixed with a name, returning them.
+async fn print_lines<R: AsyncRead + Unpin>(reader: Option<R>, name: String) -> Vec<String> {
+    let mut lines = vec![];
+    if let Some(reader) = reader {
+        let mut reader = tokio::io::BufReader::new(reader).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            println!("{name}: {line}");
+            lines.push(line);
+        }
+    }
+    lines
 }
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct InspectEntry {
     #[serde(rename = "Id")]
-    _id: String,
+    id: String,
+
+    // Only found when inspecting containers. Docker prefixes the name
+    // with a "/".
+    #[serde(rename = "Name")]
+    name: Option<String>,
 
     // Only found when inspecting containers.
     #[serde(rename = "State")]
@@ -233,9 +836,22 @@ pub(crate) struct InspectEntry {
     // Only found when inspecting images.
     #[serde(rename = "RepoTags")]
     _repo_tags: Option<Vec<String>>,
+
+    #[serde(rename = "Config")]
+    config: Option<InspectConfig>,
+
+    // Restarts done by the container engine, such as by a restart policy.
+    #[serde(rename = "RestartCount", default)]
+    restart_count: Option<u64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct InspectConfig {
+    #[serde(rename = "Labels")]
+    pub labels: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub(crate) struct InspectState {
     #[serde(rename = "Status")]
     pub status: String,
@@ -248,13 +864,77 @@ pub(crate) struct InspectState {
 
     #[serde(rename = "ExitCode")]
     pub _exit_code: i32,
+
+    #[serde(rename = "StartedAt", default)]
+    pub started_at: Option<String>,
+}
+
+impl InspectState {
+    /// How long the container has been running, None if not running.
+    pub(crate) fn uptime(&self) -> Option<chrono::Duration> {
+        if !self.running {
+            return None;
+        }
+        let started_at = chrono::DateTime::parse_from_rfc3339(self.started_at.as_deref()?).ok()?;
+        Some(chrono::Utc::now().signed_duration_since(started_at))
+    }
+}
+
+/// A short lived cache of container states.
+///
+/// Used by the menus so a redraw doesn't result in an inspect
+/// subprocess for every status check.
+pub(crate) struct StatusCache {
+    manager: ContainerManager,
+    names: Vec<&'static str>,
+    ttl: Duration,
+    entry: Option<(Instant, HashMap<String, InspectState>)>,
+}
+
+impl StatusCache {
+    pub(crate) fn new(manager: ContainerManager, names: &[&'static str]) -> Self {
+        Self {
+            manager,
+            names: names.to_vec(),
+            ttl: Duration::from_secs(2),
+            entry: None,
+        }
+    }
+
+    /// Force the next lookup to inspect the containers.
+    pub(crate) fn invalidate(&mut self) {
+        self.entry = None;
+    }
+
+    fn states(&mut self) -> &HashMap<String, InspectState> {
+        let expired = match &self.entry {
+            Some((fetched, _)) => fetched.elapsed() > self.ttl,
+            None => true,
+        };
+        if expired {
+            let states = self.manager.states(&self.names);
+            self.entry = Some((Instant::now(), states));
+        }
+        &self.entry.as_ref().unwrap().1
+    }
+
+    pub(crate) fn state(&mut self, name: &str) -> Option<InspectState> {
+        self.states().get(name).cloned()
+    }
+
+    pub(crate) fn is_running(&mut self, name: &str) -> bool {
+        self.states()
+            .get(name)
+            .map(|state| state.running)
+            .unwrap_or(false)
+    }
 }
 
 fn command_json<T>(command: &mut Command) -> Result<T>
 where
     T: serde::de::DeserializeOwned + std::fmt::Debug,
 {
-    let output = command.output()?;
+    let output = command.output_timeout(timeouts().query)?;
     if !output.status.success() {
         if output.stderr.is_empty() {
             bail!("Command failed with no stderr output");
@@ -266,15 +946,179 @@ where
     }
 }
 
-pub(crate) fn find_manager(podman: bool) -> Option<ContainerManager> {
-    if !podman {
+pub(crate) const DEFAULT_LOG_MAX_SIZE: &str = "50m";
+pub(crate) const DEFAULT_LOG_MAX_FILE: u32 = 3;
+
+/// How Podman networks the containers.
+#[derive(Debug, Clone)]
+pub(crate) struct PodmanNetworkInfo {
+    pub rootless: bool,
+    /// The network backend, netavark or cni.
+    pub backend: Option<String>,
+    /// The user mode networking used when rootless, pasta or
+    /// slirp4netns. Only reported by Podman 5 and newer.
+    pub rootless_cmd: Option<String>,
+}
+
+impl PodmanNetworkInfo {
+    /// A summary of the networking, including the mode EveBox ends up
+    /// with.
+    pub(crate) fn describe(&self, config: &PodmanConfig) -> String {
+        let evebox = match config.network {
+            Some(network) => network.as_str().to_string(),
+            None if self.rootless => format!(
+                "{} (default)",
+                self.rootless_cmd.as_deref().unwrap_or("slirp4netns")
+            ),
+            None => "bridge (default)".to_string(),
+        };
+        format!(
+            "{}, {} backend, EveBox on {evebox}, Suricata on host",
+            if self.rootless { "rootless" } else { "rootful" },
+            self.backend.as_deref().unwrap_or("unknown")
+        )
+    }
+}
+
+/// Return the `run` arguments for the network of the EveBox container
+/// with Podman.
+pub(crate) fn podman_network_args(
+    manager: &ContainerManager,
+    config: &PodmanConfig,
+) -> Vec<String> {
+    match config.network {
+        Some(network) if manager.is_podman() => {
+            if config.network_options.is_empty() {
+                vec![format!("--network={}", network.as_str())]
+            } else {
+                vec![format!(
+                    "--network={}:{}",
+                    network.as_str(),
+                    config.network_options.join(",")
+                )]
+            }
+        }
+        _ => vec![],
+    }
+}
+
+/// Return the `run` arguments for the memory and CPU limits of a
+/// container. Invalid values are skipped with a warning rather than
+/// failing with an engine error at start.
+pub(crate) fn limit_args(memory: Option<&str>, cpus: Option<&str>) -> Vec<String> {
+    let mut args = vec![];
+    if let Some(memory) = memory {
+        let valid = regex::Regex::new(r"(?i)^\d+[bkmg]?$")
+            .map(|re| re.is_match(memory))
+            .unwrap_or(false);
+        if valid {
+            args.push(format!("--memory={memory}"));
+        } else {
+            warn!("Ignoring invalid memory limit {memory:?}, expected a size such as 4g");
+        }
+    }
+    if let Some(cpus) = cpus {
+        match cpus.parse::<f64>() {
+            Ok(n) if n > 0.0 => args.push(format!("--cpus={cpus}")),
+            _ => warn!("Ignoring invalid CPU limit {cpus:?}, expected a number such as 1.5"),
+        }
+    }
+    args
+}
+
+/// Return the `run` arguments for the container log driver and size
+/// limits.
+///
+/// The default limits are only applied to the file log drivers, which
+/// is the engine default driver unless one is configured. Journald and
+/// friends manage their own storage and reject the options, so limits
+/// for them are only passed when configured.
+pub(crate) fn log_args(manager: &ContainerManager, config: &ContainerLogConfig) -> Vec<String> {
+    let mut args = vec![];
+    if let Some(driver) = &config.driver {
+        args.push(format!("--log-driver={}", driver));
+    }
+    let file_driver = config
+        .driver
+        .clone()
+        .or_else(|| manager.default_log_driver())
+        .is_some_and(|driver| matches!(driver.as_str(), "json-file" | "k8s-file" | "local"));
+
+    if let Some(max_size) = config
+        .max_size
+        .as_deref()
+        .or(file_driver.then_some(DEFAULT_LOG_MAX_SIZE))
+    {
+        args.push(format!("--log-opt=max-size={max_size}"));
+    }
+
+    if manager.capabilities().log_max_file {
+        if let Some(max_file) = config
+            .max_file
+            .or(file_driver.then_some(DEFAULT_LOG_MAX_FILE))
+        {
+            args.push(format!("--log-opt=max-file={max_file}"));
+        }
+    }
+    args
+}
+
+/// Return the `run` arguments to give a container the timezone of the
+/// host, as containers otherwise default to UTC.
+pub(crate) fn timezone_args(manager: &ContainerManager) -> Vec<String> {
+    let mut args = vec![];
+    // The timezone name is only known for the local host, on a remote
+    // host only /etc/localtime is relied on.
+    if manager.host().is_none() {
+        if let Some(tz) = host_timezone() {
+            args.push(format!("--env=TZ={tz}"));
+        }
+    }
+    if manager.host().is_some() || std::path::Path::new("/etc/localtime").exists() {
+        args.push("--volume=/etc/localtime:/etc/localtime:ro".to_string());
+    }
+    args
+}
+
+/// The name of the local host timezone, such as "America/Regina".
+fn host_timezone() -> Option<String> {
+    if let Ok(tz) = std::env::var("TZ") {
+        if !tz.is_empty() {
+            return Some(tz);
+        }
+    }
+    if let Ok(tz) = std::fs::read_to_string("/etc/timezone") {
+        let tz = tz.trim();
+        if !tz.is_empty() {
+            return Some(tz.to_string());
+        }
+    }
+    let target = std::fs::read_link("/etc/localtime").ok()?;
+    let target = target.to_string_lossy();
+    target.split_once("zoneinfo/").map(|(_, tz)| tz.to_string())
+}
+
+/// Find the container manager to use.
+///
+/// If a host is provided the container engine on that host is used
+/// over SSH.
+pub(crate) fn find_manager(runtime: Runtime, host: Option<&str>) -> Option<ContainerManager> {
+    let host = host.map(String::from);
+    if runtime != Runtime::Podman {
         debug!("Looking for Docker container engine");
 
-        let manager = ContainerManager::Docker(DockerManager::new());
+        let mut manager = ContainerManager::Docker(DockerManager::new(host.clone()));
         if manager.exists() {
             info!("Found Docker container engine");
             if let Ok(version) = manager.version() {
                 debug!("Found Docker version {version}");
+                match parse_version(&version) {
+                    Some(version) => manager.set_capabilities(Capabilities::docker(&version)),
+                    None => warn!(
+                        "Failed to parse Docker version {version}, some features will be disabled"
+                    ),
+                }
+                debug!("Docker capabilities: {:?}", manager.capabilities());
                 return Some(manager);
             }
         } else {
@@ -282,22 +1126,27 @@ pub(crate) fn find_manager(podman: bool) -> Option<ContainerManager> {
         }
     };
 
+    if runtime == Runtime::Docker {
+        return None;
+    }
+
     debug!("Looking for Podman container engine");
-    let manager = ContainerManager::Podman(PodmanManager::new());
+    let mut manager = ContainerManager::Podman(PodmanManager::new(host));
     if manager.exists() {
         info!("Found Podman container engine");
         if let Ok(version) = manager.version() {
             debug!("Found Podman version {version}");
-            match semver::Version::parse(&version) {
-                Ok(version) => {
-                    if version.major < 4 || (version.major == 4 && version.minor < 6) {
-                        error!("Podman version must be at least 4.7.0");
-                    } else {
-                        return Some(manager);
-                    }
+            match parse_version(&version) {
+                Some(version) if version < PODMAN_MIN_VERSION => {
+                    error!("Podman version must be at least {PODMAN_MIN_VERSION}, found {version}");
                 }
-                Err(_) => {
-                    error!("Failed to parse Podman version");
+                Some(version) => {
+                    manager.set_capabilities(Capabilities::podman(&version));
+                    debug!("Podman capabilities: {:?}", manager.capabilities());
+                    return Some(manager);
+                }
+                None => {
+                    error!("Failed to parse Podman version {version}");
                 }
             }
         }
@@ -308,7 +1157,7 @@ pub(crate) fn find_manager(podman: bool) -> Option<ContainerManager> {
     None
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 #[allow(dead_code)]
 pub(crate) enum Container {
     Suricata,
@@ -316,6 +1165,14 @@ pub(crate) enum Container {
 }
 
 impl Container {
+    /// The name of the container.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Container::Suricata => SURICATA_CONTAINER_NAME,
+            Container::EveBox => EVEBOX_CONTAINER_NAME,
+        }
+    }
+
     pub(crate) fn volumes(&self) -> Vec<String> {
         match self {
             Container::Suricata => {
@@ -350,7 +1207,7 @@ impl SuricataContainer {
 
     pub(crate) fn run(&self) -> RunCommandBuilder {
         let mut builder = RunCommandBuilder::new(
-            self.context.manager,
+            self.context.manager.clone(),
             self.context.image_name(Container::Suricata),
         );
         builder.volumes(&self.volumes());
@@ -42,18 +42,13 @@ impl Context {
 /// Normally this will be the hardcoded default, but we do allow
 /// it to be overridden in the configuration.
 pub(crate) fn image_name(config: &Config, container: Container) -> String {
-    match container {
-        Container::Suricata => config
-            .suricata
-            .image
-            .as_deref()
-            .unwrap_or(DEFAULT_SURICATA_IMAGE)
-            .to_string(),
-        Container::EveBox => config
-            .evebox
-            .image
-            .as_deref()
-            .unwrap_or(DEFAULT_EVEBOX_IMAGE)
-            .to_string(),
+    let (image, default) = match container {
+        Container::Suricata => (&config.suricata.image, DEFAULT_SURICATA_IMAGE),
+        Container::EveBox => (&config.evebox.image, DEFAULT_EVEBOX_IMAGE),
+    };
+    match (image, &config.updates.registry) {
+        (Some(image), _) => image.to_string(),
+        (None, Some(registry)) => default.replacen("docker.io", registry.trim_end_matches('/'), 1),
+        (None, None) => default.to_string(),
     }
 }
@@ -0,0 +1,232 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Step down logging when the disk holding the Suricata logs fills up,
+//! instead of letting the sensor fall over.
+
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::Config,
+    container::{timeouts, CommandExt},
+    context::Context,
+    fleet::parse_df_percent,
+    logrotate, notify,
+    state::State,
+    EVEBOX_CONTAINER_NAME, SURICATA_CONTAINER_NAME,
+};
+
+pub(crate) const DEFAULT_ROTATE_PERCENT: u8 = 85;
+pub(crate) const DEFAULT_REDUCE_PERCENT: u8 = 92;
+pub(crate) const DEFAULT_EVEBOX_RETENTION_DAYS: u32 = 3;
+
+/// EVE types disabled when logging is reduced, these are the highest
+/// volume types that aren't needed for alerting.
+const REDUCED_TYPES: &[&str] = &["flow", "netflow", "dns"];
+
+/// Check the disk usage and apply the policy.
+///
+/// Suricata is only restarted to change the logging if `restart` is
+/// true, as in the foreground it is not managed by us.
+pub(crate) fn check(context: &Context, restart: bool) {
+    let policy = &context.config.disk_policy;
+    if !policy.enabled {
+        return;
+    }
+    let rotate_percent = policy.rotate_percent.unwrap_or(DEFAULT_ROTATE_PERCENT);
+    let reduce_percent = policy.reduce_percent.unwrap_or(DEFAULT_REDUCE_PERCENT);
+
+    let usage = match disk_usage(context) {
+        Some(usage) => usage,
+        None => {
+            error!("Failed to get disk usage for the Suricata logs");
+            return;
+        }
+    };
+
+    let state = State::load();
+    let mut reduced = state.reduced_logging;
+    let pressure = if usage >= reduce_percent {
+        let _ = logrotate::rotate(context, true);
+        if !reduced {
+            let mut message = format!(
+                "Disk usage at {usage}%, disabling {} events",
+                REDUCED_TYPES.join(", ")
+            );
+            if context.config.evebox.agent.is_none() {
+                message.push_str(&format!(
+                    " and keeping {} days of events in EveBox",
+                    policy
+                        .evebox_retention_days
+                        .unwrap_or(DEFAULT_EVEBOX_RETENTION_DAYS)
+                ));
+            }
+            warn!("{message}");
+            notify::send_or_warn(
+                &context.config.notifications,
+                "Simple-IDS disk space low",
+                &message,
+                notify::Priority::High,
+            );
+            reduced = true;
+        }
+        Some(format!(
+            "disk usage at {usage}%, logging reduced to free space"
+        ))
+    } else if usage >= rotate_percent {
+        let message = format!("Disk usage at {usage}%, forcing log rotation");
+        warn!("{message}");
+        if state.disk_pressure.is_none() {
+            notify::send_or_warn(
+                &context.config.notifications,
+                "Simple-IDS disk space low",
+                &message,
+                notify::Priority::Default,
+            );
+        }
+        let _ = logrotate::rotate(context, true);
+        Some(format!("disk usage at {usage}%, forcing log rotation"))
+    } else {
+        if reduced {
+            let message = format!("Disk usage at {usage}%, restoring normal logging");
+            info!("{message}");
+            notify::send_or_warn(
+                &context.config.notifications,
+                "Simple-IDS disk space recovered",
+                &message,
+                notify::Priority::Default,
+            );
+            reduced = false;
+        }
+        None
+    };
+
+    let changed = reduced != state.reduced_logging;
+    if let Err(err) = State::update(|state| {
+        state.reduced_logging = reduced;
+        state.disk_pressure = pressure;
+    }) {
+        error!("Failed to record disk pressure state: {err}");
+    }
+
+    if changed && restart && context.manager.is_running(SURICATA_CONTAINER_NAME) {
+        info!("Restarting Suricata to apply logging changes");
+        crate::stop_container(context, SURICATA_CONTAINER_NAME, None);
+        if let Err(err) = crate::start_suricata_detached(context) {
+            error!("Failed to restart Suricata: {err}");
+        }
+    }
+    if changed
+        && restart
+        && context.config.evebox.agent.is_none()
+        && context.manager.is_running(EVEBOX_CONTAINER_NAME)
+    {
+        info!("Restarting EveBox to apply the retention change");
+        crate::stop_container(context, EVEBOX_CONTAINER_NAME, None);
+        if let Err(err) = crate::start_evebox_detached(context) {
+            error!("Failed to restart EveBox: {err}");
+        }
+    }
+}
+
+/// Days of events EveBox keeps, if shortened as logging is reduced.
+pub(crate) fn evebox_retention_days(config: &Config) -> Option<u32> {
+    let policy = &config.disk_policy;
+    if !policy.enabled || config.evebox.agent.is_some() || !State::load().reduced_logging {
+        return None;
+    }
+    Some(
+        policy
+            .evebox_retention_days
+            .unwrap_or(DEFAULT_EVEBOX_RETENTION_DAYS),
+    )
+}
+
+fn disk_usage(context: &Context) -> Option<u8> {
+    let output = context
+        .manager
+        .command()
+        .args([
+            "exec",
+            SURICATA_CONTAINER_NAME,
+            "df",
+            "-P",
+            "/var/log/suricata",
+        ])
+        .output_timeout(timeouts().query)
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_df_percent(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Suricata `--set` arguments to disable the reduced EVE types.
+pub(crate) fn reduced_logging_args(dump_config: &[String]) -> Vec<String> {
+    disable_eve_types_args(dump_config, |name| REDUCED_TYPES.contains(&name))
+}
+
+/// Suricata `--set` arguments to disable the EVE types matching a
+/// predicate, found in the output of `--dump-config`.
+///
+/// Types are either a plain value ("outputs.1.eve-log.types.9 = flow")
+/// or a mapping ("outputs.1.eve-log.types.5.dns = (null)"), either way
+/// setting "enabled" to "no" on the type disables it.
+pub(crate) fn disable_eve_types_args<F: Fn(&str) -> bool>(
+    dump_config: &[String],
+    disable: F,
+) -> Vec<String> {
+    let re =
+        Regex::new(r"^(outputs\.\d+\.eve-log\.types\.\d+)(?:\.([a-z0-9_-]+)\s| = ([a-z0-9_-]+)$)")
+            .unwrap();
+    dump_config
+        .iter()
+        .filter_map(|line| {
+            let c = re.captures(line)?;
+            let name = c.get(2).or_else(|| c.get(3))?.as_str();
+            if disable(name) {
+                Some(format!("{}.{}.enabled=no", &c[1], name))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Suricata `--set` arguments to enable EVE types that are not in the
+/// configuration at all, such as netflow which is commented out in the
+/// default configuration, by appending them to the types of each EVE
+/// output.
+pub(crate) fn enable_eve_types_args(dump_config: &[String], names: &[&str]) -> Vec<String> {
+    let re = Regex::new(
+        r"^(outputs\.\d+\.eve-log\.types)\.(\d+)(?:\.([a-z0-9_-]+)\s| = ([a-z0-9_-]+)$)",
+    )
+    .unwrap();
+    let mut outputs: BTreeMap<String, (u32, Vec<String>)> = BTreeMap::new();
+    for line in dump_config {
+        let Some(c) = re.captures(line) else {
+            continue;
+        };
+        let Ok(index) = c[2].parse::<u32>() else {
+            continue;
+        };
+        let output = outputs.entry(c[1].to_string()).or_default();
+        output.0 = output.0.max(index + 1);
+        if let Some(name) = c.get(3).or_else(|| c.get(4)) {
+            output.1.push(name.as_str().to_string());
+        }
+    }
+    let mut args = vec![];
+    for (prefix, (mut next, present)) in outputs {
+        for name in names {
+            if !present.iter().any(|present| present == name) {
+                args.push(format!("{prefix}.{next}={name}"));
+                next += 1;
+            }
+        }
+    }
+    args
+}
@@ -0,0 +1,90 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local, TimeZone, Utc};
+use tracing::warn;
+
+static TIMEZONE: OnceLock<Timezone> = OnceLock::new();
+
+/// The timezone timestamps are displayed in.
+///
+/// Suricata and the container engines log in UTC, which makes it hard
+/// to correlate with local wall-clock time, so everything is converted
+/// to the host local time unless configured otherwise.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) enum Timezone {
+    #[default]
+    Local,
+    Utc,
+    Named(chrono_tz::Tz),
+}
+
+impl Timezone {
+    /// Parse "local", "utc" or an IANA name like "America/Regina".
+    pub(crate) fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "" | "local" => Ok(Self::Local),
+            "utc" => Ok(Self::Utc),
+            _ => match name.parse::<chrono_tz::Tz>() {
+                Ok(tz) => Ok(Self::Named(tz)),
+                Err(_) => bail!("unknown timezone: {name}"),
+            },
+        }
+    }
+
+    /// Format a timestamp in this timezone.
+    pub(crate) fn format<T: TimeZone>(&self, ts: &DateTime<T>, fmt: &str) -> String {
+        match self {
+            Self::Local => ts.with_timezone(&Local).format(fmt).to_string(),
+            Self::Utc => ts.with_timezone(&Utc).format(fmt).to_string(),
+            Self::Named(tz) => ts.with_timezone(tz).format(fmt).to_string(),
+        }
+    }
+}
+
+/// Set the display timezone from the configuration, falling back to
+/// local time if it is not valid.
+pub(crate) fn set_timezone(name: Option<&str>) {
+    let tz = match name.map(Timezone::parse) {
+        Some(Ok(tz)) => tz,
+        Some(Err(err)) => {
+            warn!("Invalid display.timezone, using local time: {err}");
+            Timezone::Local
+        }
+        None => Timezone::Local,
+    };
+    let _ = TIMEZONE.set(tz);
+}
+
+pub(crate) fn timezone() -> Timezone {
+    TIMEZONE.get().copied().unwrap_or_default()
+}
+
+/// Format a timestamp for display with minute resolution.
+pub(crate) fn format_minutes<T: TimeZone>(ts: &DateTime<T>) -> String {
+    timezone().format(ts, "%Y-%m-%d %H:%M")
+}
+
+/// Format a size in bytes for display, such as "12.4 GB".
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Format a timestamp for display with millisecond resolution.
+pub(crate) fn format_millis<T: TimeZone>(ts: &DateTime<T>) -> String {
+    timezone().format(ts, "%Y-%m-%d %H:%M:%S%.3f")
+}
@@ -0,0 +1,362 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Diagnose the environment Simple-IDS runs in, for when things don't
+//! start.
+
+use std::{ffi::CString, net::TcpListener};
+
+use colored::Colorize;
+
+use crate::{
+    config::{Config, PodmanNetwork, Runtime},
+    container::{self, timeouts, CommandExt, Container, ContainerManager},
+    context, display, netif, nicsetup,
+    probe::EVEBOX_PORT,
+    EVEBOX_CONTAINER_NAME,
+};
+
+/// Fail if the container storage has less than this many bytes free.
+const DISK_FAIL_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Warn if the container storage has less than this many bytes free.
+const DISK_WARN_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Bit for CAP_NET_RAW in the capability sets of /proc/self/status.
+const CAP_NET_RAW: u64 = 13;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+}
+
+pub(crate) struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+impl Check {
+    pub(crate) fn pass(name: &'static str, detail: impl ToString) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Pass,
+            detail: detail.to_string(),
+            hint: None,
+        }
+    }
+
+    pub(crate) fn warn(name: &'static str, detail: impl ToString, hint: impl ToString) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Warn,
+            detail: detail.to_string(),
+            hint: Some(hint.to_string()),
+        }
+    }
+
+    pub(crate) fn fail(name: &'static str, detail: impl ToString, hint: impl ToString) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Fail,
+            detail: detail.to_string(),
+            hint: Some(hint.to_string()),
+        }
+    }
+
+    pub(crate) fn print(&self) {
+        let label = match self.outcome {
+            Outcome::Pass => "PASS".green(),
+            Outcome::Warn => "WARN".yellow(),
+            Outcome::Fail => "FAIL".red(),
+        };
+        println!("[{label}] {}: {}", self.name, self.detail);
+        if let Some(hint) = &self.hint {
+            println!("       {}", hint.italic());
+        }
+    }
+}
+
+/// Run the checks, printing the results. Returns the number of failed
+/// checks.
+pub(crate) fn run(config: &Config, runtime: Runtime) -> usize {
+    let manager = container::find_manager(runtime, None);
+    let mut checks = vec![check_manager(manager.as_ref(), runtime)];
+    checks.push(check_net_raw(manager.as_ref()));
+    checks.extend(check_interfaces(config, manager.as_ref()));
+    checks.push(check_port(config, manager.as_ref()));
+    if let Some(check) = check_podman_network(config, manager.as_ref()) {
+        checks.push(check);
+    }
+    if let Some(manager) = &manager {
+        checks.push(check_disk(manager));
+    }
+
+    for check in &checks {
+        check.print();
+    }
+    checks
+        .iter()
+        .filter(|check| check.outcome == Outcome::Fail)
+        .count()
+}
+
+fn check_manager(manager: Option<&ContainerManager>, runtime: Runtime) -> Check {
+    let name = "container manager";
+    match manager {
+        Some(manager) => match manager.version() {
+            Ok(version) => Check::pass(name, format!("{manager} {version}")),
+            Err(err) => Check::fail(
+                name,
+                format!("{manager} found, but the version could not be read: {err}"),
+                "Make sure the container service is running and this user may use it",
+            ),
+        },
+        None => {
+            let wanted = match runtime {
+                Runtime::Docker => "Docker",
+                Runtime::Podman => "Podman",
+                Runtime::Auto => "Docker or Podman",
+            };
+            Check::fail(
+                name,
+                format!("no usable {wanted} found"),
+                "Install a container runtime and make sure its service is running, \
+                 see https://evebox.org/runtimes/",
+            )
+        }
+    }
+}
+
+/// Capture needs CAP_NET_RAW. Docker grants it to the container from
+/// the daemon, Podman only has it if we do.
+fn check_net_raw(manager: Option<&ContainerManager>) -> Check {
+    let name = "CAP_NET_RAW";
+    if let Some(manager) = manager {
+        if !manager.is_podman() {
+            return Check::pass(name, "granted to the container by the Docker daemon");
+        }
+    }
+    match effective_capabilities() {
+        Some(caps) if caps & (1 << CAP_NET_RAW) != 0 => Check::pass(name, "available"),
+        Some(_) => Check::fail(
+            name,
+            "not available to this process",
+            "Run as root, rootless Podman can't capture packets",
+        ),
+        None => Check::warn(
+            name,
+            "unable to read the process capabilities",
+            "Run as root if capture fails to start",
+        ),
+    }
+}
+
+fn effective_capabilities() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let caps = status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))?;
+    u64::from_str_radix(caps.trim(), 16).ok()
+}
+
+pub(crate) fn check_interfaces(config: &Config, manager: Option<&ContainerManager>) -> Vec<Check> {
+    let name = "interface";
+    let interfaces = &config.suricata.interfaces;
+    if !config.services.suricata {
+        return vec![Check::pass(name, "none, Suricata is disabled")];
+    }
+    if interfaces.is_empty() {
+        if config.suricata.unix_socket {
+            return vec![Check::pass(name, "none, running in unix socket mode")];
+        }
+        if let Some(bridge) = &config.suricata.bridge {
+            return vec![Check::pass(
+                name,
+                format!("bridging {} and {}", bridge.lan, bridge.wan),
+            )];
+        }
+        if let Some(ips) = &config.suricata.ips {
+            return vec![Check::pass(
+                name,
+                format!("none, running in IPS mode on NFQUEUE {}", ips.queue),
+            )];
+        }
+        return vec![Check::fail(
+            name,
+            "no interface configured",
+            "Select an interface from the main menu",
+        )];
+    }
+    let image = context::image_name(config, Container::Suricata);
+    let available = match netif::lookup(manager, &image) {
+        Ok(available) => available,
+        Err(err) => {
+            return vec![Check::warn(
+                name,
+                format!("failed to list the network interfaces: {err}"),
+                "Check that the configured interfaces exist",
+            )]
+        }
+    };
+    let mut checks = vec![];
+    for interface in interfaces {
+        checks.push(
+            match available.iter().find(|found| &found.name == interface) {
+                None => Check::fail(
+                    name,
+                    format!("{interface} does not exist"),
+                    "Select an existing interface from the main menu",
+                ),
+                Some(found) if found.status != "UP" => Check::warn(
+                    name,
+                    format!("{interface} is {}", found.status),
+                    format!("Bring the interface up: ip link set {interface} up"),
+                ),
+                Some(_) => Check::pass(name, format!("{interface} is UP")),
+            },
+        );
+    }
+    for problem in nicsetup::verify(config) {
+        checks.push(Check::warn(
+            name,
+            problem,
+            "Apply the high throughput tuning preset, or disable them with ethtool -K",
+        ));
+    }
+    checks
+}
+
+pub(crate) fn check_port(config: &Config, manager: Option<&ContainerManager>) -> Check {
+    let name = "port";
+    if config.evebox.agent.is_some() {
+        return Check::pass(name, "not used by the EveBox agent in sensor-only mode");
+    }
+    if manager
+        .map(|manager| manager.is_running(EVEBOX_CONTAINER_NAME))
+        .unwrap_or(false)
+    {
+        return Check::pass(name, format!("{EVEBOX_PORT} is in use by EveBox"));
+    }
+    let addr = if config.evebox.allow_remote {
+        "0.0.0.0"
+    } else {
+        "127.0.0.1"
+    };
+    match TcpListener::bind((addr, EVEBOX_PORT)) {
+        Ok(_) => Check::pass(name, format!("{EVEBOX_PORT} is available")),
+        Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => Check::fail(
+            name,
+            format!("{EVEBOX_PORT} is in use by another process"),
+            format!("Find the process with: ss -ltnp 'sport = :{EVEBOX_PORT}'"),
+        ),
+        Err(err) => Check::warn(
+            name,
+            format!("unable to check {EVEBOX_PORT}: {err}"),
+            "EveBox may fail to start if the port is in use",
+        ),
+    }
+}
+
+fn check_podman_network(config: &Config, manager: Option<&ContainerManager>) -> Option<Check> {
+    let name = "podman network";
+    let manager = manager?;
+    if !manager.is_podman() {
+        if config.podman.is_default() {
+            return None;
+        }
+        return Some(Check::warn(
+            name,
+            "Podman settings are configured but Docker is in use",
+            "They are ignored, remove them or run with --podman",
+        ));
+    }
+    let info = match manager.podman_network_info() {
+        Some(info) => info,
+        None => {
+            return Some(Check::warn(
+                name,
+                "unable to read the networking from podman info",
+                "Check that podman info works",
+            ))
+        }
+    };
+    let detail = info.describe(&config.podman);
+    let slirp4netns = match config.podman.network {
+        Some(network) => network == PodmanNetwork::Slirp4netns,
+        None => info.rootless && info.rootless_cmd.as_deref() != Some("pasta"),
+    };
+    if slirp4netns
+        && config.evebox.allow_remote
+        && !config
+            .podman
+            .network_options
+            .iter()
+            .any(|option| option.starts_with("port_handler="))
+    {
+        return Some(Check::warn(
+            name,
+            detail,
+            "With slirp4netns EveBox sees remote clients as local, set network-options = [\"port_handler=slirp4netns\"]",
+        ));
+    }
+    Some(Check::pass(name, detail))
+}
+
+pub(crate) fn check_disk(manager: &ContainerManager) -> Check {
+    let name = "disk space";
+    let format = if manager.is_podman() {
+        "{{.Store.GraphRoot}}"
+    } else {
+        "{{.DockerRootDir}}"
+    };
+    let root = match manager
+        .command()
+        .args(["info", "--format", format])
+        .output_timeout(timeouts().query)
+    {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+        _ => {
+            return Check::warn(
+                name,
+                "unable to find the container storage directory",
+                format!("Check the free space with: {} system df", manager.bin()),
+            )
+        }
+    };
+    match available_bytes(&root) {
+        Some(bytes) if bytes < DISK_FAIL_BYTES => Check::fail(
+            name,
+            format!("{} free on {root}", display::format_bytes(bytes)),
+            "Free up space, the containers and logs will fail to write",
+        ),
+        Some(bytes) if bytes < DISK_WARN_BYTES => Check::warn(
+            name,
+            format!("{} free on {root}", display::format_bytes(bytes)),
+            "Logs and rule updates may fill the disk, consider freeing up space",
+        ),
+        Some(bytes) => Check::pass(
+            name,
+            format!("{} free on {root}", display::format_bytes(bytes)),
+        ),
+        None => Check::warn(
+            name,
+            format!("unable to get the free space on {root}"),
+            "Check the free space with: df -h",
+        ),
+    }
+}
+
+fn available_bytes(path: &str) -> Option<u64> {
+    let path = CString::new(path).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
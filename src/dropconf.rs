@@ -0,0 +1,161 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Support for suricata-update's drop.conf, which converts matching
+//! rules from alert to drop in IPS deployments.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use chrono::{Duration, Utc};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{
+    context::Context,
+    eve::{self, EVE_FILENAME},
+    state,
+};
+
+pub(crate) const FILENAME: &str = "drop.conf";
+
+enum Matcher {
+    Sid { gid: Option<u64>, sid: u64 },
+    Regex(Regex),
+}
+
+impl Matcher {
+    /// Does an alert match. suricata-update matches regular
+    /// expressions against the full rule, but only the message is
+    /// available here, so regex matches are a best effort.
+    fn matches(&self, gid: u64, sid: u64, signature: &str) -> bool {
+        match self {
+            Matcher::Sid { gid: g, sid: s } => *s == sid && g.map(|g| g == gid).unwrap_or(true),
+            Matcher::Regex(re) => re.is_match(signature),
+        }
+    }
+}
+
+/// Parse drop.conf, returning the matchers that can be checked against
+/// alerts and the number of entries (such as group:) that can't be.
+fn parse(buf: &str) -> (Vec<Matcher>, usize) {
+    let mut matchers = vec![];
+    let mut unsupported = 0;
+    for line in buf.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(pattern) = line.strip_prefix("re:") {
+            match Regex::new(pattern) {
+                Ok(re) => matchers.push(Matcher::Regex(re)),
+                Err(_) => unsupported += 1,
+            }
+        } else if let Ok(sid) = line.parse() {
+            matchers.push(Matcher::Sid { gid: None, sid });
+        } else if let Some((gid, sid)) = line
+            .split_once(':')
+            .and_then(|(gid, sid)| Some((gid.parse().ok()?, sid.parse().ok()?)))
+        {
+            matchers.push(Matcher::Sid {
+                gid: Some(gid),
+                sid,
+            });
+        } else {
+            unsupported += 1;
+        }
+    }
+    (matchers, unsupported)
+}
+
+/// A signature that has recently fired and would be dropped.
+pub(crate) struct DropCandidate {
+    pub sid: u64,
+    pub signature: String,
+    pub count: u64,
+}
+
+pub(crate) struct SafetyReport {
+    pub hours: i64,
+    pub candidates: Vec<DropCandidate>,
+    /// drop.conf entries that couldn't be checked.
+    pub unsupported: usize,
+}
+
+/// Find the alerts of the last `hours` hours that match drop.conf,
+/// that is, traffic that would have been dropped.
+pub(crate) fn safety_report(context: &Context, hours: i64) -> Result<SafetyReport> {
+    let buf = std::fs::read_to_string(state::file_path(FILENAME))?;
+    let (matchers, unsupported) = parse(&buf);
+    let since = Utc::now() - Duration::hours(hours);
+    let mut candidates: BTreeMap<u64, DropCandidate> = BTreeMap::new();
+
+    let status = eve::exec_lines(
+        context,
+        &["grep", "-F", "\"event_type\":\"alert\"", EVE_FILENAME],
+        |line| {
+            let event: Value = match serde_json::from_str(line) {
+                Ok(event) => event,
+                Err(_) => return,
+            };
+            let recent = event["timestamp"]
+                .as_str()
+                .and_then(eve::parse_timestamp)
+                .map(|ts| ts >= since)
+                .unwrap_or(false);
+            if !recent {
+                return;
+            }
+            let alert = &event["alert"];
+            let gid = alert["gid"].as_u64().unwrap_or(1);
+            let sid = alert["signature_id"].as_u64().unwrap_or(0);
+            let signature = alert["signature"].as_str().unwrap_or("");
+            if matchers.iter().any(|m| m.matches(gid, sid, signature)) {
+                candidates
+                    .entry(sid)
+                    .or_insert_with(|| DropCandidate {
+                        sid,
+                        signature: signature.to_string(),
+                        count: 0,
+                    })
+                    .count += 1;
+            }
+        },
+    )?;
+    if !status.success() && status.code() != Some(1) {
+        bail!("Failed to read {EVE_FILENAME}");
+    }
+
+    let mut candidates: Vec<DropCandidate> = candidates.into_values().collect();
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.count));
+    Ok(SafetyReport {
+        hours,
+        candidates,
+        unsupported,
+    })
+}
+
+impl SafetyReport {
+    pub(crate) fn print(&self) {
+        if self.candidates.is_empty() {
+            println!("No alerts in the last {} hours match drop.conf", self.hours);
+        } else {
+            println!(
+                "These signatures fired in the last {} hours and would start dropping traffic:",
+                self.hours
+            );
+            for candidate in &self.candidates {
+                println!(
+                    "  {:>8}  [{}] {}",
+                    candidate.count, candidate.sid, candidate.signature
+                );
+            }
+        }
+        if self.unsupported > 0 {
+            println!(
+                "{} drop.conf entries (such as group: or metadata:) could not be checked",
+                self.unsupported
+            );
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+use std::path::Path;
+
+use anyhow::Result;
+use colored::Colorize;
+use similar::TextDiff;
+use tracing::{error, info};
+
+/// Edit a file with $EDITOR, then show the changes and validate them,
+/// offering to revert if validation fails.
+///
+/// Returns true if the file was changed and kept.
+pub(crate) fn edit(path: &Path, validate: impl Fn() -> Result<()>) -> Result<bool> {
+    let original = std::fs::read_to_string(path).ok();
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "nano".into());
+    if let Err(err) = std::process::Command::new(&editor).arg(path).status() {
+        anyhow::bail!(
+            "Failed to load {} in editor {}: {}",
+            path.display(),
+            editor,
+            err
+        );
+    }
+    let edited = std::fs::read_to_string(path).ok();
+    if edited == original {
+        info!("No changes made to {}", path.display());
+        return Ok(false);
+    }
+
+    print_diff(
+        original.as_deref().unwrap_or_default(),
+        edited.as_deref().unwrap_or_default(),
+        ("original", "edited"),
+    );
+
+    if let Err(err) = validate() {
+        error!("Validation of {} failed: {err}", path.display());
+        if crate::prompt::confirm("Revert the changes?", None) {
+            match &original {
+                Some(original) => std::fs::write(path, original)?,
+                None => std::fs::remove_file(path)?,
+            }
+            info!("Reverted {}", path.display());
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Print a colored unified diff, with the labels for the old and new
+/// versions.
+pub(crate) fn print_diff(old: &str, new: &str, labels: (&str, &str)) {
+    let diff = TextDiff::from_lines(old, new);
+    for line in diff
+        .unified_diff()
+        .context_radius(2)
+        .header(labels.0, labels.1)
+        .to_string()
+        .lines()
+    {
+        if line.starts_with("+++") || line.starts_with("---") {
+            println!("{}", line.bold());
+        } else if line.starts_with('+') {
+            println!("{}", line.green());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else {
+            println!("{line}");
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Errors for the command line commands.
+//!
+//! Each error maps to a fixed exit code so scripts and monitoring can
+//! tell failure modes apart. These codes are part of the command line
+//! interface and must not be changed:
+//!
+//! | Code | Meaning                                      |
+//! |------|----------------------------------------------|
+//! | 0    | Success                                      |
+//! | 1    | General failure                              |
+//! | 10   | No container engine (Docker or Podman) found |
+//! | 11   | Invalid configuration                        |
+//! | 12   | A container failed to start, stop or remove  |
+//! | 13   | Failed to pull a container image or update   |
+//! | 14   | Suricata or EveBox is not running            |
+//! | 15   | Insufficient permissions                     |
+//! | 16   | Cancelled by the user                        |
+
+use std::fmt::Display;
+
+use tracing::error;
+
+pub(crate) const EXIT_FAILURE: i32 = 1;
+pub(crate) const EXIT_ENGINE_MISSING: i32 = 10;
+pub(crate) const EXIT_CONFIG_ERROR: i32 = 11;
+pub(crate) const EXIT_CONTAINER_FAILED: i32 = 12;
+pub(crate) const EXIT_UPDATE_FAILED: i32 = 13;
+pub(crate) const EXIT_NOT_RUNNING: i32 = 14;
+pub(crate) const EXIT_PERMISSION_DENIED: i32 = 15;
+pub(crate) const EXIT_CANCELLED: i32 = 16;
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    Failed(anyhow::Error),
+    EngineMissing,
+    Config(anyhow::Error),
+    Container(anyhow::Error),
+    Update(anyhow::Error),
+    NotRunning(String),
+    PermissionDenied(String),
+    Cancelled,
+}
+
+pub(crate) type CommandResult = Result<(), Error>;
+
+impl Error {
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            Error::Failed(_) => EXIT_FAILURE,
+            Error::EngineMissing => EXIT_ENGINE_MISSING,
+            Error::Config(_) => EXIT_CONFIG_ERROR,
+            Error::Container(_) => EXIT_CONTAINER_FAILED,
+            Error::Update(_) => EXIT_UPDATE_FAILED,
+            Error::NotRunning(_) => EXIT_NOT_RUNNING,
+            Error::PermissionDenied(_) => EXIT_PERMISSION_DENIED,
+            Error::Cancelled => EXIT_CANCELLED,
+        }
+    }
+
+    pub(crate) fn failed(msg: impl Display) -> Self {
+        Error::Failed(anyhow::anyhow!("{msg}"))
+    }
+
+    pub(crate) fn config(msg: impl Display) -> Self {
+        Error::Config(anyhow::anyhow!("{msg}"))
+    }
+
+    pub(crate) fn container(msg: impl Display) -> Self {
+        Error::Container(anyhow::anyhow!("{msg}"))
+    }
+
+    pub(crate) fn update(msg: impl Display) -> Self {
+        Error::Update(anyhow::anyhow!("{msg}"))
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Failed(err) => write!(f, "{err}"),
+            Error::EngineMissing => write!(
+                f,
+                "No container manager found. Docker or Podman must be available. \
+                 See https://evebox.org/runtimes/ for more info."
+            ),
+            Error::Config(err) => write!(f, "Configuration error: {err}"),
+            Error::Container(err) => write!(f, "Container error: {err}"),
+            Error::Update(err) => write!(f, "Update failed: {err}"),
+            Error::NotRunning(msg) => write!(f, "{msg}"),
+            Error::PermissionDenied(msg) => write!(f, "Permission denied: {msg}"),
+            Error::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        Error::Failed(err)
+    }
+}
+
+/// Exit the process with the exit code for the result of a command,
+/// logging the error if there was one.
+pub(crate) fn exit(result: CommandResult) -> ! {
+    match result {
+        Ok(()) => std::process::exit(0),
+        Err(err) => {
+            error!("{err}");
+            std::process::exit(err.exit_code());
+        }
+    }
+}
@@ -0,0 +1,151 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Reading and displaying the Suricata eve.json events.
+
+use std::{collections::VecDeque, process::ExitStatus};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, FixedOffset};
+use serde_json::Value;
+
+use crate::{
+    container::{self, timeouts},
+    context::Context,
+    display,
+    geoip::GeoIp,
+    SURICATA_CONTAINER_NAME,
+};
+
+pub(crate) const EVE_FILENAME: &str = "/var/log/suricata/eve.json";
+
+/// Run a command in the Suricata container, passing each line of
+/// output to `f`.
+pub(crate) fn exec_lines(
+    context: &Context,
+    args: &[&str],
+    f: impl FnMut(&str),
+) -> Result<ExitStatus> {
+    if !context.manager.is_running(SURICATA_CONTAINER_NAME) {
+        bail!("Suricata is not running");
+    }
+    // Following a file runs until it is cancelled.
+    let timeout = if args.contains(&"-F") {
+        None
+    } else {
+        Some(timeouts().control)
+    };
+    container::spawn_lines(
+        context
+            .manager
+            .command()
+            .arg("exec")
+            .arg(SURICATA_CONTAINER_NAME)
+            .args(args),
+        timeout,
+        f,
+    )
+}
+
+/// Print the last `count` events, optionally following new events.
+pub(crate) fn tail(context: &Context, count: usize, follow: bool) -> Result<()> {
+    let geoip = GeoIp::open(&context.config.geoip);
+    let count = count.to_string();
+    let mut args = vec!["tail", "-n", &count];
+    if follow {
+        args.push("-F");
+    }
+    args.push(EVE_FILENAME);
+    let status = exec_lines(context, &args, |line| print_line(line, geoip.as_ref()))?;
+    if !status.success() {
+        bail!("Failed to read {EVE_FILENAME}");
+    }
+    Ok(())
+}
+
+/// Print the last `count` events containing `pattern`.
+pub(crate) fn search(context: &Context, pattern: &str, count: usize) -> Result<()> {
+    let geoip = GeoIp::open(&context.config.geoip);
+    let mut matches = VecDeque::with_capacity(count);
+    let status = exec_lines(
+        context,
+        &["grep", "-F", "--", pattern, EVE_FILENAME],
+        |line| {
+            if matches.len() == count {
+                matches.pop_front();
+            }
+            matches.push_back(line.to_string());
+        },
+    )?;
+
+    // grep exits with 1 when there are no matches.
+    if !status.success() && status.code() != Some(1) {
+        bail!("Failed to search {EVE_FILENAME}");
+    }
+    for line in matches {
+        print_line(&line, geoip.as_ref());
+    }
+    Ok(())
+}
+
+fn print_line(line: &str, geoip: Option<&GeoIp>) {
+    match serde_json::from_str::<Value>(line) {
+        Ok(event) => println!("{}", format_event(&event, geoip)),
+        Err(_) => println!("{line}"),
+    }
+}
+
+/// Parse an eve timestamp like "2024-02-01T10:11:12.123456-0600".
+pub(crate) fn parse_timestamp(ts: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_str(ts, "%Y-%m-%dT%H:%M:%S%.f%z").ok()
+}
+
+/// Format an event as a single line summary.
+pub(crate) fn format_event(event: &Value, geoip: Option<&GeoIp>) -> String {
+    let timestamp = event["timestamp"]
+        .as_str()
+        .and_then(parse_timestamp)
+        .map(|ts| display::format_millis(&ts))
+        .unwrap_or_default();
+    let event_type = event["event_type"].as_str().unwrap_or("unknown");
+    let proto = event["proto"].as_str().unwrap_or("");
+    let src = address(event, "src_ip", "src_port", geoip);
+    let dest = address(event, "dest_ip", "dest_port", geoip);
+
+    let detail = match event_type {
+        "alert" => format!(
+            "[{}] {} (severity {})",
+            event["alert"]["signature_id"],
+            event["alert"]["signature"].as_str().unwrap_or(""),
+            event["alert"]["severity"]
+        ),
+        "dns" => event["dns"]["rrname"]
+            .as_str()
+            .or_else(|| event["dns"]["queries"][0]["rrname"].as_str())
+            .unwrap_or("")
+            .to_string(),
+        "http" => format!(
+            "{}{}",
+            event["http"]["hostname"].as_str().unwrap_or(""),
+            event["http"]["url"].as_str().unwrap_or("")
+        ),
+        "tls" => event["tls"]["sni"].as_str().unwrap_or("").to_string(),
+        _ => String::new(),
+    };
+
+    format!("{timestamp} {event_type:<8} {proto:<5} {src} -> {dest} {detail}")
+        .trim_end()
+        .to_string()
+}
+
+fn address(event: &Value, ip: &str, port: &str, geoip: Option<&GeoIp>) -> String {
+    let addr = event[ip].as_str().unwrap_or("-");
+    let mut out = match event[port].as_u64() {
+        Some(port) => format!("{addr}:{port}"),
+        None => addr.to_string(),
+    };
+    if let Some(info) = geoip.and_then(|geoip| geoip.lookup_str(addr)) {
+        out.push_str(&format!(" ({})", info.label()));
+    }
+    out
+}
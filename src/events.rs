@@ -0,0 +1,114 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use colored::Colorize;
+use tracing::debug;
+
+use crate::{container::ContainerManager, journal, term};
+
+/// Background watcher for container events.
+///
+/// Runs `docker events` (or `podman events`) filtered to the Simple-IDS
+/// containers so the main menu can tell when the status it displayed
+/// has gone stale.
+pub(crate) struct EventWatcher {
+    child: Child,
+    header_row: Arc<Mutex<Option<u16>>>,
+}
+
+impl EventWatcher {
+    /// Start watching events for the given containers.
+    ///
+    /// Returns `None` if the events command could not be started.
+    pub(crate) fn start(manager: &ContainerManager, containers: &[&str]) -> Option<Self> {
+        let mut command = manager.command();
+        command.args(["events", "--format", "{{json .}}"]);
+        for container in containers {
+            command.arg("--filter");
+            command.arg(format!("container={}", container));
+        }
+        let mut child = match command.stdout(Stdio::piped()).stderr(Stdio::null()).spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                debug!("Failed to start container event watcher: {err}");
+                return None;
+            }
+        };
+        let stdout = child.stdout.take()?;
+
+        let header_row: Arc<Mutex<Option<u16>>> = Arc::new(Mutex::new(None));
+
+        {
+            let header_row = header_row.clone();
+            thread::spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => break,
+                    };
+                    let (name, status) = match parse_event(&line) {
+                        Some(event) => event,
+                        None => continue,
+                    };
+                    debug!("Container event: {name}: {status}");
+                    if status == "die" || status == "died" {
+                        journal::record(journal::Kind::Exit, format!("{name} exited"));
+                    }
+                    if let Some(row) = *header_row.lock().unwrap() {
+                        let message = format!(
+                            "{} Status changed ({}: {}), select Refresh Status to update",
+                            ">>>".cyan(),
+                            name,
+                            status
+                        );
+                        term::update_line(row, &message.yellow().to_string());
+                    }
+                }
+                debug!("Container event watcher exited");
+            });
+        }
+
+        Some(Self { child, header_row })
+    }
+
+    /// Set the terminal row the status header was drawn on, this is
+    /// the line that gets rewritten when an event is received.
+    pub(crate) fn set_header_row(&self, row: Option<u16>) {
+        *self.header_row.lock().unwrap() = row;
+    }
+}
+
+impl Drop for EventWatcher {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Parse an event line into a container name and status.
+///
+/// Docker and Podman use different JSON layouts, so look for the
+/// fields of both. Exec events (such as log rotation) are ignored as
+/// they don't change the state of the container.
+fn parse_event(line: &str) -> Option<(String, String)> {
+    let json: serde_json::Value = serde_json::from_str(line).ok()?;
+    let status = json["status"]
+        .as_str()
+        .or_else(|| json["Status"].as_str())
+        .or_else(|| json["Action"].as_str())?;
+    if status.starts_with("exec") {
+        return None;
+    }
+    let name = json["Actor"]["Attributes"]["name"]
+        .as_str()
+        .or_else(|| json["Name"].as_str())
+        .unwrap_or("unknown");
+    Some((name.to_string(), status.to_string()))
+}
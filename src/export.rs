@@ -0,0 +1,400 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use serde_yaml::{Mapping, Value};
+use tracing::info;
+
+use crate::{build_evebox_args, build_suricata_args, container::Container, context::Context};
+
+/// Path of podman in the generated systemd units.
+const PODMAN: &str = "/usr/bin/podman";
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub(crate) enum Format {
+    /// docker-compose.yml
+    Compose,
+    /// Podman Quadlet .container files
+    Quadlet,
+    /// systemd .service units running podman, as podman generate
+    /// systemd --new would
+    Systemd,
+    /// Ansible tasks using community.docker.docker_container
+    Ansible,
+}
+
+/// A container as described by its `run` arguments.
+#[derive(Debug, Default)]
+struct ContainerSpec {
+    name: String,
+    image: String,
+    host_network: bool,
+    network: Option<String>,
+    caps: Vec<String>,
+    volumes: Vec<String>,
+    ports: Vec<String>,
+    labels: Vec<(String, String)>,
+    log_driver: Option<String>,
+    log_opts: Vec<(String, String)>,
+    restart: Option<String>,
+    memory: Option<String>,
+    cpus: Option<String>,
+    /// Environment variables passed through from the environment of
+    /// simple-ids, such as secrets.
+    env: Vec<String>,
+    command: Vec<String>,
+    /// The run arguments as given.
+    args: Vec<String>,
+}
+
+impl ContainerSpec {
+    /// Parse the arguments from the run command builders, so the export
+    /// always matches what a `start` would run.
+    fn from_run_args(args: &[String]) -> Self {
+        let mut spec = Self {
+            args: args.to_vec(),
+            ..Self::default()
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "run" || arg == "-d" {
+                continue;
+            }
+            if arg == "--name" {
+                spec.name = iter.next().cloned().unwrap_or_default();
+                continue;
+            }
+            if !arg.starts_with('-') {
+                spec.image = arg.to_string();
+                spec.command = iter.cloned().collect();
+                break;
+            }
+            let (key, value) = arg.split_once('=').unwrap_or((arg, ""));
+            match key {
+                "--net" | "--network" => {
+                    spec.host_network = value == "host";
+                    spec.network = Some(value.to_string());
+                }
+                "--env" => spec.env.push(value.to_string()),
+                "--cap-add" => spec.caps.push(value.to_string()),
+                "--volume" => spec.volumes.push(value.to_string()),
+                "--publish" => spec.ports.push(value.to_string()),
+                "--label" => spec.labels.push(split_pair(value)),
+                "--log-driver" => spec.log_driver = Some(value.to_string()),
+                "--log-opt" => spec.log_opts.push(split_pair(value)),
+                "--restart" => spec.restart = Some(value.to_string()),
+                "--memory" => spec.memory = Some(value.to_string()),
+                "--cpus" => spec.cpus = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        spec
+    }
+
+    /// The configured restart policy, defaulting to unless-stopped as
+    /// the exports are for having the system manage the containers.
+    fn restart_policy(&self) -> &str {
+        match self.restart.as_deref() {
+            None | Some("no") => "unless-stopped",
+            Some(policy) => policy,
+        }
+    }
+
+    /// The systemd restart setting for the restart policy.
+    fn systemd_restart(&self) -> &str {
+        match self.restart_policy() {
+            "on-failure" => "on-failure",
+            _ => "always",
+        }
+    }
+
+    /// Names of the named volumes, as opposed to bind mounts.
+    fn named_volumes(&self) -> Vec<String> {
+        self.volumes
+            .iter()
+            .filter_map(|volume| volume.split(':').next())
+            .filter(|source| !source.starts_with('/') && !source.starts_with('.'))
+            .map(String::from)
+            .collect()
+    }
+}
+
+fn split_pair(value: &str) -> (String, String) {
+    let (key, value) = value.split_once('=').unwrap_or((value, ""));
+    (key.to_string(), value.to_string())
+}
+
+fn specs(context: &Context) -> Result<Vec<ContainerSpec>> {
+    let services = context.config.services;
+    let mut specs = vec![];
+    if services.enabled(Container::Suricata) {
+        specs.push(ContainerSpec::from_run_args(
+            &build_suricata_args(context, true)?.args,
+        ));
+    }
+    if services.enabled(Container::EveBox) {
+        specs.push(ContainerSpec::from_run_args(
+            &build_evebox_args(context, true).args,
+        ));
+    }
+    Ok(specs)
+}
+
+pub(crate) fn export(context: &Context, format: Format) -> Result<String> {
+    let specs = specs(context)?;
+    match format {
+        Format::Compose => compose(&specs),
+        Format::Quadlet => Ok(concat(quadlet(&specs))),
+        Format::Systemd => Ok(concat(systemd(&specs))),
+        Format::Ansible => ansible(&specs),
+    }
+}
+
+/// Write the Quadlet or systemd units to a directory, such as
+/// /etc/containers/systemd for Quadlet or /etc/systemd/system.
+pub(crate) fn write_units(context: &Context, format: Format, dir: &Path) -> Result<Vec<PathBuf>> {
+    let units = match format {
+        Format::Quadlet => quadlet(&specs(context)?),
+        Format::Systemd => systemd(&specs(context)?),
+        Format::Compose | Format::Ansible => {
+            bail!("Only the quadlet and systemd formats are written to a directory")
+        }
+    };
+    std::fs::create_dir_all(dir)?;
+    let mut paths = vec![];
+    for (filename, unit) in units {
+        let path = dir.join(filename);
+        std::fs::write(&path, unit)?;
+        info!("Wrote {}", path.display());
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Join units into one output, each headed with its filename.
+fn concat(units: Vec<(String, String)>) -> String {
+    units
+        .into_iter()
+        .map(|(filename, unit)| format!("# {filename}\n{unit}"))
+        .collect()
+}
+
+fn strings(values: &[String]) -> Value {
+    Value::Sequence(values.iter().map(|v| Value::from(v.as_str())).collect())
+}
+
+fn pairs(values: &[(String, String)]) -> Value {
+    let mut map = Mapping::new();
+    for (key, value) in values {
+        map.insert(key.as_str().into(), value.as_str().into());
+    }
+    Value::Mapping(map)
+}
+
+fn compose(specs: &[ContainerSpec]) -> Result<String> {
+    let mut services = Mapping::new();
+    let mut volumes = Mapping::new();
+    for spec in specs {
+        let mut service = Mapping::new();
+        service.insert("image".into(), spec.image.as_str().into());
+        service.insert("container_name".into(), spec.name.as_str().into());
+        service.insert("restart".into(), spec.restart_policy().into());
+        if spec.host_network {
+            service.insert("network_mode".into(), "host".into());
+        }
+        if !spec.caps.is_empty() {
+            service.insert("cap_add".into(), strings(&spec.caps));
+        }
+        if !spec.ports.is_empty() {
+            service.insert("ports".into(), strings(&spec.ports));
+        }
+        service.insert("volumes".into(), strings(&spec.volumes));
+        if !spec.labels.is_empty() {
+            service.insert("labels".into(), pairs(&spec.labels));
+        }
+        if spec.log_driver.is_some() || !spec.log_opts.is_empty() {
+            let mut logging = Mapping::new();
+            if let Some(driver) = &spec.log_driver {
+                logging.insert("driver".into(), driver.as_str().into());
+            }
+            if !spec.log_opts.is_empty() {
+                logging.insert("options".into(), pairs(&spec.log_opts));
+            }
+            service.insert("logging".into(), Value::Mapping(logging));
+        }
+        if let Some(memory) = &spec.memory {
+            service.insert("mem_limit".into(), memory.as_str().into());
+        }
+        if let Some(cpus) = &spec.cpus {
+            service.insert("cpus".into(), cpus.as_str().into());
+        }
+        service.insert("command".into(), strings(&spec.command));
+        for volume in spec.named_volumes() {
+            volumes.insert(volume.into(), Value::Mapping(Mapping::new()));
+        }
+        let name = spec.name.trim_start_matches("simple-ids-");
+        services.insert(name.into(), Value::Mapping(service));
+    }
+    let mut compose = Mapping::new();
+    compose.insert("services".into(), Value::Mapping(services));
+    compose.insert("volumes".into(), Value::Mapping(volumes));
+    Ok(serde_yaml::to_string(&compose)?)
+}
+
+/// Quote an argument for a systemd command line.
+fn systemd_quote(arg: &str) -> String {
+    if arg.contains(char::is_whitespace) || arg.contains('"') || arg.contains('\'') {
+        format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        arg.to_string()
+    }
+}
+
+fn quadlet(specs: &[ContainerSpec]) -> Vec<(String, String)> {
+    let mut units = vec![];
+    for spec in specs {
+        let mut output = String::new();
+        output.push_str("[Unit]\n");
+        output.push_str(&format!("Description=Simple-IDS {}\n", spec.name));
+        output.push_str("Wants=network-online.target\n");
+        output.push_str("After=network-online.target\n\n");
+        output.push_str("[Container]\n");
+        output.push_str(&format!("ContainerName={}\n", spec.name));
+        output.push_str(&format!("Image={}\n", spec.image));
+        if let Some(network) = &spec.network {
+            output.push_str(&format!("Network={}\n", network));
+        }
+        for cap in &spec.caps {
+            output.push_str(&format!("AddCapability={}\n", cap.to_uppercase()));
+        }
+        for port in &spec.ports {
+            output.push_str(&format!("PublishPort={}\n", port));
+        }
+        for volume in &spec.volumes {
+            output.push_str(&format!("Volume={}\n", volume));
+        }
+        for (key, value) in &spec.labels {
+            output.push_str(&format!("Label={}={}\n", key, value));
+        }
+        if let Some(driver) = &spec.log_driver {
+            output.push_str(&format!("LogDriver={}\n", driver));
+        }
+        for (key, value) in &spec.log_opts {
+            output.push_str(&format!("PodmanArgs=--log-opt={}={}\n", key, value));
+        }
+        if let Some(memory) = &spec.memory {
+            output.push_str(&format!("PodmanArgs=--memory={}\n", memory));
+        }
+        if let Some(cpus) = &spec.cpus {
+            output.push_str(&format!("PodmanArgs=--cpus={}\n", cpus));
+        }
+        for env in &spec.env {
+            output.push_str(&format!(
+                "# Set the value of {env}: Environment={env}=...\n"
+            ));
+        }
+        let exec: Vec<String> = spec.command.iter().map(|arg| systemd_quote(arg)).collect();
+        output.push_str(&format!("Exec={}\n\n", exec.join(" ")));
+        output.push_str("[Service]\n");
+        output.push_str(&format!("Restart={}\n\n", spec.systemd_restart()));
+        output.push_str("[Install]\n");
+        output.push_str("WantedBy=multi-user.target default.target\n\n");
+        units.push((format!("{}.container", spec.name), output));
+    }
+    units
+}
+
+/// Units that run the container with podman directly, for Podman
+/// versions without Quadlet.
+fn systemd(specs: &[ContainerSpec]) -> Vec<(String, String)> {
+    let mut units = vec![];
+    for spec in specs {
+        // The container is created by the unit and removed when it
+        // stops, leaving restarts to systemd.
+        let args: Vec<String> = spec
+            .args
+            .iter()
+            .filter(|arg| *arg != "run" && *arg != "-d" && !arg.starts_with("--restart="))
+            .map(|arg| systemd_quote(arg))
+            .collect();
+        let mut output = String::new();
+        output.push_str("[Unit]\n");
+        output.push_str(&format!("Description=Simple-IDS {}\n", spec.name));
+        output.push_str("Wants=network-online.target\n");
+        output.push_str("After=network-online.target\n");
+        output.push_str("RequiresMountsFor=%t/containers\n\n");
+        output.push_str("[Service]\n");
+        output.push_str("Environment=PODMAN_SYSTEMD_UNIT=%n\n");
+        for env in &spec.env {
+            output.push_str(&format!(
+                "# Set the value of {env}: Environment={env}=...\n"
+            ));
+        }
+        output.push_str(&format!("Restart={}\n", spec.systemd_restart()));
+        output.push_str("TimeoutStopSec=70\n");
+        output.push_str(&format!(
+            "ExecStart={PODMAN} run --cidfile=%t/%n.ctr-id --cgroups=no-conmon --rm --sdnotify=conmon --replace -d {}\n",
+            args.join(" ")
+        ));
+        output.push_str(&format!(
+            "ExecStop={PODMAN} stop --ignore -t 10 --cidfile=%t/%n.ctr-id\n"
+        ));
+        output.push_str(&format!(
+            "ExecStopPost={PODMAN} rm -f --ignore -t 10 --cidfile=%t/%n.ctr-id\n"
+        ));
+        output.push_str("Type=notify\n");
+        output.push_str("NotifyAccess=all\n\n");
+        output.push_str("[Install]\n");
+        output.push_str("WantedBy=multi-user.target default.target\n\n");
+        units.push((format!("{}.service", spec.name), output));
+    }
+    units
+}
+
+fn ansible(specs: &[ContainerSpec]) -> Result<String> {
+    let mut tasks = vec![];
+    for spec in specs {
+        let mut params = Mapping::new();
+        params.insert("name".into(), spec.name.as_str().into());
+        params.insert("image".into(), spec.image.as_str().into());
+        params.insert("state".into(), "started".into());
+        params.insert("restart_policy".into(), spec.restart_policy().into());
+        if spec.host_network {
+            params.insert("network_mode".into(), "host".into());
+        }
+        if !spec.caps.is_empty() {
+            params.insert("capabilities".into(), strings(&spec.caps));
+        }
+        if !spec.ports.is_empty() {
+            params.insert("published_ports".into(), strings(&spec.ports));
+        }
+        params.insert("volumes".into(), strings(&spec.volumes));
+        if !spec.labels.is_empty() {
+            params.insert("labels".into(), pairs(&spec.labels));
+        }
+        if let Some(driver) = &spec.log_driver {
+            params.insert("log_driver".into(), driver.as_str().into());
+        }
+        if !spec.log_opts.is_empty() {
+            params.insert("log_options".into(), pairs(&spec.log_opts));
+        }
+        if let Some(memory) = &spec.memory {
+            params.insert("memory".into(), memory.as_str().into());
+        }
+        if let Some(cpus) = &spec.cpus {
+            params.insert("cpus".into(), cpus.as_str().into());
+        }
+        params.insert("command".into(), strings(&spec.command));
+
+        let mut task = Mapping::new();
+        task.insert("name".into(), format!("Run {} container", spec.name).into());
+        task.insert(
+            "community.docker.docker_container".into(),
+            Value::Mapping(params),
+        );
+        tasks.push(Value::Mapping(task));
+    }
+    Ok(serde_yaml::to_string(&tasks)?)
+}
@@ -0,0 +1,241 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+use serde::Serialize;
+
+use crate::{
+    config::{Config, Runtime},
+    container::{self, timeouts, CommandExt},
+    context::Context,
+    EVEBOX_CONTAINER_NAME, SURICATA_CONTAINER_NAME,
+};
+
+/// Status of a single sensor for the fleet overview.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct SensorStatus {
+    pub name: String,
+    pub host: Option<String>,
+    pub engine: Option<String>,
+    pub suricata: Option<String>,
+    pub evebox: Option<String>,
+    pub suricata_version: Option<String>,
+    pub evebox_version: Option<String>,
+    /// Age of the loaded rules in hours.
+    pub rule_age_hours: Option<u64>,
+    /// Kernel drop percentage from the last stats.log entry.
+    pub drop_percent: Option<f64>,
+    /// Disk usage percentage of the log volume.
+    pub disk_percent: Option<u8>,
+    pub error: Option<String>,
+}
+
+/// Collect the status of the local sensor and every configured host.
+pub(crate) fn status(config: &Config, runtime: Runtime) -> Vec<SensorStatus> {
+    let hosts = std::iter::once(("local".to_string(), None)).chain(
+        config
+            .hosts
+            .iter()
+            .map(|(name, host)| (name.to_string(), Some(host.to_string()))),
+    );
+
+    let mut sensors = vec![];
+    for (name, host) in hosts {
+        let mut sensor = SensorStatus {
+            name,
+            host: host.clone(),
+            ..Default::default()
+        };
+        match container::find_manager(runtime, host.as_deref()) {
+            Some(manager) => {
+                let context = Context::new(config.clone(), manager);
+                collect(&context, &mut sensor);
+            }
+            None => sensor.error = Some("no container manager found".to_string()),
+        }
+        sensors.push(sensor);
+    }
+    sensors
+}
+
+fn collect(context: &Context, sensor: &mut SensorStatus) {
+    let manager = &context.manager;
+    sensor.engine = manager
+        .version()
+        .ok()
+        .map(|version| format!("{} {}", manager, version));
+
+    let states = manager.states(&[SURICATA_CONTAINER_NAME, EVEBOX_CONTAINER_NAME]);
+    sensor.suricata = Some(
+        states
+            .get(SURICATA_CONTAINER_NAME)
+            .map(|state| state.status.clone())
+            .unwrap_or_else(|| "not running".to_string()),
+    );
+    sensor.evebox = Some(
+        states
+            .get(EVEBOX_CONTAINER_NAME)
+            .map(|state| state.status.clone())
+            .unwrap_or_else(|| "not running".to_string()),
+    );
+
+    if manager.is_running(SURICATA_CONTAINER_NAME) {
+        sensor.suricata_version = exec(context, SURICATA_CONTAINER_NAME, &["suricata", "-V"])
+            .and_then(|output| output.split_whitespace().last().map(String::from));
+        sensor.rule_age_hours = exec(
+            context,
+            SURICATA_CONTAINER_NAME,
+            &[
+                "sh",
+                "-c",
+                "echo $(( $(date +%s) - $(stat -c %Y /var/lib/suricata/rules/suricata.rules) ))",
+            ],
+        )
+        .and_then(|output| output.trim().parse::<u64>().ok())
+        .map(|seconds| seconds / 3600);
+        sensor.drop_percent = exec(
+            context,
+            SURICATA_CONTAINER_NAME,
+            &["tail", "-n", "500", "/var/log/suricata/stats.log"],
+        )
+        .and_then(|output| parse_drop_percent(&output));
+        sensor.disk_percent = exec(
+            context,
+            SURICATA_CONTAINER_NAME,
+            &["df", "-P", "/var/log/suricata"],
+        )
+        .and_then(|output| parse_df_percent(&output));
+    }
+
+    if manager.is_running(EVEBOX_CONTAINER_NAME) {
+        sensor.evebox_version = exec(context, EVEBOX_CONTAINER_NAME, &["evebox", "version"])
+            .and_then(|output| output.split_whitespace().nth(1).map(String::from));
+    }
+}
+
+/// Run a command in a container returning its output, or None on any
+/// failure.
+pub(crate) fn exec(context: &Context, container: &str, args: &[&str]) -> Option<String> {
+    let output = context
+        .manager
+        .command()
+        .arg("exec")
+        .arg(container)
+        .args(args)
+        .output_timeout(timeouts().query)
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Find the kernel packet and drop counters in the most recent stats.log
+/// entry and return the drop percentage.
+fn parse_drop_percent(stats: &str) -> Option<f64> {
+    let (packets, drops) = parse_capture_counters(stats)?;
+    Some(drop_percent(packets, drops))
+}
+
+/// Find the kernel packet and drop counters in the most recent stats.log
+/// entry.
+pub(crate) fn parse_capture_counters(stats: &str) -> Option<(u64, u64)> {
+    let mut packets = None;
+    let mut drops = None;
+    for line in stats.lines() {
+        // Each entry starts with a header, only the last entry matters.
+        if line.starts_with("Date:") {
+            packets = None;
+            drops = None;
+            continue;
+        }
+        let mut fields = line.split('|').map(str::trim);
+        let counter = fields.next();
+        let value = fields.nth(1).and_then(|value| value.parse::<u64>().ok());
+        match counter {
+            Some("capture.kernel_packets") => packets = value,
+            Some("capture.kernel_drops") => drops = value,
+            _ => {}
+        }
+    }
+    Some((packets?, drops.unwrap_or(0)))
+}
+
+pub(crate) fn drop_percent(packets: u64, drops: u64) -> f64 {
+    if packets + drops == 0 {
+        return 0.0;
+    }
+    drops as f64 * 100.0 / (packets + drops) as f64
+}
+
+pub(crate) fn parse_df_percent(df: &str) -> Option<u8> {
+    df.lines()
+        .nth(1)?
+        .split_whitespace()
+        .nth(4)?
+        .trim_end_matches('%')
+        .parse()
+        .ok()
+}
+
+/// Print the sensor status as a table.
+pub(crate) fn print_table(sensors: &[SensorStatus]) {
+    fn or_dash<T: ToString>(value: &Option<T>) -> String {
+        value
+            .as_ref()
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| "-".to_string())
+    }
+
+    let header = [
+        "NAME", "SURICATA", "EVEBOX", "RULE AGE", "DROPS", "DISK", "VERSIONS",
+    ];
+    let mut rows: Vec<Vec<String>> = vec![header.iter().map(|s| s.to_string()).collect()];
+    for sensor in sensors {
+        if let Some(error) = &sensor.error {
+            rows.push(vec![sensor.name.clone(), format!("error: {}", error)]);
+            continue;
+        }
+        rows.push(vec![
+            sensor.name.clone(),
+            or_dash(&sensor.suricata),
+            or_dash(&sensor.evebox),
+            sensor
+                .rule_age_hours
+                .map(|hours| format!("{}h", hours))
+                .unwrap_or_else(|| "-".to_string()),
+            sensor
+                .drop_percent
+                .map(|drops| format!("{:.2}%", drops))
+                .unwrap_or_else(|| "-".to_string()),
+            sensor
+                .disk_percent
+                .map(|disk| format!("{}%", disk))
+                .unwrap_or_else(|| "-".to_string()),
+            format!(
+                "{} / suricata {} / evebox {}",
+                or_dash(&sensor.engine),
+                or_dash(&sensor.suricata_version),
+                or_dash(&sensor.evebox_version)
+            ),
+        ]);
+    }
+
+    let columns = header.len();
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(|cell| cell.len())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| format!("{:width$}", cell, width = widths[i]))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
@@ -0,0 +1,124 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+use std::net::IpAddr;
+
+use maxminddb::{geoip2, Reader};
+use tracing::error;
+
+use crate::config::GeoIpConfig;
+
+/// GeoIP lookups using MaxMind format databases, such as the free
+/// GeoLite2 Country and ASN databases.
+pub(crate) struct GeoIp {
+    country: Option<Reader<Vec<u8>>>,
+    asn: Option<Reader<Vec<u8>>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GeoInfo {
+    pub country: Option<String>,
+    pub asn: Option<u32>,
+    pub org: Option<String>,
+}
+
+impl GeoInfo {
+    /// The ASN and organization, like "AS15169 Google LLC".
+    pub(crate) fn asn_label(&self) -> Option<String> {
+        self.asn.map(|asn| match &self.org {
+            Some(org) => format!("AS{asn} {org}"),
+            None => format!("AS{asn}"),
+        })
+    }
+
+    pub(crate) fn label(&self) -> String {
+        [self.country.clone(), self.asn_label()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+impl GeoIp {
+    /// Open the configured databases, returns `None` if no databases
+    /// are configured or none could be opened.
+    pub(crate) fn open(config: &GeoIpConfig) -> Option<Self> {
+        let open = |path: &Option<String>| {
+            path.as_ref()
+                .and_then(|path| match Reader::open_readfile(path) {
+                    Ok(reader) => Some(reader),
+                    Err(err) => {
+                        error!("Failed to open GeoIP database {path}: {err}");
+                        None
+                    }
+                })
+        };
+        let country = open(&config.database);
+        let asn = open(&config.asn_database);
+        if country.is_none() && asn.is_none() {
+            return None;
+        }
+        Some(Self { country, asn })
+    }
+
+    /// Lookup an address, returns `None` for addresses that are not
+    /// external, or not found.
+    pub(crate) fn lookup(&self, addr: IpAddr) -> Option<GeoInfo> {
+        if !is_external(addr) {
+            return None;
+        }
+        let mut info = GeoInfo::default();
+        if let Some(reader) = &self.country {
+            if let Ok(country) = reader.lookup::<geoip2::Country>(addr) {
+                info.country = country
+                    .country
+                    .and_then(|country| country.iso_code)
+                    .map(|code| code.to_string());
+            }
+        }
+        if let Some(reader) = &self.asn {
+            if let Ok(asn) = reader.lookup::<geoip2::Asn>(addr) {
+                info.asn = asn.autonomous_system_number;
+                info.org = asn.autonomous_system_organization.map(|s| s.to_string());
+            }
+        }
+        if info.country.is_none() && info.asn.is_none() {
+            return None;
+        }
+        Some(info)
+    }
+
+    /// Lookup an address as found in an eve record.
+    pub(crate) fn lookup_str(&self, addr: &str) -> Option<GeoInfo> {
+        addr.parse().ok().and_then(|addr| self.lookup(addr))
+    }
+}
+
+/// Return true if the address is globally routable, there is no point
+/// looking up private or local addresses.
+fn is_external(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => {
+            let octets = addr.octets();
+            let shared = octets[0] == 100 && (octets[1] & 0xc0) == 64;
+            !(addr.is_private()
+                || addr.is_loopback()
+                || addr.is_link_local()
+                || addr.is_broadcast()
+                || addr.is_multicast()
+                || addr.is_unspecified()
+                || shared)
+        }
+        IpAddr::V6(addr) => {
+            let first = addr.segments()[0];
+            let unique_local = (first & 0xfe00) == 0xfc00;
+            let link_local = (first & 0xffc0) == 0xfe80;
+            !(addr.is_loopback()
+                || addr.is_multicast()
+                || addr.is_unspecified()
+                || unique_local
+                || link_local)
+        }
+    }
+}
@@ -0,0 +1,236 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Vulnerability scanning of the container images after an update,
+//! with Trivy or Grype if installed, otherwise with Trivy in a
+//! container.
+
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use tracing::{info, warn};
+
+use crate::{
+    container::{timeouts, CommandExt, ContainerManager},
+    context::Context,
+};
+
+/// Image used when no scanner is installed, pinned as it is given the
+/// container engine socket. Can be changed with `updates.scanner-image`.
+const TRIVY_IMAGE: &str = "docker.io/aquasec/trivy:0.56.2";
+
+/// Volume caching the Trivy vulnerability database between scans.
+const TRIVY_CACHE_VOLUME: &str = "simple-ids-trivy-cache";
+
+/// The most findings listed per image, the rest are only counted.
+const MAX_LISTED: usize = 10;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Scanner {
+    Trivy,
+    Grype,
+    /// Trivy run with the container engine.
+    TrivyContainer,
+}
+
+impl std::fmt::Display for Scanner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scanner::Trivy => write!(f, "trivy"),
+            Scanner::Grype => write!(f, "grype"),
+            Scanner::TrivyContainer => write!(f, "trivy (container)"),
+        }
+    }
+}
+
+/// A critical vulnerability found in an image.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Finding {
+    pub id: String,
+    pub package: String,
+    pub version: String,
+    pub fixed: Option<String>,
+}
+
+/// Find a scanner. The installed scanners can only see local images.
+fn scanner(manager: &ContainerManager) -> Scanner {
+    if manager.host().is_none() {
+        for scanner in [Scanner::Trivy, Scanner::Grype] {
+            let installed = Command::new(scanner.to_string())
+                .arg("--version")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map(|status| status.success())
+                .unwrap_or(false);
+            if installed {
+                return scanner;
+            }
+        }
+    }
+    Scanner::TrivyContainer
+}
+
+/// Scan the images, printing a summary of the critical findings.
+/// Returns the number of critical findings.
+pub(crate) fn scan(context: &Context, images: &[String]) -> Result<usize> {
+    let scanner = scanner(&context.manager);
+    let mut total = 0;
+    for image in images {
+        info!("Scanning {image} with {scanner}");
+        let findings = match scan_image(context, scanner, image) {
+            Ok(findings) => findings,
+            Err(err) => bail!("Failed to scan {image}: {err}"),
+        };
+        print_summary(image, &findings);
+        total += findings.len();
+    }
+    Ok(total)
+}
+
+fn scan_image(context: &Context, scanner: Scanner, image: &str) -> Result<Vec<Finding>> {
+    let mut command = match scanner {
+        Scanner::Trivy => {
+            let mut command = Command::new("trivy");
+            command.args(trivy_args(image));
+            command
+        }
+        Scanner::Grype => {
+            let mut command = Command::new("grype");
+            command.args([image, "--output", "json", "--quiet"]);
+            command
+        }
+        Scanner::TrivyContainer => {
+            // Trivy reads the image through the engine socket.
+            let socket = if context.manager.is_podman() {
+                "/run/podman/podman.sock"
+            } else {
+                "/var/run/docker.sock"
+            };
+            let mut command = context.manager.command();
+            command
+                .args(["run", "--rm"])
+                .arg(format!("--volume={socket}:/var/run/docker.sock"))
+                .arg(format!("--volume={TRIVY_CACHE_VOLUME}:/root/.cache"))
+                .arg(
+                    context
+                        .config
+                        .updates
+                        .scanner_image
+                        .as_deref()
+                        .unwrap_or(TRIVY_IMAGE),
+                )
+                .args(trivy_args(image));
+            command
+        }
+    };
+    // Downloading the vulnerability database can take as long as a pull.
+    let output = command.output_timeout(timeouts().pull)?;
+    if !output.status.success() {
+        bail!(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    Ok(match scanner {
+        Scanner::Grype => parse_grype(&json),
+        Scanner::Trivy | Scanner::TrivyContainer => parse_trivy(&json),
+    })
+}
+
+fn trivy_args(image: &str) -> Vec<String> {
+    [
+        "image",
+        "--quiet",
+        "--severity",
+        "CRITICAL",
+        "--format",
+        "json",
+        image,
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect()
+}
+
+fn parse_trivy(json: &serde_json::Value) -> Vec<Finding> {
+    let mut findings = vec![];
+    for result in json["Results"].as_array().into_iter().flatten() {
+        for vuln in result["Vulnerabilities"].as_array().into_iter().flatten() {
+            if vuln["Severity"].as_str() != Some("CRITICAL") {
+                continue;
+            }
+            findings.push(Finding {
+                id: vuln["VulnerabilityID"].as_str().unwrap_or("").to_string(),
+                package: vuln["PkgName"].as_str().unwrap_or("").to_string(),
+                version: vuln["InstalledVersion"].as_str().unwrap_or("").to_string(),
+                fixed: vuln["FixedVersion"]
+                    .as_str()
+                    .filter(|fixed| !fixed.is_empty())
+                    .map(String::from),
+            });
+        }
+    }
+    dedup(findings)
+}
+
+fn parse_grype(json: &serde_json::Value) -> Vec<Finding> {
+    let mut findings = vec![];
+    for entry in json["matches"].as_array().into_iter().flatten() {
+        let vuln = &entry["vulnerability"];
+        if vuln["severity"].as_str() != Some("Critical") {
+            continue;
+        }
+        let fixed: Vec<&str> = vuln["fix"]["versions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|version| version.as_str())
+            .collect();
+        findings.push(Finding {
+            id: vuln["id"].as_str().unwrap_or("").to_string(),
+            package: entry["artifact"]["name"].as_str().unwrap_or("").to_string(),
+            version: entry["artifact"]["version"]
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+            fixed: (!fixed.is_empty()).then(|| fixed.join(", ")),
+        });
+    }
+    dedup(findings)
+}
+
+/// The same vulnerability is often reported for several files of a
+/// package.
+fn dedup(mut findings: Vec<Finding>) -> Vec<Finding> {
+    findings.sort_by(|a, b| (&a.id, &a.package).cmp(&(&b.id, &b.package)));
+    findings.dedup_by(|a, b| a.id == b.id && a.package == b.package);
+    findings
+}
+
+fn print_summary(image: &str, findings: &[Finding]) {
+    if findings.is_empty() {
+        info!("{image}: no critical vulnerabilities found");
+        return;
+    }
+    warn!(
+        "{image}: {} critical vulnerabilit{} found",
+        findings.len(),
+        if findings.len() == 1 { "y" } else { "ies" }
+    );
+    for finding in findings.iter().take(MAX_LISTED) {
+        let fixed = match &finding.fixed {
+            Some(fixed) => format!("fixed in {fixed}").green().to_string(),
+            None => "no fix available".dimmed().to_string(),
+        };
+        println!(
+            "  {} {} {} ({})",
+            finding.id.red(),
+            finding.package,
+            finding.version,
+            fixed
+        );
+    }
+    if findings.len() > MAX_LISTED {
+        println!("  ... and {} more", findings.len() - MAX_LISTED);
+    }
+}
@@ -0,0 +1,134 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! A journal of what Simple-IDS did, such as starting and stopping the
+//! containers and updating rules, kept in the state directory as JSON
+//! lines.
+
+use std::io::{BufRead, BufReader, Write};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{display, state};
+
+const FILENAME: &str = "journal.jsonl";
+
+/// Once the journal grows past this size, it is trimmed down to the
+/// newest half of its entries.
+const MAX_SIZE: u64 = 2 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Kind {
+    Start,
+    Stop,
+    Pause,
+    Resume,
+    Exit,
+    RuleUpdate,
+    RuleUpdateFailed,
+    ImageUpdate,
+    ImageUpdateFailed,
+    SelfUpdate,
+    WatchdogRestart,
+    ConfigReload,
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = serde_json::to_value(self).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}", kind.as_str().unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Entry {
+    pub timestamp: DateTime<Utc>,
+    pub kind: Kind,
+    pub message: String,
+}
+
+/// Record an entry in the journal. Failures are only logged at debug
+/// level, the journal is not worth failing an operation over.
+pub(crate) fn record(kind: Kind, message: impl Into<String>) {
+    let entry = Entry {
+        timestamp: Utc::now(),
+        kind,
+        message: message.into(),
+    };
+    if let Err(err) = append(&entry) {
+        debug!("Failed to write journal entry: {err}");
+    }
+}
+
+/// Append an entry, holding the journal lock so an entry appended by
+/// another process can't be lost to a trim.
+fn append(entry: &Entry) -> Result<()> {
+    let _lock = state::lock_wait("journal")?;
+    let path = state::file_path(FILENAME);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    let size = file.metadata()?.len();
+    drop(file);
+
+    if size > MAX_SIZE {
+        let entries = read()?;
+        let mut file = tempfile::NamedTempFile::new_in(path.parent().unwrap_or(&path))?;
+        for entry in &entries[entries.len() / 2..] {
+            writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        }
+        file.persist(&path)?;
+    }
+    Ok(())
+}
+
+/// Read all entries, oldest first. Lines that fail to parse are
+/// skipped.
+pub(crate) fn read() -> Result<Vec<Entry>> {
+    let path = state::file_path(FILENAME);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let file = std::fs::File::open(path)?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+/// Print the last `count` entries of the journal.
+pub(crate) fn print(count: usize, json: bool) -> Result<()> {
+    let entries = read()?;
+    let entries = &entries[entries.len().saturating_sub(count)..];
+    if json {
+        println!("{}", serde_json::to_string_pretty(entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        println!("No history recorded");
+    }
+    for entry in entries {
+        let kind = match entry.kind {
+            Kind::Exit
+            | Kind::RuleUpdateFailed
+            | Kind::ImageUpdateFailed
+            | Kind::WatchdogRestart => entry.kind.to_string().red(),
+            _ => entry.kind.to_string().normal(),
+        };
+        println!(
+            "{}  {:<20} {}",
+            display::format_minutes(&entry.timestamp),
+            kind,
+            entry.message
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,151 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+use std::{path::Path, thread, time::Duration};
+
+use anyhow::{bail, Result};
+use chrono::Utc;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    container::{timeouts, CommandExt},
+    context::Context,
+    state::State,
+    system, SURICATA_CONTAINER_NAME,
+};
+
+const CRON_FILENAME: &str = "/etc/cron.d/simple-ids";
+
+/// How often log rotation is run by default, in minutes.
+const DEFAULT_INTERVAL: u32 = 10;
+
+/// How often log rotation is run, in minutes.
+fn interval(context: &Context) -> u32 {
+    context
+        .config
+        .suricata
+        .tuning
+        .rotate_interval
+        .unwrap_or(DEFAULT_INTERVAL)
+        .clamp(1, 24 * 60)
+}
+
+/// Run logrotate in the Suricata container, recording the result in
+/// the state so failures show up in the status.
+pub(crate) fn rotate(context: &Context, force: bool) -> Result<()> {
+    let flags = if force { "-fv" } else { "-v" };
+    let result = context
+        .manager
+        .command()
+        .args([
+            "exec",
+            SURICATA_CONTAINER_NAME,
+            "logrotate",
+            flags,
+            "/etc/logrotate.d/suricata",
+        ])
+        .output_timeout(timeouts().control)
+        .and_then(|output| {
+            if output.status.success() {
+                Ok(())
+            } else {
+                bail!(String::from_utf8_lossy(&output.stderr).trim().to_string())
+            }
+        });
+
+    let error = result.as_ref().err().map(|err| err.to_string());
+    if let Err(err) = State::update(|state| {
+        state.last_logrotate = Some(Utc::now());
+        state.last_logrotate_error = error;
+    }) {
+        error!("Failed to record log rotation result: {err}");
+    }
+    result
+}
+
+/// Schedule log rotation with the host cron daemon, running the
+/// `rotate-logs` command of this executable.
+pub(crate) fn install_schedule(minutes: u32) -> Result<()> {
+    if !Path::new("/etc/cron.d").is_dir() {
+        bail!("/etc/cron.d does not exist");
+    }
+    let exe = std::env::current_exe()?;
+    let cwd = std::env::current_dir()?;
+    let schedule = if minutes < 60 {
+        format!("*/{minutes} * * * *")
+    } else {
+        format!("0 */{} * * *", minutes / 60)
+    };
+    let entry = format!(
+        "# Installed by Simple-IDS to rotate the Suricata logs.\n\
+         {} root cd {} && {} rotate-logs >/dev/null 2>&1\n",
+        schedule,
+        system::cron_quote(&cwd.to_string_lossy()),
+        system::cron_quote(&exe.to_string_lossy())
+    );
+    if std::fs::read_to_string(CRON_FILENAME).ok().as_deref() == Some(entry.as_str()) {
+        return Ok(());
+    }
+    std::fs::write(CRON_FILENAME, entry)?;
+    info!("Installed log rotation schedule {CRON_FILENAME}");
+    Ok(())
+}
+
+pub(crate) fn remove_schedule() {
+    if Path::new(CRON_FILENAME).exists() {
+        if let Err(err) = std::fs::remove_file(CRON_FILENAME) {
+            error!("Failed to remove {CRON_FILENAME}: {err}");
+        }
+    }
+}
+
+/// Start log rotation for a detached Suricata.
+///
+/// Rotation is driven by the host cron daemon if enabled with
+/// `logrotate-cron`, otherwise, or if that fails, by a loop inside the
+/// container which will stop if the container is restarted outside of
+/// Simple-IDS.
+pub(crate) fn start_detached(context: &Context) -> Result<()> {
+    if context.manager.host().is_none() {
+        if context.config.suricata.logrotate_cron {
+            match install_schedule(interval(context)) {
+                Ok(()) => return Ok(()),
+                Err(err) => warn!("Failed to schedule log rotation with cron: {err}"),
+            }
+        } else {
+            remove_schedule();
+        }
+    }
+
+    info!("Starting Suricata log rotation inside the container");
+    context
+        .manager
+        .command()
+        .args([
+            "exec",
+            "-d",
+            SURICATA_CONTAINER_NAME,
+            "sh",
+            "-c",
+            &format!(
+                "while true; do logrotate -v /etc/logrotate.d/suricata > /tmp/last_logrotate 2>&1; sleep {}; done",
+                interval(context) * 60
+            ),
+        ])
+        .status_output_timeout(timeouts().control)?;
+    Ok(())
+}
+
+/// Rotate logs from a thread while running in the foreground.
+pub(crate) fn start_foreground(context: &Context) {
+    let context = context.clone();
+    let interval = Duration::from_secs(interval(&context) as u64 * 60);
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        debug!("Running Suricata log rotation");
+        if let Err(err) = rotate(&context, false) {
+            error!("Suricata log rotation failed: {err}");
+        }
+        crate::diskpolicy::check(&context, false);
+    });
+}
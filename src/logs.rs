@@ -3,27 +3,59 @@
 
 use std::{
     io::{BufRead, BufReader, Read},
-    process::Stdio,
+    process::{Command, Stdio},
     thread,
+    time::{Duration, Instant},
 };
 
+use chrono::{DateTime, FixedOffset};
 use clap::Parser;
 use regex::Regex;
+use tracing::{debug, error, warn};
 
-use crate::{context::Context, EVEBOX_CONTAINER_NAME, SURICATA_CONTAINER_NAME};
+use crate::{
+    container::{timeouts, CommandExt},
+    context::Context,
+    display,
+    eve::{self, EVE_FILENAME},
+    geoip::GeoIp,
+    state, EVEBOX_CONTAINER_NAME, SURICATA_CONTAINER_NAME,
+};
+
+/// Directory in the state directory holding the logs of the containers
+/// last removed.
+const PREVIOUS_DIR: &str = "previous-logs";
+
+/// How long to wait for Suricata to finish starting when looking for
+/// startup warnings.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Warning and error lines, "Warning:" from Suricata 7, "<Warning>" from
+/// earlier versions.
+const ISSUE_PATTERN: &str = r"(?:^|\s)(?:<(Warning|Error)>\s*(?:--\s*)?|(Warning|Error):\s*)(.*)";
 
 #[derive(Parser, Debug)]
 pub(crate) struct LogArgs {
     #[arg(short, long, help = "Follow log output")]
     follow: bool,
-    #[arg(help = "Service to display logs for, default = all")]
+    #[arg(long, help = "Include Suricata alerts from eve.json")]
+    with_events: bool,
+    #[arg(
+        long,
+        help = "Show the logs of the containers before they were last removed"
+    )]
+    previous: bool,
+    #[arg(
+        help = "Service to display logs for, default = all",
+        value_parser = ["suricata", "evebox"]
+    )]
     services: Vec<String>,
 }
 
 pub(crate) fn logs(ctx: &Context, args: LogArgs) {
     let containers = [SURICATA_CONTAINER_NAME, EVEBOX_CONTAINER_NAME];
     let max_container_name_len = containers.iter().map(|s| s.len()).max().unwrap_or(0);
-    let mut handles = vec![];
+    let mut selected = vec![];
 
     for container in containers {
         if !args.services.is_empty() {
@@ -42,6 +74,28 @@ pub(crate) fn logs(ctx: &Context, args: LogArgs) {
             }
         }
 
+        selected.push(container);
+    }
+
+    if args.previous {
+        if args.follow {
+            warn!("Can't follow the previous logs");
+        }
+        let entries = selected
+            .iter()
+            .flat_map(|container| read_previous(container, max_container_name_len))
+            .collect();
+        let alerts = if args.with_events {
+            read_alerts(ctx, max_container_name_len)
+        } else {
+            vec![]
+        };
+        print_sorted(entries, alerts);
+        return;
+    }
+
+    let mut commands = vec![];
+    for container in selected {
         let mut command = ctx.manager.command();
         command.arg("logs");
         command.arg("--timestamps");
@@ -49,6 +103,187 @@ pub(crate) fn logs(ctx: &Context, args: LogArgs) {
             command.arg("--follow");
         }
         command.arg(container);
+        commands.push((container, command));
+    }
+
+    if args.follow {
+        if args.with_events {
+            follow_alerts(ctx, max_container_name_len);
+        }
+        follow_logs(commands, max_container_name_len);
+    } else {
+        let alerts = if args.with_events {
+            read_alerts(ctx, max_container_name_len)
+        } else {
+            vec![]
+        };
+        sorted_logs(commands, alerts, max_container_name_len);
+    }
+}
+
+fn previous_path(container: &str, stream: &str) -> std::path::PathBuf {
+    state::file_path(PREVIOUS_DIR).join(format!("{container}.{stream}.log"))
+}
+
+/// Save the logs of a container that is about to be removed, as they
+/// are often the only clue to why it stopped. Containers without any
+/// output leave the previously saved logs in place.
+pub(crate) fn save_previous(ctx: &Context, container: &str) {
+    if !ctx.manager.container_exists(container) {
+        return;
+    }
+    let output = match ctx
+        .manager
+        .command()
+        .args(["logs", "--timestamps", container])
+        .output_timeout(timeouts().query)
+    {
+        Ok(output) => output,
+        Err(err) => {
+            debug!("Failed to read the logs of {container}: {err}");
+            return;
+        }
+    };
+    if output.stdout.is_empty() && output.stderr.is_empty() {
+        return;
+    }
+    if let Err(err) = std::fs::create_dir_all(state::file_path(PREVIOUS_DIR)) {
+        warn!("Failed to save the logs of {container}: {err}");
+        return;
+    }
+    for (stream, buf) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+        if let Err(err) = std::fs::write(previous_path(container, stream), buf) {
+            warn!("Failed to save the logs of {container}: {err}");
+        }
+    }
+}
+
+/// A warning or error logged by Suricata while starting.
+pub(crate) struct StartupIssue {
+    pub error: bool,
+    pub message: String,
+    pub count: usize,
+}
+
+/// Wait for Suricata to finish starting, or to exit, then return the
+/// warnings and errors it logged, such as rules that failed to load or
+/// af-packet falling back from mmap.
+pub(crate) fn suricata_startup_issues(ctx: &Context) -> Vec<StartupIssue> {
+    let started = Instant::now();
+    let mut lines = vec![];
+    loop {
+        let output = ctx
+            .manager
+            .command()
+            .args(["logs", SURICATA_CONTAINER_NAME])
+            .output_timeout(timeouts().query);
+        if let Ok(output) = output {
+            lines = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .chain(String::from_utf8_lossy(&output.stderr).lines())
+                .map(String::from)
+                .collect();
+        }
+        if lines.iter().any(|line| line.contains("Engine started"))
+            || !ctx.manager.is_running(SURICATA_CONTAINER_NAME)
+            || started.elapsed() > STARTUP_TIMEOUT
+        {
+            break;
+        }
+        thread::sleep(Duration::from_secs(2));
+    }
+
+    let re = Regex::new(ISSUE_PATTERN).unwrap();
+    let mut issues: Vec<StartupIssue> = vec![];
+    for line in &lines {
+        let Some(captures) = re.captures(line) else {
+            continue;
+        };
+        let error = captures.get(1).or(captures.get(2)).map(|m| m.as_str()) == Some("Error");
+        let message = captures[3].trim().to_string();
+        match issues.iter_mut().find(|issue| issue.message == message) {
+            Some(issue) => issue.count += 1,
+            None => issues.push(StartupIssue {
+                error,
+                message,
+                count: 1,
+            }),
+        }
+    }
+    issues
+}
+
+/// Read the saved logs of a removed container.
+fn read_previous(container: &str, max_container_name_len: usize) -> Vec<LogEntry> {
+    let mut entries = vec![];
+    for stream in ["stdout", "stderr"] {
+        if let Ok(buf) = std::fs::read(previous_path(container, stream)) {
+            let prefix = format!(
+                "{:width$} | {}",
+                container,
+                stream,
+                width = max_container_name_len
+            );
+            entries.extend(parse_log_lines(&prefix, &buf));
+        }
+    }
+    if entries.is_empty() {
+        warn!("No previous logs saved for {container}");
+    }
+    entries
+}
+
+fn alert_prefix(max_container_name_len: usize) -> String {
+    format!("{:width$} | alert", "eve", width = max_container_name_len)
+}
+
+/// Read the alerts from eve.json as log entries.
+fn read_alerts(ctx: &Context, max_container_name_len: usize) -> Vec<LogEntry> {
+    let geoip = GeoIp::open(&ctx.config.geoip);
+    let prefix = alert_prefix(max_container_name_len);
+    let mut entries = vec![];
+    if let Err(err) = eve::exec_lines(
+        ctx,
+        &["grep", "-F", "\"event_type\":\"alert\"", EVE_FILENAME],
+        |line| {
+            if let Ok(event) = serde_json::from_str::<serde_json::Value>(line) {
+                entries.push(LogEntry {
+                    timestamp: event["timestamp"].as_str().and_then(eve::parse_timestamp),
+                    prefix: prefix.clone(),
+                    line: eve::format_event(&event, geoip.as_ref()),
+                });
+            }
+        },
+    ) {
+        error!("Failed to read alerts: {err}");
+    }
+    entries
+}
+
+/// Print new alerts as they are logged, from a background thread.
+fn follow_alerts(ctx: &Context, max_container_name_len: usize) {
+    let ctx = ctx.clone();
+    thread::spawn(move || {
+        let geoip = GeoIp::open(&ctx.config.geoip);
+        let prefix = alert_prefix(max_container_name_len);
+        if let Err(err) = eve::exec_lines(&ctx, &["tail", "-F", "-n", "0", EVE_FILENAME], |line| {
+            if !line.contains("\"event_type\":\"alert\"") {
+                return;
+            }
+            if let Ok(event) = serde_json::from_str::<serde_json::Value>(line) {
+                println!("{} | {}", prefix, eve::format_event(&event, geoip.as_ref()));
+            }
+        }) {
+            error!("Failed to follow alerts: {err}");
+        }
+    });
+}
+
+/// Print logs as they arrive, each stream from its own thread.
+fn follow_logs(commands: Vec<(&'static str, Command)>, max_container_name_len: usize) {
+    let mut handles = vec![];
+
+    for (container, mut command) in commands {
         let handle = thread::spawn(move || {
             match command
                 .stdout(Stdio::piped())
@@ -102,13 +337,107 @@ pub(crate) fn logs(ctx: &Context, args: LogArgs) {
     }
 }
 
+/// Read all the logs, then print them merged in timestamp order so the
+/// output of the containers makes a single timeline.
+fn sorted_logs(
+    commands: Vec<(&'static str, Command)>,
+    alerts: Vec<LogEntry>,
+    max_container_name_len: usize,
+) {
+    let mut entries: Vec<LogEntry> = vec![];
+
+    for (container, mut command) in commands {
+        let output = match command.output_timeout(timeouts().control) {
+            Ok(output) => output,
+            Err(err) => {
+                error!("Failed to get logs for {container}: {err}");
+                continue;
+            }
+        };
+        for (stream, buf) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+            let prefix = format!(
+                "{:width$} | {}",
+                container,
+                stream,
+                width = max_container_name_len
+            );
+            entries.extend(parse_log_lines(&prefix, buf));
+        }
+    }
+    print_sorted(entries, alerts);
+}
+
+/// Print log entries in timestamp order, with the alerts from the
+/// period they cover.
+fn print_sorted(mut entries: Vec<LogEntry>, alerts: Vec<LogEntry>) {
+    // Only include alerts from the period covered by the logs.
+    if let Some(first) = entries.iter().filter_map(|entry| entry.timestamp).min() {
+        entries.extend(
+            alerts
+                .into_iter()
+                .filter(|alert| alert.timestamp.map(|ts| ts >= first).unwrap_or(false)),
+        );
+    }
+
+    // A stable sort keeps lines with equal timestamps in their
+    // original order.
+    entries.sort_by_key(|entry| entry.timestamp);
+
+    let re = Regex::new(EVEBOX_TS_PATTERN).unwrap();
+    for entry in entries {
+        let line = localize_timestamp(&entry.line);
+        let line = re.replace_all(&line, "");
+        println!("{} | {}", entry.prefix, line);
+    }
+}
+
+struct LogEntry {
+    timestamp: Option<DateTime<FixedOffset>>,
+    prefix: String,
+    line: String,
+}
+
+fn parse_log_lines(prefix: &str, buf: &[u8]) -> Vec<LogEntry> {
+    let mut entries = vec![];
+    let mut last_timestamp = None;
+    for line in String::from_utf8_lossy(buf).lines() {
+        // The timestamp added by --timestamps is the first field. Lines
+        // without one inherit the timestamp of the line before so they
+        // stay together.
+        let timestamp = line
+            .split_once(' ')
+            .and_then(|(ts, _)| DateTime::parse_from_rfc3339(ts).ok())
+            .or(last_timestamp);
+        last_timestamp = timestamp;
+        entries.push(LogEntry {
+            timestamp,
+            prefix: prefix.to_string(),
+            line: line.to_string(),
+        });
+    }
+    entries
+}
+
+/// Convert the UTC timestamp added by --timestamps to the display
+/// timezone.
+fn localize_timestamp(line: &str) -> String {
+    if let Some((ts, rest)) = line.split_once(' ') {
+        if let Ok(ts) = DateTime::parse_from_rfc3339(ts) {
+            return format!("{} {}", display::format_millis(&ts), rest);
+        }
+    }
+    line.to_string()
+}
+
+const EVEBOX_TS_PATTERN: &str = r".....\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}.....";
+
 fn log_line_printer<R: Read + Sync + Send + 'static>(prefix: String, output: R) {
-    let evebox_ts_pattern = r".....\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}.....";
-    let re = Regex::new(evebox_ts_pattern).unwrap();
+    let re = Regex::new(EVEBOX_TS_PATTERN).unwrap();
 
     let reader = BufReader::new(output).lines();
     for line in reader {
         if let Ok(line) = line {
+            let line = localize_timestamp(&line);
             let line = re.replace_all(&line, "");
             println!("{} | {}", prefix, line);
         } else {
@@ -2,33 +2,81 @@
 // SPDX-License-Identifier: MIT
 
 use std::{
-    io::{BufRead, BufReader, Read, Write},
+    io::{BufRead, BufReader, IsTerminal, Read, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
     process::{self, Stdio},
-    sync::mpsc::Sender,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
     thread,
+    time::Duration,
 };
 
 use anyhow::{bail, Result};
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 use colored::Colorize;
-use container::{Container, SuricataContainer};
+use container::{CommandExt, Container, SuricataContainer};
 use logs::LogArgs;
-use tracing::{debug, error, info, Level};
+use tracing::{debug, error, info, warn, Level};
 
 use crate::context::Context;
+use crate::error::{CommandResult, Error};
 
 mod actions;
+mod afpacket;
+mod analyze;
+mod buildinfo;
 mod config;
+mod configcheck;
+mod configwatch;
 mod container;
 mod context;
+mod diskpolicy;
+mod display;
+mod doctor;
+mod dropconf;
+mod editor;
+mod error;
+mod eve;
+mod events;
+mod export;
+mod fleet;
+mod geoip;
+mod imagescan;
+mod journal;
+mod logrotate;
 mod logs;
+mod maintenance;
 mod menu;
 mod menus;
+mod netif;
+mod nftables;
+mod nicsetup;
+mod notify;
+mod pcaplog;
+mod pki;
 mod prelude;
+mod privilege;
+mod probe;
 mod prompt;
+mod readpcap;
+mod report;
 mod ruleindex;
+mod secrets;
 mod selfupdate;
+mod service;
+mod staging;
+mod state;
+mod status;
+mod suggest;
+mod system;
 mod term;
+mod troubleshoot;
+mod tuning;
+mod watchdog;
+mod wsl;
 
 const SURICATA_CONTAINER_NAME: &str = "simple-ids-suricata";
 const EVEBOX_CONTAINER_NAME: &str = "simple-ids-evebox";
@@ -36,9 +84,30 @@ const EVEBOX_CONTAINER_NAME: &str = "simple-ids-evebox";
 const SURICATA_VOLUME_LOG: &str = "simple-ids-suricata-log";
 const SURICATA_VOLUME_LIB: &str = "simple-ids-suricata-lib";
 const SURICATA_VOLUME_RUN: &str = "simple-ids-suricata-run";
+const SURICATA_VOLUME_PCAP: &str = "simple-ids-suricata-pcap";
 
 const EVEBOX_VOLUME_LIB: &str = "simple-ids-evebox-lib";
 
+/// User provided Suricata configuration files, used if they exist in
+/// the state directory.
+const CLASSIFICATION_CONFIG: &str = "classification.config";
+const REFERENCE_CONFIG: &str = "reference.config";
+const THRESHOLD_CONFIG: &str = "threshold.config";
+
+/// Where a user provided EveBox configuration file is mounted.
+const EVEBOX_CONTAINER_CONFIG: &str = "/etc/evebox/evebox.yaml";
+/// Directory the TLS certificates and keys are mounted in.
+const EVEBOX_CONTAINER_TLS: &str = "/etc/evebox/tls";
+const SURICATA_CONTAINER_CONFIG: &str = "/etc/suricata/simple-ids/suricata.yaml";
+
+/// Bounds of the delay before restarting a container in the foreground.
+const FOREGROUND_BACKOFF_MIN: Duration = Duration::from_secs(2);
+const FOREGROUND_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Container label holding the fingerprint of the configuration the
+/// container was started with.
+const CONFIG_LABEL: &str = "org.evebox.simple-ids.config";
+
 fn get_clap_style() -> clap::builder::Styles {
     clap::builder::Styles::styled()
         .header(clap::builder::styling::AnsiColor::Yellow.on_default())
@@ -50,7 +119,7 @@ fn get_clap_style() -> clap::builder::Styles {
 #[derive(Parser, Debug)]
 #[command(styles=get_clap_style())]
 struct Args {
-    /// Use Podman, by default Docker is used if found
+    /// Use Podman, overriding the configured runtime
     #[arg(long)]
     podman: bool,
 
@@ -60,6 +129,32 @@ struct Args {
     #[arg(long, short, global = true, action = clap::ArgAction::Count)]
     verbose: u8,
 
+    /// Manage a remote sensor over SSH, as user@host or a name from the
+    /// hosts table
+    #[arg(long, global = true)]
+    host: Option<String>,
+
+    /// Fetch the configuration from a file or URL if not yet configured
+    #[arg(long, value_name = "URL")]
+    provision: Option<String>,
+
+    /// Only allow commands that don't change anything, such as status,
+    /// logs, events and reports
+    #[arg(long, global = true)]
+    read_only: bool,
+
+    /// Refuse to run with unknown keys in the configuration file
+    #[arg(long, global = true)]
+    strict_config: bool,
+
+    /// Apply a tuning preset to the configuration
+    #[arg(long, value_enum)]
+    preset: Option<tuning::Preset>,
+
+    /// Change to this directory first, used by the privilege helper
+    #[arg(long, global = true, hide = true)]
+    directory: Option<std::path::PathBuf>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -71,46 +166,501 @@ enum Commands {
         #[arg(long, short)]
         debug: bool,
     },
-    Stop,
+    Stop {
+        /// Also stop containers with the always restart policy
+        #[arg(long)]
+        all: bool,
+    },
     Restart,
-    Status,
-    UpdateRules,
-    Update,
+    /// Pause Suricata packet processing, leaving EveBox running
+    Pause,
+    /// Run Suricata over pcap files, the events show up in EveBox
+    Readpcap {
+        #[arg(required = true)]
+        pcaps: Vec<std::path::PathBuf>,
+    },
+    /// Resume Suricata packet processing after a pause
+    Resume,
+    Status {
+        /// Show the status of all configured hosts
+        #[arg(long)]
+        all: bool,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+    UpdateRules {
+        /// Run even if outside of the maintenance window
+        #[arg(long)]
+        now: bool,
+    },
+    Update {
+        /// Run even if outside of the maintenance window
+        #[arg(long)]
+        now: bool,
+        /// Scan the pulled images for critical vulnerabilities with
+        /// trivy or grype
+        #[arg(long)]
+        scan: bool,
+    },
 
     /// View the container logs
     Logs(LogArgs),
 
+    /// Remove the containers, data volumes and images
+    Remove(RemoveArgs),
+
+    /// Rotate the Suricata logs, run periodically by cron
+    RotateLogs,
+
+    /// View the Suricata events
+    Events {
+        #[command(subcommand)]
+        command: EventsCommands,
+    },
+
+    /// IPS (inline mode) helpers
+    Ips {
+        #[command(subcommand)]
+        command: IpsCommands,
+    },
+
+    /// Summarize recent alerts
+    Report {
+        /// Number of hours to report on
+        #[arg(long, default_value_t = 24)]
+        hours: i64,
+        /// Number of entries to show in each section
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// Send the report to the configured email address and webhook
+        #[arg(long)]
+        send: bool,
+        #[arg(long, value_enum, default_value = "text")]
+        format: report::Format,
+        /// Write the report to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Suggest disabling, suppressing or thresholding the noisiest
+    /// alerts
+    Tune {
+        /// Number of days of alerts to look at
+        #[arg(long, default_value_t = 7)]
+        days: i64,
+        /// Number of signatures to offer
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+
+    /// Export or import the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Render the deployment for other tools
+    Export {
+        #[arg(long, value_enum)]
+        format: export::Format,
+        /// Write the quadlet or systemd units to this directory, such as
+        /// /etc/containers/systemd, instead of printing them
+        #[arg(long)]
+        output_dir: Option<std::path::PathBuf>,
+    },
+
+    /// Commands for all configured sensors
+    Fleet {
+        #[command(subcommand)]
+        command: FleetCommands,
+    },
+
+    /// Check the environment for common problems
+    Doctor,
+
+    /// Push notifications
+    Notify {
+        #[command(subcommand)]
+        command: NotifyCommands,
+    },
+
+    /// Rule helpers
+    Rules {
+        #[command(subcommand)]
+        command: RulesCommands,
+    },
+
+    /// Manage the pcap-log files of full packet capture
+    Pcap {
+        #[command(subcommand)]
+        command: PcapCommands,
+    },
+
+    /// Show the history of what Simple-IDS has done
+    History {
+        /// Number of entries to show
+        #[arg(long, short = 'n', default_value = "50")]
+        count: usize,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Restart the containers if they die or EveBox stops responding
+    Watchdog {
+        /// Seconds between checks
+        #[arg(long, default_value_t = 30)]
+        interval: u64,
+    },
+
+    /// Generate sudoers or polkit rules for running the menus as an
+    /// unprivileged user
+    Privileges {
+        #[command(subcommand)]
+        command: PrivilegesCommands,
+    },
+
+    /// Install Simple-IDS as a systemd service
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommands,
+    },
+
+    /// Manage secrets stored outside of the main configuration
+    Secrets {
+        #[command(subcommand)]
+        command: SecretsCommands,
+    },
+
+    /// Issue certificates for EveBox and for mutual TLS between sensors
+    /// and EveBox through reverse proxies
+    Certs {
+        #[command(subcommand)]
+        command: CertsCommands,
+    },
+
     // Commands to jump to specific menus.
     ConfigureMenu,
 
     Menu {
+        #[arg(value_parser = clap::builder::PossibleValuesParser::new(MENUS))]
         menu: String,
     },
+
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
+/// Menus that can be jumped to with the menu command.
+const MENUS: &[&str] = &["configure.advanced"];
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// Print the configuration, including defaults
+    Export,
+    /// Print a JSON Schema for the configuration file
+    Schema,
+    /// Replace the configuration with one from a file or URL
+    Import { source: String },
+    /// Convert a YAML configuration to TOML, asking which to keep if
+    /// both exist with different settings
+    Migrate,
+    /// Print a generated Suricata configuration file
+    Render {
+        #[command(subcommand)]
+        target: RenderCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RenderCommands {
+    /// The af-packet.yaml stub, with the edits from af-packet.local.yaml
+    /// layered on top
+    AfPacket {
+        /// Write the stub to the state directory instead of printing it
+        #[arg(long)]
+        write: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum NotifyCommands {
+    /// Send a test notification to the configured channels
+    Test,
+    /// Push new alerts to the channels with alert notifications
+    /// enabled, rate limited per channel
+    Alerts,
+}
+
+#[derive(Subcommand, Debug)]
+enum PcapCommands {
+    /// Remove the pcap-log files older than a number of days
+    Prune {
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RulesCommands {
+    /// Run the Suricata engine analysis and show rules with warnings or
+    /// fast pattern issues
+    Analyze {
+        /// Rule file to analyze instead of the loaded rules
+        file: Option<std::path::PathBuf>,
+    },
+    /// Update the rules into a staging area, comparing the alerts
+    /// against the live rules if pcaps are given
+    Stage {
+        /// Pcap files to compare the live and staged rules on
+        pcaps: Vec<std::path::PathBuf>,
+        /// Number of signature changes to show
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Make the staged rules live
+    Promote,
+}
+
+#[derive(Subcommand, Debug)]
+enum EventsCommands {
+    /// Show the most recent events
+    Tail {
+        /// Number of events to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        count: usize,
+        /// Follow new events
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Show the most recent events containing a string
+    Search {
+        pattern: String,
+        /// Maximum number of events to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        count: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum IpsCommands {
+    /// Divert traffic forwarded between two interfaces to NFQUEUE
+    EnableDivert {
+        /// The LAN side interface
+        #[arg(long)]
+        lan: String,
+        /// The WAN side interface
+        #[arg(long)]
+        wan: String,
+        /// The NFQUEUE number Suricata reads from
+        #[arg(long, default_value_t = 0)]
+        queue: u16,
+        /// Load the rules, otherwise they are only printed
+        #[arg(long)]
+        install: bool,
+        /// Save the rules so they are loaded on boot, implies --install
+        #[arg(long)]
+        persist: bool,
+    },
+    /// Remove the divert rules
+    DisableDivert,
+    /// Show if the divert rules are loaded
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+enum ServiceCommands {
+    /// Install and enable a systemd unit for the configuration in the
+    /// current directory
+    Install {
+        /// Run Simple-IDS in the foreground under systemd instead of
+        /// starting the detached containers
+        #[arg(long)]
+        foreground: bool,
+    },
+    /// Stop, disable and remove the systemd unit
+    Uninstall,
+}
+
+#[derive(Subcommand, Debug)]
+enum PrivilegesCommands {
+    /// Print a sudoers snippet allowing each action
+    Sudoers {
+        /// Group allowed to run the actions
+        #[arg(long, default_value = "simple-ids")]
+        group: String,
+    },
+    /// Print a polkit rule allowing each action with pkexec
+    Polkit {
+        /// Group allowed to run the actions
+        #[arg(long, default_value = "simple-ids")]
+        group: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum FleetCommands {
+    /// Show an overview of every configured sensor
+    Status {
+        /// Output JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CertsCommands {
+    /// Create the certificate authority
+    Ca,
+    /// Issue a certificate for the EveBox server
+    Server {
+        /// Host names or IP addresses the sensors connect to
+        #[arg(required = true)]
+        names: Vec<String>,
+        /// Serve it from EveBox
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Issue a client certificate for the reverse proxy of a sensor
+    Client { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum SecretsCommands {
+    /// List the secret keys
+    List,
+    /// Set a secret, the value is prompted for or read from stdin
+    Set { key: String },
+    /// Remove a secret
+    Rm { key: String },
 }
 
 fn is_interactive(command: &Option<Commands>) -> bool {
     match command {
         Some(command) => match command {
             Commands::Start { debug: _ } => false,
-            Commands::Stop => false,
+            Commands::Stop { all: _ } => false,
             Commands::Restart => false,
-            Commands::Status => false,
-            Commands::UpdateRules => false,
-            Commands::Update => false,
+            Commands::Pause => false,
+            Commands::Readpcap { pcaps: _ } => false,
+            Commands::Resume => false,
+            Commands::Status { all: _, json: _ } => false,
+            Commands::UpdateRules { now: _ } => false,
+            Commands::Update { now: _, scan: _ } => false,
             Commands::Logs(_) => false,
+            Commands::Remove(_) => false,
+            Commands::RotateLogs => false,
+            Commands::Events { command: _ } => false,
+            Commands::Ips { command: _ } => false,
+            Commands::Report {
+                hours: _,
+                top: _,
+                send: _,
+                format: _,
+                output: _,
+            } => false,
+            Commands::Config { command: _ } => false,
+            Commands::Secrets { command: _ } => false,
+            Commands::Certs { command: _ } => false,
+            Commands::History { count: _, json: _ } => false,
+            Commands::Doctor => false,
+            Commands::Watchdog { interval: _ } => false,
+            Commands::Notify { command: _ } => false,
+            Commands::Tune { days: _, top: _ } => true,
+            Commands::Service { command: _ } => false,
+            Commands::Rules { command: _ } => false,
+            Commands::Pcap { command: _ } => false,
+            Commands::Privileges { command: _ } => false,
+            Commands::Fleet { command: _ } => false,
+            Commands::Export {
+                format: _,
+                output_dir: _,
+            } => false,
             Commands::ConfigureMenu => true,
             Commands::Menu { menu: _ } => true,
+            Commands::Completions { shell: _ } => false,
         },
         None => true,
     }
 }
 
+/// Return true if the command doesn't change anything and may be run in
+/// read-only mode.
+fn is_read_only(command: &Option<Commands>) -> bool {
+    match command {
+        Some(command) => match command {
+            Commands::Start { debug: _ } => false,
+            Commands::Stop { all: _ } => false,
+            Commands::Restart => false,
+            Commands::Pause => false,
+            Commands::Readpcap { pcaps: _ } => false,
+            Commands::Resume => false,
+            Commands::Status { all: _, json: _ } => true,
+            Commands::UpdateRules { now: _ } => false,
+            Commands::Update { now: _, scan: _ } => false,
+            Commands::Logs(_) => true,
+            Commands::Remove(_) => false,
+            Commands::RotateLogs => false,
+            Commands::Events { command: _ } => true,
+            Commands::Ips { command } => matches!(command, IpsCommands::Status),
+            Commands::Report {
+                hours: _,
+                top: _,
+                send,
+                format: _,
+                output: _,
+            } => !send,
+            Commands::Config { command } => {
+                matches!(
+                    command,
+                    ConfigCommands::Export
+                        | ConfigCommands::Schema
+                        | ConfigCommands::Render {
+                            target: RenderCommands::AfPacket { write: false }
+                        }
+                )
+            }
+            Commands::Secrets { command } => matches!(command, SecretsCommands::List),
+            Commands::Certs { command: _ } => false,
+            Commands::History { count: _, json: _ } => true,
+            Commands::Doctor => true,
+            Commands::Watchdog { interval: _ } => false,
+            Commands::Notify { command: _ } => false,
+            Commands::Tune { days: _, top: _ } => false,
+            Commands::Service { command: _ } => false,
+            Commands::Rules { command } => matches!(command, RulesCommands::Analyze { file: _ }),
+            Commands::Pcap { command: _ } => false,
+            Commands::Privileges { command: _ } => true,
+            Commands::Fleet { command: _ } => true,
+            Commands::Export {
+                format: _,
+                output_dir,
+            } => output_dir.is_none(),
+            Commands::ConfigureMenu => false,
+            Commands::Menu { menu: _ } => false,
+            Commands::Completions { shell: _ } => true,
+        },
+        // The menus make changes throughout.
+        None => false,
+    }
+}
+
 fn confirm(msg: &str) -> bool {
     inquire::Confirm::new(msg).prompt().unwrap_or(false)
 }
 
 fn wizard(context: &mut Context) {
-    if context.config.suricata.interfaces.is_empty()
+    wsl::wizard(context);
+    if context.config.services.suricata
+        && context.config.suricata.interfaces.is_empty()
+        && context.config.suricata.bridge.is_none()
+        && context.config.suricata.live_capture()
         && confirm("No network interface configured, configure now?")
     {
         select_interface(context);
@@ -118,10 +668,43 @@ fn wizard(context: &mut Context) {
 }
 
 fn main() -> Result<()> {
-    // Mainly for use when developing...
-    let _ = std::process::Command::new("stty").args(["sane"]).status();
+    // Mainly for use when developing... Without stty, at least make
+    // sure the terminal isn't left in raw mode.
+    if std::io::stdin().is_terminal()
+        && std::process::Command::new("stty")
+            .args(["sane"])
+            .status()
+            .is_err()
+    {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
 
     let args = Args::parse();
+    if let Some(Commands::Completions { shell }) = &args.command {
+        clap_complete::generate(
+            *shell,
+            &mut Args::command(),
+            env!("CARGO_PKG_NAME"),
+            &mut std::io::stdout(),
+        );
+        return Ok(());
+    }
+    if let Some(directory) = &args.directory {
+        if let Err(err) = std::env::set_current_dir(directory) {
+            eprintln!("Failed to change to {}: {err}", directory.display());
+            std::process::exit(error::EXIT_FAILURE);
+        }
+    }
+    if args.directory.is_some() && privilege::is_elevated() {
+        let directory = std::env::current_dir().unwrap_or_default();
+        if let Err(err) = privilege::check_directory(&directory) {
+            eprintln!(
+                "Refusing to run as root from {}: {err}",
+                directory.display()
+            );
+            std::process::exit(error::EXIT_FAILURE);
+        }
+    }
     let is_interactive = is_interactive(&args.command);
 
     let log_level = if args.verbose > 0 {
@@ -137,22 +720,297 @@ fn main() -> Result<()> {
             .with_target(false)
             .init();
     } else {
-        tracing_subscriber::fmt().with_max_level(log_level).init();
+        // Log to stderr so command output, like JSON, can be piped.
+        tracing_subscriber::fmt()
+            .with_max_level(log_level)
+            .with_writer(std::io::stderr)
+            .init();
+    }
+
+    if args.read_only {
+        if !is_read_only(&args.command) || args.provision.is_some() || args.preset.is_some() {
+            error::exit(Err(Error::PermissionDenied(
+                "Not allowed in read-only mode".to_string(),
+            )));
+        }
+        config::set_read_only();
+    } else {
+        state::migrate();
+    }
+
+    if let Some(source) = &args.provision {
+        if !config::Config::exists() {
+            info!("Provisioning configuration from {source}");
+            if let Err(err) = import_config(source) {
+                error::exit(Err(Error::config(format!(
+                    "Failed to provision configuration from {source}: {err}"
+                ))));
+            }
+        }
     }
 
-    let config = config::Config::new();
+    // The migrate command resolves the conflict itself.
+    if !matches!(
+        args.command,
+        Some(Commands::Config {
+            command: ConfigCommands::Migrate
+        })
+    ) {
+        if let Some(conflict) = config::Config::conflict() {
+            if is_interactive && !args.read_only && std::io::stdin().is_terminal() {
+                if let Err(err) = resolve_config_conflict(conflict) {
+                    error!("Failed to resolve the configuration conflict: {err}");
+                }
+            } else {
+                warn!(
+                    "Both {} and {} exist with different settings, using {}, resolve with: simple-ids config migrate",
+                    config::TOML_FILENAME,
+                    config::YAML_FILENAME,
+                    config::TOML_FILENAME
+                );
+            }
+        }
+    }
 
-    let manager = match container::find_manager(args.podman) {
-        Some(manager) => manager,
-        None => {
-            error!("No container manager found. Docker or Podman must be available.");
-            error!("See https://evebox.org/runtimes/ for more info.");
-            std::process::exit(1);
+    let mut config = config::Config::new();
+    let unknown = config::Config::read_current()
+        .map(|buf| configcheck::unknown_keys(&buf))
+        .unwrap_or_default();
+    for key in &unknown {
+        warn!("{key}");
+    }
+    if !unknown.is_empty() && (args.strict_config || config.strict_config) {
+        error::exit(Err(Error::config(format!(
+            "{} unknown key(s) in the configuration file",
+            unknown.len()
+        ))));
+    }
+    if let Some(preset) = args.preset {
+        preset.apply(&mut config);
+        if let Err(err) = config.save() {
+            error::exit(Err(Error::config(format!(
+                "Failed to save the configuration: {err}"
+            ))));
         }
+        info!("Applied the {} preset", preset.description());
+        if preset == tuning::Preset::Performance {
+            tuning::check_offloads(&config, std::io::stdin().is_terminal());
+        }
+    }
+    container::set_timeouts(&config.timeouts);
+    display::set_timezone(config.display.timezone.as_deref());
+
+    if let Some(Commands::Secrets { command }) = &args.command {
+        error::exit(command_secrets(command).map_err(Error::from));
+    }
+
+    if let Some(Commands::Certs { command }) = &args.command {
+        error::exit(command_certs(&mut config, command).map_err(Error::from));
+    }
+
+    if let Some(Commands::Privileges { command }) = &args.command {
+        let result = match command {
+            PrivilegesCommands::Sudoers { group } => privilege::sudoers(group),
+            PrivilegesCommands::Polkit { group } => privilege::polkit_rule(group),
+        };
+        error::exit(result.map(|rules| print!("{rules}")).map_err(Error::from));
+    }
+
+    if let Some(Commands::Service { command }) = &args.command {
+        let result = match command {
+            ServiceCommands::Install { foreground } => service::install(*foreground),
+            ServiceCommands::Uninstall => service::uninstall(),
+        };
+        error::exit(result.map_err(Error::from));
+    }
+
+    if let Some(Commands::History { count, json }) = &args.command {
+        error::exit(journal::print(*count, *json).map_err(Error::from));
+    }
+
+    // The IPS divert commands only deal with the host firewall.
+    if let Some(Commands::Ips { command }) = &args.command {
+        let result: CommandResult = match command {
+            IpsCommands::EnableDivert {
+                lan,
+                wan,
+                queue,
+                install,
+                persist,
+            } => {
+                if *install || *persist {
+                    nftables::enable(lan, wan, *queue, *persist).map_err(Error::from)
+                } else {
+                    print!("{}", nftables::ruleset(lan, wan, *queue));
+                    Ok(())
+                }
+            }
+            IpsCommands::DisableDivert => nftables::disable().map_err(Error::from),
+            IpsCommands::Status => match nftables::status() {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(Error::NotRunning("IPS divert is not enabled".to_string())),
+                Err(err) => Err(err.into()),
+            },
+        };
+        error::exit(result);
+    }
+
+    // The config commands don't need a container manager.
+    if let Some(Commands::Config { command }) = &args.command {
+        let result = match command {
+            ConfigCommands::Export => match toml::to_string(&config.with_defaults()) {
+                Ok(config) => {
+                    print!("{config}");
+                    Ok(())
+                }
+                Err(err) => Err(Error::failed(format!(
+                    "Failed to export configuration: {err}"
+                ))),
+            },
+            ConfigCommands::Schema => match serde_json::to_string_pretty(&config::Config::schema())
+            {
+                Ok(schema) => {
+                    println!("{schema}");
+                    Ok(())
+                }
+                Err(err) => Err(Error::failed(format!(
+                    "Failed to generate the schema: {err}"
+                ))),
+            },
+            ConfigCommands::Import { source } => match import_config(source) {
+                Ok(()) => {
+                    info!("Configuration imported from {source}");
+                    Ok(())
+                }
+                Err(err) => Err(Error::config(format!(
+                    "Failed to import configuration from {source}: {err}"
+                ))),
+            },
+            ConfigCommands::Migrate => match config::Config::conflict() {
+                Some(conflict) => {
+                    if std::io::stdin().is_terminal() {
+                        resolve_config_conflict(conflict).map_err(Error::from)
+                    } else {
+                        Err(Error::config(format!(
+                            "Both {} and {} exist with different settings, run interactively to choose",
+                            config::TOML_FILENAME,
+                            config::YAML_FILENAME
+                        )))
+                    }
+                }
+                None => match config::Config::migrate() {
+                    Ok(true) => {
+                        info!(
+                            "Converted {} to {}, the original was moved to {}",
+                            config::YAML_FILENAME,
+                            config::TOML_FILENAME,
+                            config::YAML_BACKUP_FILENAME
+                        );
+                        Ok(())
+                    }
+                    Ok(false) => {
+                        info!("No YAML configuration to migrate");
+                        Ok(())
+                    }
+                    Err(err) => Err(Error::config(format!(
+                        "Failed to migrate the configuration: {err}"
+                    ))),
+                },
+            },
+            ConfigCommands::Render {
+                target: RenderCommands::AfPacket { write },
+            } => {
+                let result = if *write {
+                    afpacket::write_af_packet_stub(&config.suricata).map(|path| {
+                        info!("Wrote {}", path.display());
+                    })
+                } else {
+                    afpacket::render_with_local(&config.suricata).map(|rendered| {
+                        print!("{rendered}");
+                    })
+                };
+                result.map_err(|err| Error::failed(format!("Failed to render af-packet: {err}")))
+            }
+        };
+        error::exit(result);
+    }
+
+    let runtime = if args.podman {
+        config::Runtime::Podman
+    } else {
+        config.runtime
+    };
+
+    // The fleet commands find their own container manager for each host.
+    if let Some(Commands::Fleet { command }) = &args.command {
+        let result = match command {
+            FleetCommands::Status { json } => {
+                let sensors = fleet::status(&config, runtime);
+                if *json {
+                    match serde_json::to_string_pretty(&sensors) {
+                        Ok(json) => println!("{json}"),
+                        Err(err) => error!("Failed to serialize fleet status: {err}"),
+                    }
+                } else {
+                    fleet::print_table(&sensors);
+                }
+                let failed = sensors
+                    .iter()
+                    .filter(|sensor| sensor.error.is_some())
+                    .count();
+                if failed > 0 {
+                    Err(Error::failed(format!("{failed} sensor(s) failed")))
+                } else {
+                    Ok(())
+                }
+            }
+        };
+        error::exit(result);
+    }
+
+    // The doctor must work when the container manager doesn't.
+    if let Some(Commands::Doctor) = &args.command {
+        let failed = doctor::run(&config, runtime);
+        error::exit(if failed > 0 {
+            Err(Error::failed(format!("{failed} check(s) failed")))
+        } else {
+            Ok(())
+        });
+    }
+
+    // Notifications only need the configuration.
+    if let Some(Commands::Notify {
+        command: NotifyCommands::Test,
+    }) = &args.command
+    {
+        let result = if notify::is_configured(&config.notifications) {
+            notify::send(
+                &config.notifications,
+                "Simple-IDS test notification",
+                "Notifications from Simple-IDS are working.",
+                notify::Priority::Default,
+            )
+            .map_err(|err| Error::failed(format!("Failed to send notification: {err}")))
+        } else {
+            Err(Error::config("No notification channels configured"))
+        };
+        error::exit(result);
+    }
+
+    // A host can be given by its name in the hosts table.
+    let host = args
+        .host
+        .as_ref()
+        .map(|host| config.hosts.get(host).unwrap_or(host).to_string());
+
+    let manager = match container::find_manager(runtime, host.as_deref()) {
+        Some(manager) => manager,
+        None => error::exit(Err(Error::EngineMissing)),
     };
-    if manager.is_podman() && evectl::system::getuid() != 0 && !args.no_root {
-        error!("The Podman container manager requires running as root");
-        std::process::exit(1);
+    if manager.is_podman() && manager.host().is_none() && !system::is_root() && !args.no_root {
+        error::exit(Err(Error::PermissionDenied(
+            "The Podman container manager requires running as root".to_string(),
+        )));
     }
     info!("Found container manager {manager}");
 
@@ -160,24 +1018,39 @@ fn main() -> Result<()> {
 
     let prompt_for_update = {
         let mut not_found = false;
-        if !manager.has_image(&context.suricata_image) {
+        if !context.manager.has_image(&context.suricata_image) {
             info!("Suricata image {} not found", &context.suricata_image);
             not_found = true;
         }
-        if !manager.has_image(&context.evebox_image) {
+        if !context.manager.has_image(&context.evebox_image) {
             info!("EveBox image {} not found", &context.evebox_image);
             not_found = true
         }
         not_found
     };
 
-    if prompt_for_update {
+    // No point in offering to download images that are about to be
+    // removed, or aren't needed by the command.
+    let images_needed = !matches!(
+        args.command,
+        Some(Commands::Remove(_))
+            | Some(Commands::Export {
+                format: _,
+                output_dir: _
+            })
+    );
+
+    if prompt_for_update && images_needed && args.read_only {
+        warn!("Container images not found, not downloading in read-only mode");
+    } else if prompt_for_update && images_needed && context.config.updates.offline {
+        warn!("Offline mode is enabled, the container images must be loaded manually");
+    } else if prompt_for_update && images_needed {
         if let Ok(true) =
             inquire::Confirm::new("Required container images not found, download now?")
                 .with_default(true)
                 .prompt()
         {
-            if !update(&context) {
+            if !update(&context, false) {
                 error!("Failed to downloading container images");
                 prompt::enter();
             }
@@ -185,143 +1058,480 @@ fn main() -> Result<()> {
     }
 
     if let Some(command) = args.command {
-        let code = match command {
+        let result = match command {
             Commands::Start { debug: detach } => command_start(&context, detach),
-            Commands::Stop => {
-                if stop(&context) {
-                    0
+            Commands::Stop { all } => {
+                if stop(&context, all) {
+                    Ok(())
                 } else {
-                    1
+                    Err(Error::container("Failed to stop containers"))
                 }
             }
             Commands::Restart => {
-                stop(&context);
-                command_start(&context, true)
-            }
-            Commands::Status => command_status(&context),
-            Commands::UpdateRules => {
-                if actions::update_rules(&context).is_ok() {
-                    0
-                } else {
-                    1
-                }
+                stop(&context, true);
+                command_start(&context, false)
             }
-            Commands::Update => {
-                if update(&context) {
-                    0
+            Commands::Pause => command_pause(&context),
+            Commands::Readpcap { pcaps } => readpcap::readpcap(&context, &pcaps)
+                .map_err(|err| Error::failed(format!("Failed to read pcaps: {err}"))),
+            Commands::Resume => command_resume(&context),
+            Commands::Status { all: false, json } => {
+                if json {
+                    command_status_json(&context)
                 } else {
-                    1
+                    command_status(&context, !args.read_only)
                 }
             }
-            Commands::ConfigureMenu => {
-                menu::configure::main(&mut context)?;
-                0
+            Commands::Status { all: true, json } => {
+                command_status_all(&context.config, runtime, json)
             }
+            Commands::UpdateRules { now } => match maintenance_allowed(&context, now) {
+                Ok(true) => actions::update_rules(&context)
+                    .map_err(|err| Error::update(format!("Failed to update rules: {err}"))),
+                result => result.map(|_| ()),
+            },
+            Commands::Update { now, scan } => match maintenance_allowed(&context, now) {
+                Ok(true) if update(&context, scan) => Ok(()),
+                Ok(true) => Err(Error::update("Failed to update Simple-IDS")),
+                result => result.map(|_| ()),
+            },
+            Commands::ConfigureMenu => menu::configure::main(&mut context).map_err(Error::from),
             Commands::Logs(args) => {
                 logs::logs(&context, args);
-                0
+                Ok(())
+            }
+            Commands::Remove(args) => command_remove(&context, args),
+            Commands::Events { command } => {
+                let result = match command {
+                    EventsCommands::Tail { count, follow } => eve::tail(&context, count, follow),
+                    EventsCommands::Search { pattern, count } => {
+                        eve::search(&context, &pattern, count)
+                    }
+                };
+                result.map_err(Error::from)
+            }
+            Commands::Rules { command } => match command {
+                RulesCommands::Analyze { file } => analyze::analyze(&context, file.as_deref())
+                    .map_err(|err| Error::failed(format!("Failed to analyze rules: {err}"))),
+                RulesCommands::Stage { pcaps, top } => staging::stage(&context, &pcaps, top)
+                    .map_err(|err| Error::update(format!("Failed to stage rules: {err}"))),
+                RulesCommands::Promote => staging::promote(&context)
+                    .map_err(|err| Error::update(format!("Failed to promote rules: {err}"))),
+            },
+            Commands::Pcap {
+                command: PcapCommands::Prune { days },
+            } => pcaplog::prune(&context, days).map_err(|err| Error::failed(err.to_string())),
+            Commands::Tune { days, top } => suggest::session(&context, days, top)
+                .map_err(|err| Error::failed(format!("Tuning session failed: {err}"))),
+            Commands::Report {
+                hours,
+                top,
+                send: false,
+                format,
+                output,
+            } => report::report(&context, hours, top, format, output.as_deref())
+                .map_err(|err| Error::failed(format!("Failed to generate report: {err}"))),
+            Commands::Report {
+                hours,
+                top,
+                send: true,
+                format: _,
+                output: _,
+            } => report::send(&context, hours, top)
+                .map_err(|err| Error::failed(format!("Failed to send report: {err}"))),
+            Commands::RotateLogs => {
+                if !context.manager.is_running(SURICATA_CONTAINER_NAME) {
+                    Ok(())
+                } else if let Err(err) = logrotate::rotate(&context, false) {
+                    Err(Error::failed(format!(
+                        "Suricata log rotation failed: {err}"
+                    )))
+                } else {
+                    diskpolicy::check(&context, true);
+                    Ok(())
+                }
             }
+            Commands::Config { command: _ } => unreachable!(),
+            Commands::Secrets { command: _ } => unreachable!(),
+            Commands::Certs { command: _ } => unreachable!(),
+            Commands::History { count: _, json: _ } => unreachable!(),
+            Commands::Doctor => unreachable!(),
+            Commands::Watchdog { interval } => {
+                watchdog::run(&context, interval).map_err(|err| Error::failed(err.to_string()))
+            }
+            Commands::Notify { command } => match command {
+                NotifyCommands::Test => unreachable!(),
+                NotifyCommands::Alerts => notify::alerts(&context).map_err(Error::from),
+            },
+            Commands::Service { command: _ } => unreachable!(),
+            Commands::Privileges { command: _ } => unreachable!(),
+            Commands::Fleet { command: _ } => unreachable!(),
+            Commands::Ips { command: _ } => unreachable!(),
+            Commands::Export {
+                format,
+                output_dir: None,
+            } => match export::export(&context, format) {
+                Ok(output) => {
+                    print!("{output}");
+                    Ok(())
+                }
+                Err(err) => Err(Error::failed(format!("Failed to export deployment: {err}"))),
+            },
+            Commands::Export {
+                format,
+                output_dir: Some(dir),
+            } => match export::write_units(&context, format, &dir) {
+                Ok(paths) => {
+                    let units: Vec<String> = paths
+                        .iter()
+                        .filter_map(|path| path.file_stem())
+                        .map(|stem| format!("{}.service", stem.to_string_lossy()))
+                        .collect();
+                    info!("Stop the containers started by simple-ids first with: simple-ids stop");
+                    info!(
+                        "Then start the units with: systemctl daemon-reload && systemctl {} {}",
+                        if matches!(format, export::Format::Quadlet) {
+                            "start"
+                        } else {
+                            "enable --now"
+                        },
+                        units.join(" ")
+                    );
+                    Ok(())
+                }
+                Err(err) => Err(Error::failed(format!("Failed to write the units: {err}"))),
+            },
             Commands::Menu { menu } => match menu.as_str() {
                 "configure.advanced" => {
                     menu::advanced::advanced_menu(&mut context);
-                    0
+                    Ok(())
                 }
                 _ => panic!("Unhandled menu: {}", menu),
             },
+            Commands::Completions { shell: _ } => unreachable!(),
         };
-        std::process::exit(code);
+        error::exit(result);
     } else {
+        // Ctrl-C cancels the container engine commands being waited on,
+        // such as a pull, returning to the menu instead of exiting.
+        if let Err(err) = ctrlc::set_handler(container::cancel) {
+            error!("Failed to setup Ctrl-C handler: {}", err);
+        }
         menu_main(context)?;
     }
 
     Ok(())
 }
 
-fn process_output_handler<R: Read + Sync + Send + 'static>(
-    output: R,
-    label: &'static str,
-    tx: Sender<bool>,
-) {
-    let reader = BufReader::new(output).lines();
-    thread::spawn(move || {
-        for line in reader {
-            if let Ok(line) = line {
-                // Add some coloring to the Suricata output as it
-                // doesn't add its own color when writing to a
-                // non-interactive terminal.
-                let line = if line.starts_with("Info") {
-                    line.green().to_string()
-                } else if line.starts_with("Error") {
-                    line.red().to_string()
-                } else if line.starts_with("Notice") {
-                    line.magenta().to_string()
-                } else if line.starts_with("Warn") {
-                    line.yellow().to_string()
-                } else {
-                    line.to_string()
-                };
-                let mut stdout = std::io::stdout().lock();
-                let _ = writeln!(&mut stdout, "{}: {}", label, line);
-                let _ = stdout.flush();
+fn command_secrets(command: &SecretsCommands) -> Result<()> {
+    let mut secrets = secrets::Secrets::load()?;
+    match command {
+        SecretsCommands::List => {
+            for key in secrets.keys() {
+                println!("{key}");
+            }
+        }
+        SecretsCommands::Set { key } => {
+            let value = if std::io::stdin().is_terminal() {
+                inquire::Password::new(&format!("Enter value for {key}"))
+                    .without_confirmation()
+                    .prompt()?
             } else {
-                debug!("{}: EOF", label);
-                break;
+                let mut value = String::new();
+                std::io::stdin().read_to_string(&mut value)?;
+                value.trim_end().to_string()
+            };
+            secrets.set(key, &value);
+            secrets.save()?;
+        }
+        SecretsCommands::Rm { key } => {
+            if !secrets.remove(key) {
+                bail!("No secret named {key}");
             }
+            secrets.save()?;
         }
-        let _ = tx.send(true);
-    });
-}
-
-/// Run when "start" is run from the command line.
-fn command_start(context: &Context, debug: bool) -> i32 {
-    if debug {
-        start_foreground(context)
-    } else {
-        start(context);
-        0
     }
+    Ok(())
 }
 
-/// Start Simple-IDS in the foreground.
-///
-/// Typically not done from the menus but instead the command line.
-fn start_foreground(context: &Context) -> i32 {
-    context.manager.quiet_rm(SURICATA_CONTAINER_NAME);
-    context.manager.quiet_rm(EVEBOX_CONTAINER_NAME);
-
-    let (tx, rx) = std::sync::mpsc::channel::<bool>();
-    let mut suricata_command = match build_suricata_command(context, false) {
-        Ok(command) => command,
-        Err(err) => {
-            error!("Invalid Suricata configuration: {}", err);
-            return 1;
+fn command_certs(config: &mut config::Config, command: &CertsCommands) -> Result<()> {
+    match command {
+        CertsCommands::Ca => {
+            let ca = pki::init_ca()?;
+            println!("{}", ca.display());
+        }
+        CertsCommands::Server { names, apply } => {
+            let issued = pki::issue(pki::Usage::Server, names)?;
+            info!("Issued {}", issued.cert.display());
+            if *apply {
+                config.evebox.tls = Some(config::EveBoxTlsConfig {
+                    cert: issued.cert.display().to_string(),
+                    key: issued.key.display().to_string(),
+                });
+                config.evebox.no_tls = false;
+                config.save()?;
+                info!("EveBox will serve the certificate once restarted");
+            }
         }
+        CertsCommands::Client { name } => {
+            let issued = pki::issue(pki::Usage::Client, std::slice::from_ref(name))?;
+            info!("Issued {}", issued.cert.display());
+            println!(
+                "Copy these files to the reverse proxy of the sensor, see \"Mutual TLS for Sensors\" in the README:"
+            );
+            println!("  Client certificate: {}", issued.cert.display());
+            println!("  Client key:         {}", issued.key.display());
+            println!("  CA certificate:     {}", issued.ca.display());
+        }
+    }
+    Ok(())
+}
+
+fn import_config(source: &str) -> Result<()> {
+    let config = config::Config::load(source)?;
+    config.save()
+}
+
+/// Ask which configuration to keep when both a TOML and a YAML
+/// configuration exist with different settings, instead of silently
+/// ignoring the YAML one.
+fn resolve_config_conflict(conflict: config::Conflict) -> Result<()> {
+    warn!(
+        "Both {} and {} exist with different settings, only {} is used",
+        config::TOML_FILENAME,
+        config::YAML_FILENAME,
+        config::TOML_FILENAME
+    );
+    // Compare both as TOML so only the settings differ.
+    editor::print_diff(
+        &toml::to_string(&conflict.toml)?,
+        &toml::to_string(&conflict.yaml)?,
+        (config::TOML_FILENAME, config::YAML_FILENAME),
+    );
+    let selections = vec![
+        SelectItem::new("toml", format!("Keep {}", config::TOML_FILENAME)),
+        SelectItem::new("yaml", format!("Keep {}", config::YAML_FILENAME)),
+        SelectItem::new(
+            "merge",
+            format!(
+                "Merge, preferring the settings changed in {}",
+                config::TOML_FILENAME
+            ),
+        ),
+    ];
+    let selections = add_index(&selections);
+    let selection =
+        inquire::Select::new("Which configuration should be kept?", selections).prompt()?;
+    let config = match selection.tag.as_ref() {
+        "toml" => conflict.toml,
+        "yaml" => conflict.yaml,
+        _ => conflict.merge()?,
     };
+    config.save()?;
+    info!(
+        "Saved {}, {} was moved to {}",
+        config::TOML_FILENAME,
+        config::YAML_FILENAME,
+        config::YAML_BACKUP_FILENAME
+    );
+    Ok(())
+}
 
-    let mut suricata_process = match suricata_command
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-    {
-        Ok(process) => process,
-        Err(err) => {
-            error!("Failed to spawn Suricata process: {}", err);
-            return 1;
-        }
+fn process_output_handler<R: Read + Sync + Send + 'static>(output: R, label: &'static str) {
+    let reader = BufReader::new(output).lines();
+    let service = match label {
+        "suricata" => label.cyan(),
+        "evebox" => label.blue(),
+        _ => label.normal(),
     };
+    thread::spawn(move || {
+        let formatter = OutputFormatter::new();
+        for line in reader {
+            if let Ok(line) = line {
+                let line = formatter.format(&line);
+                let timestamp = display::format_millis(&chrono::Utc::now());
+                let mut stdout = std::io::stdout().lock();
+                let _ = writeln!(&mut stdout, "{} {}: {}", timestamp, service, line);
+                let _ = stdout.flush();
+            } else {
+                debug!("{}: EOF", label);
+                break;
+            }
+        }
+    });
+}
 
-    let mut evebox_command = build_evebox_command(context, false);
-    let mut evebox_process = match evebox_command
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-    {
-        Ok(process) => process,
-        Err(err) => {
-            error!("Failed to spawn EveBox process: {}", err);
-            return 1;
+/// Tidy up container output for display in the foreground.
+///
+/// Each line gets its own timestamp, so timestamps already in the
+/// output are removed, and EveBox JSON log records are turned back into
+/// text.
+struct OutputFormatter {
+    ansi: regex::Regex,
+    timestamp: regex::Regex,
+}
+
+impl OutputFormatter {
+    fn new() -> Self {
+        Self {
+            ansi: regex::Regex::new(r"\x1b\[[0-9;]*m").unwrap(),
+            timestamp: regex::Regex::new(
+                r"^\d{4}-\d{2}-\d{2}[ T]\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:?\d{2})?\s*",
+            )
+            .unwrap(),
+        }
+    }
+
+    fn format(&self, line: &str) -> String {
+        let line = match Self::parse_json(line) {
+            Some(line) => line,
+            None => {
+                let line = self.ansi.replace_all(line, "");
+                self.timestamp.replace(&line, "").to_string()
+            }
+        };
+
+        // Add some coloring to the output as Suricata doesn't add its
+        // own color when writing to a non-interactive terminal.
+        let lower = line.to_lowercase();
+        if lower.starts_with("info") {
+            line.green().to_string()
+        } else if lower.starts_with("error") {
+            line.red().to_string()
+        } else if lower.starts_with("notice") {
+            line.magenta().to_string()
+        } else if lower.starts_with("warn") {
+            line.yellow().to_string()
+        } else if lower.starts_with("debug") || lower.starts_with("trace") {
+            line.dimmed().to_string()
+        } else {
+            line
+        }
+    }
+
+    /// Format a JSON log record as "LEVEL target: message".
+    fn parse_json(line: &str) -> Option<String> {
+        if !line.starts_with('{') {
+            return None;
+        }
+        let json: serde_json::Value = serde_json::from_str(line).ok()?;
+        let message = json["fields"]["message"]
+            .as_str()
+            .or_else(|| json["message"].as_str())
+            .or_else(|| json["msg"].as_str())?;
+        let level = json["level"].as_str().unwrap_or("INFO").to_uppercase();
+        match json["target"].as_str() {
+            Some(target) => Some(format!("{} {}: {}", level, target, message)),
+            None => Some(format!("{} {}", level, message)),
         }
+    }
+}
+
+/// Check if a disruptive operation may run now, logging why not if
+/// outside of the maintenance window.
+fn maintenance_allowed(context: &Context, now: bool) -> Result<bool, Error> {
+    if now {
+        return Ok(true);
+    }
+    let window = context.config.maintenance.window.as_deref();
+    let allowed = maintenance::allowed(window).map_err(Error::Config)?;
+    if !allowed {
+        info!(
+            "Outside of the maintenance window {}, use --now to run anyway",
+            window.unwrap_or_default()
+        );
+    }
+    Ok(allowed)
+}
+
+/// Run when "start" is run from the command line.
+fn command_start(context: &Context, debug: bool) -> CommandResult {
+    if debug {
+        start_foreground(context)
+    } else if start(context) {
+        print_start_banner(context);
+        Ok(())
+    } else {
+        Err(Error::container("Failed to start Simple-IDS"))
+    }
+}
+
+/// Print a summary of what was started and what to do next.
+fn print_start_banner(context: &Context) {
+    let evebox = &context.config.evebox;
+    let capture = if context.config.suricata.unix_socket {
+        "none (unix-socket mode)".to_string()
+    } else if let Some(ips) = &context.config.suricata.ips {
+        format!("NFQUEUE {} (IPS mode)", ips.queue)
+    } else if let Some(bridge) = &context.config.suricata.bridge {
+        format!("{} <-> {} (inline bridge)", bridge.lan, bridge.wan)
+    } else {
+        context.config.suricata.interfaces.join(", ")
     };
+    let credentials = if evebox.agent.is_some() {
+        "sensor-only, events are forwarded to the remote EveBox server".to_string()
+    } else if evebox.no_auth {
+        "authentication disabled".yellow().to_string()
+    } else {
+        "login as admin, reset the password from the EveBox menu if unknown".to_string()
+    };
+    let rules = match state::State::load().last_rule_update {
+        Some(ts) => format!(
+            "updated {} ago",
+            format_duration(chrono::Utc::now().signed_duration_since(ts))
+        ),
+        None => "never updated".red().to_string(),
+    };
+
+    println!();
+    println!("{}", "Simple-IDS is running".green().bold());
+    println!("  EveBox:      {}", guess_evebox_url(context).bold());
+    println!("  Credentials: {}", credentials);
+    println!("  Monitoring:  {}", capture);
+    println!("  Rules:       {}", rules);
+    println!();
+    println!("Next steps:");
+    if state::State::load().last_rule_update.is_none() {
+        println!("  simple-ids update-rules   Download the rules");
+    }
+    println!("  simple-ids status         Check the status of the containers");
+    println!("  simple-ids logs -f        Follow the container logs");
+    println!("  simple-ids events tail    Show the latest events");
+}
+
+/// Start Simple-IDS in the foreground.
+///
+/// Typically not done from the menus but instead the command line. A
+/// container that exits is restarted if its restart policy says so,
+/// otherwise both are stopped.
+fn start_foreground(context: &Context) -> CommandResult {
+    remove_container(context, SURICATA_CONTAINER_NAME);
+    remove_container(context, EVEBOX_CONTAINER_NAME);
+
+    let containers: Vec<Container> = [Container::Suricata, Container::EveBox]
+        .into_iter()
+        .filter(|container| context.config.services.enabled(*container))
+        .collect();
+    if containers.is_empty() {
+        return Err(Error::config(
+            "No services are enabled in the configuration",
+        ));
+    }
+
+    let (tx, rx) = std::sync::mpsc::channel::<bool>();
+    let stopping = Arc::new(AtomicBool::new(false));
+
+    if containers.contains(&Container::EveBox) {
+        menu::evebox::bootstrap_admin(context);
+    }
+
+    let mut processes = vec![];
+    for container in &containers {
+        processes.push((*container, spawn_foreground(context, *container)?));
+        if let Err(err) = state::State::record_start(container.name()) {
+            error!("Failed to record start of {}: {err}", container.name());
+        }
+    }
 
     {
         let tx = tx.clone();
@@ -333,180 +1543,779 @@ fn start_foreground(context: &Context) -> i32 {
         }
     }
 
-    let now = std::time::Instant::now();
-    loop {
-        if !context.manager.is_running(SURICATA_CONTAINER_NAME) {
-            if now.elapsed().as_secs() > 3 {
-                error!("Timed out waiting for the Suricata container to start running, not starting log rotation");
-                break;
-            } else {
-                continue;
-            }
-        }
+    logrotate::start_foreground(context);
 
-        if let Err(err) = start_suricata_logrotate(context) {
-            error!("Failed to start Suricata log rotation: {err}");
-        }
-        break;
+    let supervisors: Vec<_> = processes
+        .into_iter()
+        .map(|(container, process)| {
+            supervise(context, container, process, tx.clone(), stopping.clone())
+        })
+        .collect();
+
+    configwatch::watch_foreground(context);
+
+    let _ = rx.recv();
+    stopping.store(true, Ordering::Relaxed);
+    if containers.contains(&Container::Suricata) {
+        let _ = context.manager.stop(SURICATA_CONTAINER_NAME, None);
     }
+    if containers.contains(&Container::EveBox) {
+        let _ = context.manager.stop(EVEBOX_CONTAINER_NAME, Some("SIGINT"));
+    }
+    for supervisor in supervisors {
+        let _ = supervisor.join();
+    }
+    Ok(())
+}
 
-    if let Some(output) = suricata_process.stdout.take() {
-        process_output_handler(output, "suricata", tx.clone());
+/// Run a container in the foreground, printing its output.
+fn spawn_foreground(context: &Context, container: Container) -> Result<process::Child, Error> {
+    let (mut command, label) = match container {
+        Container::Suricata => match check_suricata_build(context)
+            .and_then(|()| build_suricata_command(context, false))
+        {
+            Ok(command) => (command, "suricata"),
+            Err(err) => {
+                return Err(Error::config(format!(
+                    "Invalid Suricata configuration: {err}"
+                )));
+            }
+        },
+        Container::EveBox => (build_evebox_command(context, false), "evebox"),
+    };
+    let mut process = match command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(process) => process,
+        Err(err) => {
+            return Err(Error::container(format!(
+                "Failed to spawn {label} process: {err}"
+            )));
+        }
+    };
+    if let Some(output) = process.stdout.take() {
+        process_output_handler(output, label);
     }
-    if let Some(output) = suricata_process.stderr.take() {
-        process_output_handler(output, "suricata", tx.clone());
+    if let Some(output) = process.stderr.take() {
+        process_output_handler(output, label);
     }
+    Ok(process)
+}
 
-    if let Some(output) = evebox_process.stdout.take() {
-        process_output_handler(output, "evebox", tx.clone());
+/// Wait for a foreground container to exit, restarting it with backoff
+/// if its restart policy says so, otherwise signalling everything to
+/// stop.
+fn supervise(
+    context: &Context,
+    container: Container,
+    mut process: process::Child,
+    tx: Sender<bool>,
+    stopping: Arc<AtomicBool>,
+) -> thread::JoinHandle<()> {
+    let context = context.clone();
+    let (name, policy) = match container {
+        Container::Suricata => (
+            SURICATA_CONTAINER_NAME,
+            context.config.restart_policy(Container::Suricata),
+        ),
+        Container::EveBox => (
+            EVEBOX_CONTAINER_NAME,
+            context.config.restart_policy(Container::EveBox),
+        ),
+    };
+    thread::spawn(move || {
+        let mut backoff = FOREGROUND_BACKOFF_MIN;
+        loop {
+            let started = std::time::Instant::now();
+            let status = process.wait();
+            debug!("{name} exit status: {:?}", status);
+            if stopping.load(Ordering::Relaxed) {
+                return;
+            }
+            journal::record(journal::Kind::Exit, format!("{name} exited"));
+            let success = status.map(|status| status.success()).unwrap_or(false);
+            let restarts = policy.restarts(success);
+            notify::send_or_warn(
+                &context.config.notifications,
+                &format!("{name} exited"),
+                if restarts {
+                    "Restarting it due to its restart policy."
+                } else {
+                    "Stopping Simple-IDS."
+                },
+                notify::Priority::High,
+            );
+            if !restarts {
+                let _ = tx.send(true);
+                return;
+            }
+
+            // Only back off if the container keeps exiting soon after
+            // being started.
+            if started.elapsed() > FOREGROUND_BACKOFF_MAX {
+                backoff = FOREGROUND_BACKOFF_MIN;
+            }
+            warn!(
+                "{name} exited, restarting in {}s due to its {} restart policy",
+                backoff.as_secs(),
+                policy.as_str()
+            );
+            let deadline = std::time::Instant::now() + backoff;
+            while std::time::Instant::now() < deadline {
+                if stopping.load(Ordering::Relaxed) {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(250));
+            }
+            backoff = (backoff * 2).min(FOREGROUND_BACKOFF_MAX);
+
+            remove_container(&context, name);
+            process = match spawn_foreground(&context, container) {
+                Ok(process) => process,
+                Err(err) => {
+                    error!("Failed to restart {name}: {err}");
+                    let _ = tx.send(true);
+                    return;
+                }
+            };
+            if let Err(err) = state::State::record_start(name) {
+                error!("Failed to record start of {name}: {err}");
+            }
+        }
+    })
+}
+
+/// Stop the containers. Unless `all` is set, containers with the always
+/// restart policy are left running.
+fn stop(context: &Context, all: bool) -> bool {
+    let keep = |name: &str, policy: config::RestartPolicy| {
+        let keep = !all && policy == config::RestartPolicy::Always;
+        if keep && context.manager.is_running(name) {
+            info!("Leaving {name} running due to its always restart policy, use --all to stop it");
+        }
+        keep
+    };
+    let keep_suricata = keep(
+        SURICATA_CONTAINER_NAME,
+        context.config.restart_policy(Container::Suricata),
+    );
+    let keep_evebox = keep(
+        EVEBOX_CONTAINER_NAME,
+        context.config.restart_policy(Container::EveBox),
+    );
+
+    // Stop both containers at the same time, EveBox doesn't need to
+    // wait for Suricata.
+    let stop = |name: &'static str, signal: Option<&'static str>| {
+        let context = context.clone();
+        container::spawn_blocking(move || stop_container(&context, name, signal))
+    };
+    container::block_on(async {
+        let suricata = (!keep_suricata).then(|| stop(SURICATA_CONTAINER_NAME, None));
+        let evebox = (!keep_evebox).then(|| stop(EVEBOX_CONTAINER_NAME, Some("SIGINT")));
+        let mut ok = true;
+        for handle in [suricata, evebox].into_iter().flatten() {
+            ok &= handle.await.unwrap_or(false);
+        }
+        ok
+    })
+}
+
+/// Pause Suricata, keeping the container, its logs and EveBox. With
+/// af-packet the kernel drops the packets while paused.
+fn command_pause(context: &Context) -> CommandResult {
+    if !context.manager.is_running(SURICATA_CONTAINER_NAME) {
+        return Err(Error::NotRunning("Suricata is not running".to_string()));
     }
-    if let Some(output) = evebox_process.stderr.take() {
-        process_output_handler(output, "evebox", tx.clone());
+    if context.manager.is_paused(SURICATA_CONTAINER_NAME) {
+        info!("Suricata is already paused");
+        return Ok(());
     }
+    context
+        .manager
+        .pause(SURICATA_CONTAINER_NAME)
+        .map_err(|err| Error::container(format!("Failed to pause Suricata: {err}")))?;
+    journal::record(journal::Kind::Pause, SURICATA_CONTAINER_NAME);
+    info!("Suricata paused, resume with: simple-ids resume");
+    Ok(())
+}
 
-    let _ = rx.recv();
-    let _ = context.manager.stop(SURICATA_CONTAINER_NAME, None);
-    let _ = context.manager.stop(EVEBOX_CONTAINER_NAME, Some("SIGINT"));
-    let status = suricata_process.wait();
-    debug!("Suricata exit status: {:?}", status);
-    let status = evebox_process.wait();
-    debug!("EveBox exit status: {:?}", status);
-    0
+fn command_resume(context: &Context) -> CommandResult {
+    if !context.manager.is_paused(SURICATA_CONTAINER_NAME) {
+        if context.manager.is_running(SURICATA_CONTAINER_NAME) {
+            info!("Suricata is not paused");
+            return Ok(());
+        }
+        return Err(Error::NotRunning("Suricata is not running".to_string()));
+    }
+    context
+        .manager
+        .unpause(SURICATA_CONTAINER_NAME)
+        .map_err(|err| Error::container(format!("Failed to resume Suricata: {err}")))?;
+    journal::record(journal::Kind::Resume, SURICATA_CONTAINER_NAME);
+    info!("Suricata resumed");
+    Ok(())
 }
 
-fn stop(context: &Context) -> bool {
+fn stop_container(context: &Context, name: &str, signal: Option<&str>) -> bool {
     let mut ok = true;
-
-    if context.manager.container_exists(SURICATA_CONTAINER_NAME) {
-        info!("Stopping {SURICATA_CONTAINER_NAME}");
-        if let Err(err) = context.manager.stop(SURICATA_CONTAINER_NAME, None) {
-            error!(
-                "Failed to stop container {SURICATA_CONTAINER_NAME}: {}",
-                err
-            );
+    if context.manager.container_exists(name) {
+        info!("Stopping {name}");
+        if let Err(err) = context.manager.stop(name, signal) {
+            error!("Failed to stop container {name}: {}", err);
             ok = false;
+        } else {
+            journal::record(journal::Kind::Stop, name);
         }
-        context.manager.quiet_rm(SURICATA_CONTAINER_NAME);
+        remove_container(context, name);
     } else {
-        info!("Container {SURICATA_CONTAINER_NAME} is not running");
+        info!("Container {name} is not running");
     }
-    if context.manager.container_exists(EVEBOX_CONTAINER_NAME) {
-        info!("Stopping {EVEBOX_CONTAINER_NAME}");
-        if let Err(err) = context.manager.stop(EVEBOX_CONTAINER_NAME, Some("SIGINT")) {
-            error!("Failed to stop container {EVEBOX_CONTAINER_NAME}: {}", err);
-            ok = false;
+    ok
+}
+
+/// Remove a container, saving its logs first for `logs --previous`.
+fn remove_container(context: &Context, name: &str) {
+    logs::save_previous(context, name);
+    context.manager.quiet_rm(name);
+}
+
+#[derive(Parser, Debug)]
+struct RemoveArgs {
+    /// Only remove the containers, keeping data volumes and images
+    #[arg(long)]
+    containers_only: bool,
+
+    /// Keep the data volumes (event history, rules)
+    #[arg(long)]
+    keep_data: bool,
+
+    /// Keep the container images
+    #[arg(long)]
+    keep_images: bool,
+
+    /// Don't ask for confirmation before removing data volumes
+    #[arg(long)]
+    force: bool,
+}
+
+fn command_remove(context: &Context, args: RemoveArgs) -> CommandResult {
+    let remove_volumes = !args.containers_only && !args.keep_data;
+    let remove_images = !args.containers_only && !args.keep_images;
+    let volumes = [
+        SURICATA_VOLUME_LOG,
+        SURICATA_VOLUME_LIB,
+        SURICATA_VOLUME_RUN,
+        SURICATA_VOLUME_PCAP,
+        EVEBOX_VOLUME_LIB,
+    ];
+
+    let _lock = state::lock("remove").map_err(Error::Failed)?;
+
+    if remove_volumes && !args.force {
+        if !std::io::stdin().is_terminal() {
+            return Err(Error::failed(
+                "Refusing to remove data volumes without --force",
+            ));
         }
-        context.manager.quiet_rm(EVEBOX_CONTAINER_NAME);
+        confirm_remove_volumes(context, &volumes)?;
+    }
+
+    let mut ok = stop(context, true);
+    logrotate::remove_schedule();
+    report::remove_schedule();
+
+    if remove_volumes {
+        for volume in volumes {
+            info!("Removing volume {volume}");
+            if let Err(err) = context.manager.remove_volume(volume) {
+                error!(
+                    "Failed to remove volume {volume}: {}",
+                    err.to_string().trim_end()
+                );
+                ok = false;
+            } else if volume == EVEBOX_VOLUME_LIB {
+                // The users went with the volume.
+                if let Err(err) = state::State::update(|state| state.evebox_admin_created = false) {
+                    error!("Failed to update state: {err}");
+                }
+            }
+        }
+    }
+
+    if remove_images {
+        for image in [&context.suricata_image, &context.evebox_image] {
+            info!("Removing image {image}");
+            if let Err(err) = context.manager.remove_image(image) {
+                error!(
+                    "Failed to remove image {image}: {}",
+                    err.to_string().trim_end()
+                );
+                ok = false;
+            }
+        }
+    }
+
+    if ok {
+        Ok(())
     } else {
-        info!("Container {EVEBOX_CONTAINER_NAME} is not running");
+        Err(Error::container("Failed to remove Simple-IDS"))
     }
+}
 
-    ok
+/// Show how much data is about to be removed, requiring the size to be
+/// typed back to confirm.
+fn confirm_remove_volumes(context: &Context, volumes: &[&str]) -> CommandResult {
+    info!("Calculating the size of the data volumes");
+    let expected = match context
+        .manager
+        .volume_sizes(&context.suricata_image, volumes)
+    {
+        Ok(sizes) => {
+            for (volume, size) in &sizes {
+                println!("  {:<24} {}", volume, display::format_bytes(*size));
+            }
+            let total = sizes.iter().map(|(_, size)| size).sum();
+            format!("delete {}", display::format_bytes(total))
+        }
+        Err(err) => {
+            warn!("Failed to calculate the size of the data volumes: {err}");
+            "delete all data".to_string()
+        }
+    };
+    match inquire::Text::new(&format!(
+        "This will remove all Simple-IDS data including event history, type \"{expected}\" to confirm:"
+    ))
+    .with_help_message("Use --keep-data to only remove containers and images")
+    .prompt()
+    {
+        Ok(input) if input.trim() == expected => Ok(()),
+        _ => Err(Error::Cancelled),
+    }
 }
 
-fn command_status(context: &Context) -> i32 {
-    let mut code = 0;
+fn command_status_json(context: &Context) -> CommandResult {
+    let status = status::status(context);
+    let json = serde_json::to_string_pretty(&status).map_err(|err| Error::Failed(err.into()))?;
+    println!("{json}");
+    if status.is_running() {
+        Ok(())
+    } else {
+        Err(Error::NotRunning("Simple-IDS is not running".to_string()))
+    }
+}
+
+/// Print the status. With `record` the EveBox certificate expiry found
+/// is saved to the state, for the warning in the menu.
+fn command_status(context: &Context, record: bool) -> CommandResult {
+    let mut result = Ok(());
+    let starts = state::State::load();
+    let services = context.config.services;
     match context.manager.state(SURICATA_CONTAINER_NAME) {
-        Ok(state) => info!("suricata: {}", state.status),
+        Err(_) if !services.suricata => info!("suricata: disabled"),
+        Ok(state) => info!(
+            "suricata: {}{}{}{}{}",
+            state.status,
+            uptime_label(&state, &starts, SURICATA_CONTAINER_NAME),
+            engine_restarts_label(context, SURICATA_CONTAINER_NAME),
+            watchdog_restarts_label(&starts, SURICATA_CONTAINER_NAME),
+            restart_required_label(context, Container::Suricata)
+        ),
         Err(err) => {
             let err = format!("{}", err);
             error!("suricata: {}", err.trim_end());
-            code = 1;
+            result = Err(Error::NotRunning("Suricata is not running".to_string()));
         }
     }
     match context.manager.state(EVEBOX_CONTAINER_NAME) {
-        Ok(state) => info!("evebox: {}", state.status),
+        Err(_) if !services.evebox => info!("evebox: disabled"),
+        Ok(state) => info!(
+            "evebox: {}{}{}{}{}",
+            state.status,
+            uptime_label(&state, &starts, EVEBOX_CONTAINER_NAME),
+            engine_restarts_label(context, EVEBOX_CONTAINER_NAME),
+            watchdog_restarts_label(&starts, EVEBOX_CONTAINER_NAME),
+            restart_required_label(context, Container::EveBox)
+        ),
         Err(err) => {
             let err = format!("{}", err);
             error!("evebox: {}", err.trim_end());
-            code = 1;
+            if result.is_ok() {
+                result = Err(Error::NotRunning("EveBox is not running".to_string()));
+            }
+        }
+    }
+
+    if context.manager.is_running(EVEBOX_CONTAINER_NAME) {
+        let probe = if record {
+            probe::record_evebox_cert(context)
+        } else {
+            probe::evebox(context)
+        };
+        match probe {
+            Some(Ok(probe)) => {
+                info!(
+                    "evebox http: HTTP {} in {}ms{}",
+                    probe.status,
+                    probe.latency.as_millis(),
+                    cert_expiry_label(probe.cert_expires)
+                );
+                if let Some(warning) = probe::cert_warning(probe.cert_expires) {
+                    warn!("{warning}");
+                }
+                if probe.status >= 500 {
+                    result = Err(Error::NotRunning("EveBox is not serving".to_string()));
+                }
+            }
+            Some(Err(err)) => {
+                error!("evebox http: not reachable: {err}");
+                if result.is_ok() {
+                    result = Err(Error::NotRunning("EveBox is not reachable".to_string()));
+                }
+            }
+            None => {}
+        }
+    }
+
+    if let Some(network) = context.manager.podman_network_info() {
+        info!(
+            "podman network: {}",
+            network.describe(&context.config.podman)
+        );
+    }
+
+    if context.manager.is_running(SURICATA_CONTAINER_NAME) {
+        if let Some(stats) = actions::rule_stats(context) {
+            info!("rules: {} loaded, {} failed", stats.loaded, stats.failed);
+        }
+        if let Some(sources) = actions::enabled_sources(context) {
+            if sources.is_empty() {
+                info!("rule sources: et/open (default)");
+            } else {
+                info!("rule sources: {}", sources.join(", "));
+            }
+        }
+    }
+    info!("rules last updated: {}", last_rule_update());
+
+    let state = state::State::load();
+    match (state.last_logrotate, state.last_logrotate_error) {
+        (Some(ts), None) => info!("log rotation: last ran {}", display::format_minutes(&ts)),
+        (Some(ts), Some(err)) => {
+            error!(
+                "log rotation: failed at {}: {}",
+                display::format_minutes(&ts),
+                err
+            );
+            if result.is_ok() {
+                result = Err(Error::failed("Log rotation failed"));
+            }
         }
+        (None, _) => info!("log rotation: never run"),
     }
-    code
+    if let Some(pressure) = &state.disk_pressure {
+        warn!("disk: {pressure}");
+    }
+    if context.manager.host().is_none() && nicsetup::installed() {
+        for problem in nicsetup::verify(&context.config) {
+            warn!("nic: {problem}");
+        }
+    }
+    if context.manager.host().is_none() && wsl::is_wsl2() {
+        let mode = wsl::networking_mode();
+        match mode.as_deref() {
+            Some("mirrored") => info!("wsl2: mirrored networking"),
+            Some(mode) => warn!("wsl2: {mode} networking, only WSL traffic is visible"),
+            None => {
+                warn!("wsl2: unknown networking mode, mirrored is required to see host traffic")
+            }
+        }
+    }
+    result
 }
 
-fn guess_evebox_url(context: &Context) -> String {
-    let scheme = if context.config.evebox.no_tls {
-        "http"
-    } else {
-        "https"
+fn cert_expiry_label(expires: Option<chrono::DateTime<chrono::Utc>>) -> String {
+    let Some(expires) = expires else {
+        return String::new();
     };
+    let days = expires.signed_duration_since(chrono::Utc::now()).num_days();
+    if days < 0 {
+        format!(
+            ", certificate expired {}",
+            display::format_minutes(&expires)
+        )
+        .red()
+        .to_string()
+    } else {
+        format!(
+            ", certificate expires in {days} days ({})",
+            display::format_minutes(&expires)
+        )
+    }
+}
 
-    if !context.config.evebox.allow_remote {
-        format!("{}://127.0.0.1:5636", scheme)
+/// Number of starts in a day at which a container is considered to be
+/// flapping.
+const FLAPPING_STARTS: usize = 3;
+
+/// Format the uptime and recent starts of a container for display,
+/// flagging a container that has been started too many times.
+fn uptime_label(inspect: &container::InspectState, state: &state::State, name: &str) -> String {
+    let mut label = String::new();
+    if let Some(uptime) = inspect.uptime() {
+        label.push_str(&format!(" (up {})", format_duration(uptime)));
+    }
+    let starts = state.starts_last_day(name);
+    if starts >= FLAPPING_STARTS {
+        label.push_str(
+            &format!(" [{} starts in 24h, flapping?]", starts)
+                .yellow()
+                .to_string(),
+        );
+    } else if starts > 1 {
+        label.push_str(&format!(" [{} starts in 24h]", starts));
+    }
+    label
+}
+
+fn engine_restarts_label(context: &Context, name: &str) -> String {
+    match context.manager.restart_count(name) {
+        Some(count) if count > 0 => format!(" [restarted {} times by {}]", count, context.manager),
+        _ => String::new(),
+    }
+}
+
+fn watchdog_restarts_label(state: &state::State, name: &str) -> String {
+    match state.watchdog_restarts.get(name) {
+        Some(count) if *count > 0 => format!(" [restarted {} times by the watchdog]", count),
+        _ => String::new(),
+    }
+}
+
+fn format_duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes();
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else if minutes < 60 * 24 {
+        format!("{}h {}m", minutes / 60, minutes % 60)
     } else {
-        let interfaces = match evectl::system::get_interfaces() {
-            Ok(interfaces) => interfaces,
-            Err(err) => {
-                error!("Failed to get system interfaces: {err}");
-                return format!("{}://127.0.0.1:5636", scheme);
-            }
-        };
+        format!("{}d {}h", minutes / (60 * 24), (minutes / 60) % 24)
+    }
+}
 
-        // Find the first interface that is up...
-        let mut addr: Option<&String> = None;
-
-        for interface in &interfaces {
-            // Only consider IPv4 addresses for now.
-            if interface.addr4.is_empty() {
-                continue;
-            }
-            if interface.name == "lo" && addr.is_none() {
-                addr = interface.addr4.first();
-            } else if interface.status == "UP" {
-                match addr {
-                    Some(previous) => {
-                        if previous.starts_with("127") {
-                            addr = interface.addr4.first();
-                        }
-                    }
-                    None => {
-                        addr = interface.addr4.first();
+fn last_rule_update() -> String {
+    state::State::load()
+        .last_rule_update
+        .map(|ts| display::format_minutes(&ts))
+        .unwrap_or_else(|| "never".to_string())
+}
+
+/// Show the status of the local sensor and every host in the hosts
+/// table.
+fn command_status_all(
+    config: &config::Config,
+    runtime: config::Runtime,
+    json: bool,
+) -> CommandResult {
+    let mut failed = 0;
+    let mut statuses = std::collections::BTreeMap::new();
+    let hosts = std::iter::once(("local".to_string(), None)).chain(
+        config
+            .hosts
+            .iter()
+            .map(|(name, host)| (name.to_string(), Some(host.as_str()))),
+    );
+    for (name, host) in hosts {
+        if !json {
+            println!("{}", format!("== {name} ==").bold());
+        }
+        match container::find_manager(runtime, host) {
+            Some(manager) => {
+                let context = Context::new(config.clone(), manager);
+                if json {
+                    let status = status::status(&context);
+                    if !status.is_running() {
+                        failed += 1;
                     }
+                    statuses.insert(name, Some(status));
+                } else if let Err(err) = command_status(&context, false) {
+                    error!("{name}: {err}");
+                    failed += 1;
                 }
             }
+            None => {
+                error!("{name}: no container manager found");
+                statuses.insert(name, None);
+                failed += 1;
+            }
         }
+    }
+    if json {
+        let json =
+            serde_json::to_string_pretty(&statuses).map_err(|err| Error::Failed(err.into()))?;
+        println!("{json}");
+    }
+    if failed > 0 {
+        Err(Error::failed(format!("{failed} host(s) not healthy")))
+    } else {
+        Ok(())
+    }
+}
+
+/// Return true if the container was started with a different
+/// configuration than the current one.
+fn restart_required(context: &Context, container: Container) -> bool {
+    let name = match container {
+        Container::Suricata => SURICATA_CONTAINER_NAME,
+        Container::EveBox => EVEBOX_CONTAINER_NAME,
+    };
+    match context.manager.label(name, CONFIG_LABEL) {
+        Some(fingerprint) => fingerprint != context.config.fingerprint(container),
+        // Started by an older version, nothing to compare against.
+        None => false,
+    }
+}
+
+fn restart_required_label(context: &Context, container: Container) -> &'static str {
+    if restart_required(context, container) {
+        " (restart required)"
+    } else {
+        ""
+    }
+}
+
+/// The guessed EveBox address, only looked up once per run as the URL
+/// is shown on every redraw of the menu, and looking up the interfaces
+/// may run a container.
+static GUESSED_ADDRESS: std::sync::OnceLock<Option<String>> = std::sync::OnceLock::new();
+
+fn guess_evebox_url(context: &Context) -> String {
+    if let Some(agent) = &context.config.evebox.agent {
+        return agent.server.clone();
+    }
+    let scheme = if context.config.evebox.no_tls {
+        "http"
+    } else {
+        "https"
+    };
+
+    if let Some(url) = &context.config.evebox.external_url {
+        return url.trim_end_matches('/').to_string();
+    }
 
+    if !context.config.evebox.allow_remote {
+        format!("{}://127.0.0.1:5636", scheme)
+    } else {
+        let address = GUESSED_ADDRESS.get_or_init(|| {
+            let interfaces = match netif::get_interfaces(context) {
+                Ok(interfaces) => interfaces,
+                Err(err) => {
+                    error!("Failed to get system interfaces: {err}");
+                    return None;
+                }
+            };
+            // The routes are only known for the local host.
+            let local = context.manager.host().is_none();
+            let default_route = if local {
+                netif::default_route_interface()
+            } else {
+                None
+            };
+            guess_address(&interfaces, default_route.as_deref(), local)
+        });
         format!(
             "{}://{}:5636",
             scheme,
-            addr.unwrap_or(&"127.0.0.1".to_string())
+            address.as_deref().unwrap_or("127.0.0.1")
         )
     }
 }
 
+/// Guess the IPv4 address EveBox is reached at, preferring the
+/// interface carrying the default route, then the first interface that
+/// is up and not a container, hypervisor or bridge interface, falling
+/// back to the first interface that is up.
+fn guess_address(
+    interfaces: &[netif::Interface],
+    default_route: Option<&str>,
+    local: bool,
+) -> Option<String> {
+    // Only consider IPv4 addresses for now.
+    let candidates: Vec<&netif::Interface> = interfaces
+        .iter()
+        .filter(|interface| {
+            interface.name != "lo" && interface.status == "UP" && !interface.addr4.is_empty()
+        })
+        .collect();
+    if let Some(default_route) = default_route {
+        if let Some(interface) = candidates
+            .iter()
+            .find(|interface| interface.name == default_route)
+        {
+            return interface.addr4.first().cloned();
+        }
+    }
+    candidates
+        .iter()
+        .find(|interface| !netif::is_virtual(&interface.name, local))
+        .or_else(|| candidates.first())
+        .and_then(|interface| interface.addr4.first().cloned())
+}
+
 fn menu_main(mut context: Context) -> Result<()> {
     let mut first = true;
+    let watcher = events::EventWatcher::start(
+        &context.manager,
+        &[SURICATA_CONTAINER_NAME, EVEBOX_CONTAINER_NAME],
+    );
+    let mut status = container::StatusCache::new(
+        context.manager.clone(),
+        &[SURICATA_CONTAINER_NAME, EVEBOX_CONTAINER_NAME],
+    );
     loop {
         term::title("Simple-IDS: Main Menu");
 
         if first {
             first = false;
             wizard(&mut context);
+            if status.is_running(EVEBOX_CONTAINER_NAME) {
+                let _ = probe::record_evebox_cert(&context);
+            }
         }
 
         let evebox_url = guess_evebox_url(&context);
 
-        let suricata_state = context
-            .manager
+        let starts = state::State::load();
+        let suricata_state = status
             .state(SURICATA_CONTAINER_NAME)
-            .map(|state| state.status)
-            .unwrap_or_else(|_| "not running".to_string());
-        let evebox_state = context
-            .manager
+            .map(|state| {
+                format!(
+                    "{}{}",
+                    state.status,
+                    uptime_label(&state, &starts, SURICATA_CONTAINER_NAME)
+                )
+            })
+            .unwrap_or_else(|| disabled_or_not_running(&context, Container::Suricata));
+        let evebox_state = status
             .state(EVEBOX_CONTAINER_NAME)
             .map(|state| {
+                let uptime = uptime_label(&state, &starts, EVEBOX_CONTAINER_NAME);
                 if state.status == "running" {
-                    format!("{} {}", state.status, evebox_url,)
+                    format!("{}{} {}", state.status, uptime, evebox_url,)
                 } else {
-                    state.status
+                    format!("{}{}", state.status, uptime)
                 }
             })
-            .unwrap_or_else(|_| "not running".to_string());
+            .unwrap_or_else(|| disabled_or_not_running(&context, Container::EveBox));
 
-        let running = context.manager.is_running(SURICATA_CONTAINER_NAME)
-            || context.manager.is_running(EVEBOX_CONTAINER_NAME);
+        let running =
+            status.is_running(SURICATA_CONTAINER_NAME) || status.is_running(EVEBOX_CONTAINER_NAME);
 
+        if let Some(watcher) = &watcher {
+            watcher.set_header_row(term::cursor_row());
+        }
         println!(
             "{} Suricata: {} {} EveBox: {}",
             ">>>".cyan(),
@@ -514,32 +2323,56 @@ fn menu_main(mut context: Context) -> Result<()> {
             ">>>".cyan(),
             evebox_state
         );
+        let paused = status
+            .state(SURICATA_CONTAINER_NAME)
+            .map(|state| state.status == "paused")
+            .unwrap_or(false);
+        let rules = if status.is_running(SURICATA_CONTAINER_NAME) && !paused {
+            actions::rule_stats(&context)
+                .map(|stats| format!("{} loaded, {} failed, ", stats.loaded, stats.failed))
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+        println!(
+            "{} Rules: {}last updated {}",
+            ">>>".cyan(),
+            rules,
+            last_rule_update()
+        );
+        if status.is_running(EVEBOX_CONTAINER_NAME) {
+            if let Some(warning) = probe::cert_warning(starts.evebox_cert_expires) {
+                println!("{} {}", ">>>".cyan(), warning.yellow());
+            }
+        }
         println!();
 
-        let interface = context
-            .config
-            .suricata
-            .interfaces
-            .first()
-            .map(String::from)
-            .unwrap_or_default();
+        let interface = context.config.suricata.interfaces.join(", ");
 
         let mut selections = vec![SelectItem::new("refresh", "Refresh Status")];
 
         if running {
             selections.push(SelectItem::new("restart", "Restart"));
             selections.push(SelectItem::new("stop", "Stop"));
+            if paused {
+                selections.push(SelectItem::new("resume", "Resume Capture"));
+            } else if status.is_running(SURICATA_CONTAINER_NAME) {
+                selections.push(SelectItem::new("pause", "Pause Capture"));
+            }
         } else {
             selections.push(SelectItem::new("start", "Start"));
         }
 
-        selections.push(SelectItem::new(
-            "interface",
-            format!("Select Interface [{interface}]"),
-        ));
-        selections.push(SelectItem::new("update-rules", "Update Rules"));
+        if context.config.services.suricata {
+            selections.push(SelectItem::new(
+                "interface",
+                format!("Select Interface [{interface}]"),
+            ));
+            selections.push(SelectItem::new("update-rules", "Update Rules"));
+        }
         selections.push(SelectItem::new("update", "Update"));
         selections.push(SelectItem::new("configure", "Configure"));
+        selections.push(SelectItem::new("troubleshoot", "Troubleshoot"));
         selections.push(SelectItem::new("other", "Other"));
         selections.push(SelectItem::new("exit", "Exit"));
 
@@ -547,39 +2380,76 @@ fn menu_main(mut context: Context) -> Result<()> {
         let response = inquire::Select::new("Select a menu option", selections)
             .with_page_size(12)
             .prompt();
+
+        // The screen is about to change, stop rewriting the header.
+        if let Some(watcher) = &watcher {
+            watcher.set_header_row(None);
+        }
+        let helper = privilege::helper(&context.config);
         match response {
-            Ok(selection) => match selection.tag.as_ref() {
-                "refresh" => {}
-                "start" => {
+            Ok(selection) => match (selection.tag.as_ref(), helper) {
+                ("refresh", _) => status.invalidate(),
+                (
+                    tag @ ("start" | "stop" | "restart" | "pause" | "resume" | "update"
+                    | "update-rules"),
+                    Some(helper),
+                ) => {
+                    let action = match tag {
+                        "update" | "update-rules" => format!("{tag} --now"),
+                        _ => tag.to_string(),
+                    };
+                    privilege::run(helper, &action);
+                    prompt::enter();
+                    status.invalidate();
+                }
+                ("start", _) => {
                     if !start(&context) {
                         prompt::enter();
                     }
+                    status.invalidate();
                 }
-                "stop" => {
-                    if !stop(&context) {
+                ("stop", _) => {
+                    if !stop(&context, false) {
                         prompt::enter();
                     }
+                    status.invalidate();
                 }
-                "restart" => {
-                    stop(&context);
+                ("restart", _) => {
+                    stop(&context, true);
                     if !start(&context) {
                         prompt::enter();
                     }
+                    status.invalidate();
+                }
+                ("pause", _) => {
+                    if let Err(err) = command_pause(&context) {
+                        error!("{err}");
+                        prompt::enter();
+                    }
+                    status.invalidate();
+                }
+                ("resume", _) => {
+                    if let Err(err) = command_resume(&context) {
+                        error!("{err}");
+                        prompt::enter();
+                    }
+                    status.invalidate();
                 }
-                "interface" => select_interface(&mut context),
-                "update" => {
-                    update(&context);
+                ("interface", _) => select_interface(&mut context),
+                ("update", _) => {
+                    update(&context, false);
                     prompt::enter();
                 }
-                "other" => menus::other(&context),
-                "configure" => menu::configure::main(&mut context)?,
-                "update-rules" => {
+                ("troubleshoot", _) => troubleshoot::menu(&context),
+                ("other", _) => menus::other(&context),
+                ("configure", _) => menu::configure::main(&mut context)?,
+                ("update-rules", _) => {
                     if let Err(err) = actions::update_rules(&context) {
                         error!("{}", err);
                     }
                     prompt::enter();
                 }
-                "exit" => break,
+                ("exit", _) => break,
                 _ => panic!("Unhandled selection: {}", selection.tag),
             },
             Err(_) => break,
@@ -589,29 +2459,112 @@ fn menu_main(mut context: Context) -> Result<()> {
     Ok(())
 }
 
+/// The main menu status of a container that doesn't exist.
+fn disabled_or_not_running(context: &Context, container: Container) -> String {
+    if context.config.services.enabled(container) {
+        "not running".to_string()
+    } else {
+        "disabled".dimmed().to_string()
+    }
+}
+
 /// Returns true if everything started successfully, otherwise false
 /// is return.
 fn start(context: &Context) -> bool {
+    let services = context.config.services;
+    if !services.suricata && !services.evebox {
+        error!("No services are enabled in the configuration");
+        return false;
+    }
     let mut ok = true;
-    info!("Starting Suricata");
-    if let Err(err) = start_suricata_detached(context) {
-        error!("Failed to start Suricata: {}", err);
-        ok = false;
+    if services.suricata {
+        info!("Starting Suricata");
+        if let Err(err) = start_suricata_detached(context) {
+            error!("Failed to start Suricata: {}", err);
+            ok = false;
+        }
     }
-    info!("Starting EveBox");
-    if let Err(err) = start_evebox_detached(context) {
-        error!("Failed to start EveBox: {}", err);
-        ok = false;
+    if services.evebox {
+        info!("Starting EveBox");
+        if let Err(err) = start_evebox_detached(context) {
+            error!("Failed to start EveBox: {}", err);
+            ok = false;
+        }
+    }
+    // The report is only scheduled when asked for with a schedule in
+    // the configuration, and removed again when the schedule is.
+    if context.manager.host().is_none() {
+        if let Err(err) = report::update_schedule(&context.config.reports) {
+            error!("Failed to schedule the report: {err}");
+        }
+    }
+    // A Suricata that exited right away is when the issues matter most.
+    if services.suricata && context.manager.container_exists(SURICATA_CONTAINER_NAME) {
+        print_startup_issues(context);
     }
     ok
 }
 
+/// Summarize the warnings and errors Suricata logged while starting, as
+/// they otherwise go unseen in the container logs.
+fn print_startup_issues(context: &Context) {
+    info!("Waiting for Suricata to finish starting");
+    let issues = logs::suricata_startup_issues(context);
+    if issues.is_empty() {
+        return;
+    }
+    warn!("Suricata logged {} issue(s) while starting:", issues.len());
+    for issue in &issues {
+        let count = if issue.count > 1 {
+            format!(" (x{})", issue.count)
+        } else {
+            String::new()
+        };
+        let message = format!("{}{count}", issue.message);
+        if issue.error {
+            println!("  {} {}", "Error:".red(), message);
+        } else {
+            println!("  {} {}", "Warning:".yellow(), message);
+        }
+    }
+    println!("  See the full log with: simple-ids logs suricata");
+}
+
+/// Check the Suricata image supports the capture mode, before starting
+/// it. Loading the build info may run a container, so this is left out
+/// of building the arguments, which is also done to only show them.
+fn check_suricata_build(context: &Context) -> Result<()> {
+    // Without the image there is nothing to check against.
+    let build_info = buildinfo::load(context).unwrap_or_default();
+    if context.config.suricata.ips.is_some() {
+        build_info.require(buildinfo::Feature::NfQueue, "IPS mode")?;
+    } else if context.config.suricata.live_capture() {
+        build_info.require(buildinfo::Feature::AfPacket, "Live capture")?;
+    }
+    Ok(())
+}
+
 fn build_suricata_command(context: &Context, detached: bool) -> Result<std::process::Command> {
-    let interface = match context.config.suricata.interfaces.first() {
-        Some(interface) => interface,
-        None => bail!("no network interface set"),
-    };
+    let args = build_suricata_args(context, detached)?;
+    let mut command = context.manager.command();
+    command.args(&args.args);
+    Ok(command)
+}
+
+/// Build the `run` arguments for the Suricata container.
+fn build_suricata_args(context: &Context, detached: bool) -> Result<ArgBuilder> {
+    let interfaces = &context.config.suricata.interfaces;
+    if context.config.suricata.bridge.is_some() && !context.config.suricata.live_capture() {
+        bail!("bridge mode can't be combined with unix-socket or NFQUEUE mode");
+    }
+    if interfaces.is_empty()
+        && context.config.suricata.bridge.is_none()
+        && context.config.suricata.live_capture()
+    {
+        bail!("no network interface set");
+    }
 
+    // NFQUEUE, like af-packet, requires net_admin.
     let mut args = ArgBuilder::from(&[
         "run",
         "--name",
@@ -624,29 +2577,182 @@ fn build_suricata_command(context: &Context, detached: bool) -> Result<std::proc
 
     if detached {
         args.add("-d");
+        args.add(format!(
+            "--restart={}",
+            context
+                .manager
+                .restart_arg(context.config.restart_policy(Container::Suricata))
+        ));
     }
 
     for volume in SuricataContainer::new(context.clone()).volumes() {
         args.add(format!("--volume={}", volume));
     }
+    if context.config.suricata.pcap_log.enabled {
+        args.add(format!("--volume={}", pcaplog::volume()));
+    }
+    args.extend(&container::limit_args(
+        context.config.suricata.memory_limit.as_deref(),
+        context.config.suricata.cpus.as_deref(),
+    ));
+
+    let config_file = &context.config.suricata.config_file;
+    if let Some(volume) = suricata_config_volume(&context.config.suricata)? {
+        args.add(format!("--volume={volume}"));
+    }
+
+    // The af-packet stub is one of the built-in fixups not applied to a
+    // user provided configuration.
+    let af_packet = if !context.config.suricata.live_capture() || config_file.is_some() {
+        false
+    } else {
+        let path = afpacket::write_af_packet_stub(&context.config.suricata)?;
+        args.add(format!(
+            "--volume={}:{}:ro",
+            path.display(),
+            afpacket::CONTAINER_PATH
+        ));
+        true
+    };
+
+    let mut user_configs = vec![];
+    for (filename, option) in [
+        (CLASSIFICATION_CONFIG, "classification-file"),
+        (REFERENCE_CONFIG, "reference-config-file"),
+        (THRESHOLD_CONFIG, "threshold-file"),
+    ] {
+        let path = state::file_path(filename);
+        if path.exists() {
+            args.add(format!(
+                "--volume={}:/etc/suricata/simple-ids/{}:ro",
+                path.display(),
+                filename
+            ));
+            user_configs.push((filename, option));
+        }
+    }
+
+    args.add(format!(
+        "--label={}={}",
+        CONFIG_LABEL,
+        context.config.fingerprint(Container::Suricata)
+    ));
+
+    args.extend(&container::log_args(
+        &context.manager,
+        &context.config.container_logs,
+    ));
+
+    if context.config.display.container_timezone {
+        args.extend(&container::timezone_args(&context.manager));
+    }
 
     args.add(context.image_name(Container::Suricata));
-    args.extend(&["-v", "-i", interface]);
 
+    let verbosity = context.config.suricata.verbosity.unwrap_or(1);
+    if verbosity > 0 {
+        args.add(format!("-{}", "v".repeat(verbosity as usize)));
+    }
+
+    if config_file.is_some() {
+        args.extend(&["-c", SURICATA_CONTAINER_CONFIG]);
+    }
+
+    if af_packet {
+        args.extend(&["--include", afpacket::CONTAINER_PATH]);
+    }
+
+    for (filename, option) in &user_configs {
+        args.add("--set");
+        args.add(format!("{option}=/etc/suricata/simple-ids/{filename}"));
+    }
+
+    let tuning = &context.config.suricata.tuning;
+    if let Some(runmode) = &tuning.runmode {
+        args.add(format!("--runmode={runmode}"));
+    }
+    if tuning.cpu_affinity {
+        args.extend(&["--set", "threading.set-cpu-affinity=yes"]);
+    }
+    if tuning.no_rules {
+        // Exclusively load an empty rule file.
+        args.extend(&["-S", "/dev/null"]);
+    }
+
+    // Last so they override the settings made above.
+    args.extend(&suricata_user_set_args(&context.config.suricata));
+
+    if context.config.suricata.unix_socket {
+        args.add("--unix-socket");
+    } else if let Some(ips) = &context.config.suricata.ips {
+        args.add("-q");
+        args.add(ips.queue.to_string());
+    } else if context.config.suricata.bridge.is_some() {
+        // Both interfaces of the pair are in the af-packet stub.
+        args.add("--af-packet");
+    } else if let [interface] = interfaces.as_slice() {
+        args.extend(&["-i", interface]);
+    } else {
+        // Capture on every interface in the af-packet stub.
+        args.add("--af-packet");
+    }
+    args.extend(&context.config.suricata.extra_cli_args);
+
+    // The BPF filter must be last, and only applies to live capture.
     if let Some(bpf) = &context.config.suricata.bpf {
-        args.add(bpf);
+        if context.config.suricata.live_capture() {
+            args.add(bpf);
+        }
     }
 
-    let mut command = context.manager.command();
-    command.args(&args.args);
-    Ok(command)
+    Ok(args)
+}
+
+/// The volume mounting the Suricata configuration file of the user to
+/// [`SURICATA_CONTAINER_CONFIG`], None if there is none.
+pub(crate) fn suricata_config_volume(config: &config::SuricataConfig) -> Result<Option<String>> {
+    let Some(config_file) = &config.config_file else {
+        return Ok(None);
+    };
+    let path = match std::fs::canonicalize(config_file) {
+        Ok(path) => path,
+        Err(err) => bail!("Suricata configuration file {config_file}: {err}"),
+    };
+    Ok(Some(format!(
+        "{}:{}:ro",
+        path.display(),
+        SURICATA_CONTAINER_CONFIG
+    )))
+}
+
+/// The `--set` arguments for HOME_NET and the settings of the user.
+pub(crate) fn suricata_user_set_args(config: &config::SuricataConfig) -> Vec<String> {
+    let mut args = vec![];
+    if let Some(home_net) = &config.home_net {
+        args.push("--set".to_string());
+        args.push(format!("vars.address-groups.HOME_NET={home_net}"));
+    }
+    for set in &config.set {
+        if !set.contains('=') {
+            warn!("Ignoring Suricata setting without a value: {set}");
+            continue;
+        }
+        args.push("--set".to_string());
+        args.push(set.to_string());
+    }
+    args
 }
 
 fn suricata_dump_config(context: &Context) -> Result<Vec<String>> {
-    context.manager.quiet_rm(SURICATA_CONTAINER_NAME);
-    let mut command = build_suricata_command(context, false)?;
+    remove_container(context, SURICATA_CONTAINER_NAME);
+    let mut args = build_suricata_args(context, false)?;
+    // Don't leave the container behind to be mistaken for the previous
+    // Suricata.
+    args.args.insert(1, "--rm".to_string());
+    let mut command = context.manager.command();
+    command.args(&args.args);
     command.arg("--dump-config");
-    let output = command.output()?;
+    let output = command.output_timeout(container::timeouts().control)?;
     if output.status.success() {
         let stdout = std::str::from_utf8(&output.stdout)?;
         let lines: Vec<String> = stdout.lines().map(|s| s.to_string()).collect();
@@ -656,85 +2762,324 @@ fn suricata_dump_config(context: &Context) -> Result<Vec<String>> {
     }
 }
 
-fn start_suricata_detached(context: &Context) -> Result<()> {
+/// Settings applied on top of the Suricata configuration in the image,
+/// found from its `--dump-config` output.
+fn suricata_fixup_args(context: &Context) -> Result<Vec<String>> {
     let config = suricata_dump_config(context)?;
-    let mut set_args: Vec<String> = vec![
-        "app-layer.protocols.tls.ja4-fingerprints=true".to_string(),
-        "app-layer.protocols.quic.ja4-fingerprints=true".to_string(),
-    ];
-    let patterns = &[
-        regex::Regex::new(r"(outputs\.\d+\.eve-log\.types\.\d+\.tls)\s")?,
-        regex::Regex::new(r"(outputs\.\d+\.eve-log\.types\.\d+\.quic)\s")?,
-    ];
-    for line in &config {
-        for r in patterns {
-            if let Some(c) = r.captures(line) {
-                set_args.push(format!("{}.ja4=true", &c[1]));
+    let mut set_args: Vec<String> = vec![];
+    if buildinfo::load_or_default(context).supports(buildinfo::Feature::Ja4) {
+        set_args.extend([
+            "app-layer.protocols.tls.ja4-fingerprints=true".to_string(),
+            "app-layer.protocols.quic.ja4-fingerprints=true".to_string(),
+        ]);
+        let patterns = &[
+            regex::Regex::new(r"(outputs\.\d+\.eve-log\.types\.\d+\.tls)\s")?,
+            regex::Regex::new(r"(outputs\.\d+\.eve-log\.types\.\d+\.quic)\s")?,
+        ];
+        for line in &config {
+            for r in patterns {
+                if let Some(c) = r.captures(line) {
+                    set_args.push(format!("{}.ja4=true", &c[1]));
+                }
             }
         }
     }
+    set_args.extend(stats_set_args(&context.config.suricata.stats, &config));
+    set_args.extend(pcaplog::set_args(
+        &context.config.suricata.pcap_log,
+        &config,
+    ));
+    set_args.extend(tuning::eve_set_args(
+        &context.config.suricata.tuning,
+        &config,
+    ));
+    if state::State::load().reduced_logging {
+        warn!("Logging is reduced due to low disk space");
+        set_args.extend(diskpolicy::reduced_logging_args(&config));
+    }
+    Ok(set_args)
+}
+
+fn start_suricata_detached(context: &Context) -> Result<()> {
+    check_suricata_build(context)?;
 
-    context.manager.quiet_rm(SURICATA_CONTAINER_NAME);
+    // A user provided configuration is used as is.
+    let mut set_args = if context.config.suricata.config_file.is_some() {
+        vec![]
+    } else {
+        suricata_fixup_args(context)?
+    };
+
+    // Settings from the configuration file take precedence.
+    let overridden: Vec<&str> = context
+        .config
+        .suricata
+        .set
+        .iter()
+        .filter_map(|set| set.split_once('=').map(|(key, _)| key.trim()))
+        .collect();
+    set_args.retain(|set| {
+        set.split_once('=')
+            .map(|(key, _)| !overridden.contains(&key))
+            .unwrap_or(true)
+    });
+
+    remove_container(context, SURICATA_CONTAINER_NAME);
     let mut command = build_suricata_command(context, true)?;
     for s in &set_args {
         command.arg("--set");
         command.arg(s);
     }
-    let output = command.output()?;
+    let output = command.output_timeout(container::timeouts().control)?;
     if !output.status.success() {
         bail!(String::from_utf8_lossy(&output.stderr).to_string());
     }
+    if let Err(err) = state::State::record_start(SURICATA_CONTAINER_NAME) {
+        error!("Failed to record Suricata start: {err}");
+    }
 
-    if let Err(err) = start_suricata_logrotate(context) {
-        error!("{}", err);
+    if let Err(err) = logrotate::start_detached(context) {
+        error!("Failed to start Suricata log rotation: {err}");
     }
     Ok(())
 }
 
-fn start_suricata_logrotate(context: &Context) -> Result<()> {
-    info!("Starting Suricata log rotation");
-    match context
-        .manager
-        .command()
-        .args([
-            "exec",
-            "-d",
-            SURICATA_CONTAINER_NAME,
-            "bash",
-            "-c",
-            "while true; do logrotate -v /etc/logrotate.d/suricata > /tmp/last_logrotate 2>&1; sleep 600; done",
-        ])
-        .output()
+/// Suricata `--set` arguments for the stats configuration, the output
+/// indexes are found in the `--dump-config` output.
+fn stats_set_args(stats: &config::StatsConfig, dump_config: &[String]) -> Vec<String> {
+    let mut set_args = vec![];
+    if let Some(interval) = stats.interval {
+        set_args.push(format!("stats.interval={interval}"));
+    }
+    let outputs = [
+        (stats.stats_log, r"^(outputs\.\d+\.stats)[\s.]"),
+        (
+            stats.eve_stats,
+            r"^(outputs\.\d+\.eve-log\.types\.\d+\.stats)[\s.]",
+        ),
+    ];
+    for (enabled, pattern) in outputs {
+        let enabled = match enabled {
+            Some(enabled) => enabled,
+            None => continue,
+        };
+        let re = regex::Regex::new(pattern).unwrap();
+        let mut prefixes: Vec<&str> = dump_config
+            .iter()
+            .filter_map(|line| re.captures(line).and_then(|c| c.get(1)))
+            .map(|m| m.as_str())
+            .collect();
+        prefixes.dedup();
+        for prefix in prefixes {
+            set_args.push(format!(
+                "{prefix}.enabled={}",
+                if enabled { "yes" } else { "no" }
+            ));
+        }
+    }
+    set_args
+}
+
+/// Environment variable the EveBox agent reads the API key of the
+/// remote server from. It is passed through from the environment of
+/// the container engine client so it doesn't show up in the arguments.
+const EVEBOX_AGENT_API_KEY_ENV: &str = "EVEBOX_AGENT_API_KEY";
+
+/// The EveBox configuration file generated with the settings managed by
+/// Simple-IDS, in the state directory.
+const EVEBOX_GENERATED_CONFIG: &str = "evebox.yaml";
+
+/// Whether the EveBox configuration file needs to be generated, for the
+/// alerting settings or a retention shortened by the disk policy.
+fn evebox_config_generated(config: &config::Config) -> bool {
+    !config.evebox.alerting.is_default() || diskpolicy::evebox_retention_days(config).is_some()
+}
+
+/// The EveBox configuration file to mount into the container: the
+/// generated one if needed, otherwise the user's file.
+fn evebox_config_file(config: &config::Config) -> Option<String> {
+    if evebox_config_generated(config) {
+        Some(
+            state::file_path(EVEBOX_GENERATED_CONFIG)
+                .display()
+                .to_string(),
+        )
+    } else {
+        config.evebox.config_file.clone()
+    }
+}
+
+/// Get a mapping from a YAML mapping, inserting an empty one if missing.
+fn yaml_mapping<'a>(
+    mapping: &'a mut serde_yaml::Mapping,
+    key: &str,
+) -> Result<&'a mut serde_yaml::Mapping> {
+    match mapping
+        .entry(key.into())
+        .or_insert_with(|| serde_yaml::Value::Mapping(Default::default()))
+        .as_mapping_mut()
     {
-        Ok(output) => {
-            if !output.status.success() {
-                bail!(String::from_utf8_lossy(&output.stderr).to_string());
+        Some(mapping) => Ok(mapping),
+        None => bail!("{key} in the EveBox configuration file is not a YAML mapping"),
+    }
+}
+
+/// Write the generated EveBox configuration file, the user's file if
+/// any with the alerting and retention settings of Simple-IDS.
+fn write_evebox_config(config: &config::Config) -> Result<()> {
+    if !evebox_config_generated(config) {
+        return Ok(());
+    }
+    let mut evebox = match &config.evebox.config_file {
+        Some(path) => serde_yaml::from_str(&std::fs::read_to_string(path)?)?,
+        None => serde_yaml::Value::Mapping(Default::default()),
+    };
+    let Some(evebox) = evebox.as_mapping_mut() else {
+        bail!("The EveBox configuration file is not a YAML mapping");
+    };
+
+    if !config.evebox.alerting.is_default() {
+        let secrets = secrets::Secrets::load()?;
+        let mut webhooks = vec![];
+        for webhook in &config.evebox.alerting.webhooks {
+            let mut entry = serde_yaml::Mapping::new();
+            entry.insert("url".into(), secrets.resolve(&webhook.url)?.into());
+            if let Some(min_severity) = webhook.min_severity {
+                entry.insert("min-severity".into(), min_severity.into());
             }
+            webhooks.push(serde_yaml::Value::Mapping(entry));
         }
-        Err(err) => bail!("Failed to initialize log rotation: {err}"),
+        yaml_mapping(evebox, "alerting")?.insert("webhooks".into(), webhooks.into());
+    }
+
+    if let Some(days) = diskpolicy::evebox_retention_days(config) {
+        let retention = yaml_mapping(yaml_mapping(evebox, "database")?, "retention")?;
+        retention.insert("days".into(), days.into());
+    }
+
+    // The webhook URLs may contain tokens.
+    let path = state::file_path(EVEBOX_GENERATED_CONFIG);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
     }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)?;
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    file.write_all(serde_yaml::to_string(evebox)?.as_bytes())?;
     Ok(())
 }
 
 fn build_evebox_command(context: &Context, daemon: bool) -> process::Command {
-    let mut args = ArgBuilder::from(&[
-        "run",
-        "--name",
-        EVEBOX_CONTAINER_NAME,
-        // "--restart=unless-stopped",
-    ]);
-    if context.config.evebox.allow_remote {
+    if let Err(err) = write_evebox_config(&context.config) {
+        error!("Failed to write the EveBox configuration file: {err}");
+    }
+    let args = build_evebox_args(context, daemon);
+    let mut command = context.manager.command();
+    command.args(&args.args);
+    if let Some(api_key) = context
+        .config
+        .evebox
+        .agent
+        .as_ref()
+        .and_then(|agent| agent.api_key.as_ref())
+    {
+        match secrets::Secrets::load().and_then(|secrets| secrets.resolve(api_key)) {
+            Ok(api_key) => {
+                command.env(EVEBOX_AGENT_API_KEY_ENV, api_key);
+            }
+            Err(err) => warn!("Failed to resolve the EveBox agent API key: {err}"),
+        }
+    }
+    command
+}
+
+/// Build the `run` arguments for the EveBox container.
+fn build_evebox_args(context: &Context, daemon: bool) -> ArgBuilder {
+    let agent = context.config.evebox.agent.as_ref();
+    let network_args = container::podman_network_args(&context.manager, &context.config.podman);
+    let host_network = context.manager.is_podman()
+        && context.config.podman.network == Some(config::PodmanNetwork::Host);
+    let mut args = ArgBuilder::from(&["run", "--name", EVEBOX_CONTAINER_NAME]);
+    args.extend(&network_args);
+    if agent.is_some() {
+        // The agent only makes outgoing connections.
+    } else if host_network {
+        // Nothing to publish, EveBox binds to the host addresses itself.
+    } else if context.config.evebox.allow_remote {
         args.add("--publish=5636:5636");
     } else {
         args.add("--publish=127.0.0.1:5636:5636");
     }
     if daemon {
         args.add("-d");
+        args.add(format!(
+            "--restart={}",
+            context
+                .manager
+                .restart_arg(context.config.restart_policy(Container::EveBox))
+        ));
     }
 
     for volume in Container::EveBox.volumes() {
         args.add(format!("--volume={}", volume));
     }
+    args.extend(&container::limit_args(
+        context.config.evebox.memory_limit.as_deref(),
+        context.config.evebox.cpus.as_deref(),
+    ));
+
+    let config_file = evebox_config_file(&context.config);
+    if let Some(config_file) = &config_file {
+        args.add(format!(
+            "--volume={}:{}:ro",
+            config_file, EVEBOX_CONTAINER_CONFIG
+        ));
+    }
+
+    let tls = evebox_tls_files(context);
+    for (_, host, filename) in &tls {
+        args.add(format!(
+            "--volume={host}:{EVEBOX_CONTAINER_TLS}/{filename}:ro"
+        ));
+    }
+
+    args.add(format!(
+        "--label={}={}",
+        CONFIG_LABEL,
+        context.config.fingerprint(Container::EveBox)
+    ));
+
+    args.extend(&container::log_args(
+        &context.manager,
+        &context.config.container_logs,
+    ));
+
+    if context.config.display.container_timezone {
+        args.extend(&container::timezone_args(&context.manager));
+    }
+
+    if let Some(agent) = agent {
+        if agent.api_key.is_some() {
+            args.add(format!("--env={EVEBOX_AGENT_API_KEY_ENV}"));
+        }
+        args.add(context.image_name(Container::EveBox));
+        args.extend(&["evebox", "agent", "--server", &agent.server]);
+        if config_file.is_some() {
+            args.extend(&["--config", EVEBOX_CONTAINER_CONFIG]);
+        }
+        // The bookmark is kept in the EveBox volume so the agent resumes
+        // where it left off when the container is recreated.
+        args.extend(&[
+            "--data-directory",
+            "/var/lib/evebox",
+            "/var/log/suricata/eve.json",
+        ]);
+        return args;
+    }
 
     args.add(context.image_name(Container::EveBox));
     args.extend(&["evebox", "server"]);
@@ -747,10 +3092,41 @@ fn build_evebox_command(context: &Context, daemon: bool) -> process::Command {
         args.add("--no-auth");
     }
 
-    args.extend(&["--host=[::0]", "--sqlite", "/var/log/suricata/eve.json"]);
-    let mut command = context.manager.command();
-    command.args(&args.args);
-    command
+    if config_file.is_some() {
+        args.extend(&["--config", EVEBOX_CONTAINER_CONFIG]);
+    }
+
+    for (option, _, filename) in &tls {
+        args.add(format!("{option}={EVEBOX_CONTAINER_TLS}/{filename}"));
+    }
+
+    if host_network && !context.config.evebox.allow_remote {
+        args.add("--host=127.0.0.1");
+    } else {
+        args.add("--host=[::0]");
+    }
+    args.extend(&["--sqlite", "/var/log/suricata/eve.json"]);
+    args
+}
+
+/// The TLS files to mount into the EveBox server container, as the
+/// EveBox option, the path on the host and the filename in the
+/// container.
+///
+/// EveBox has no client certificate options, mutual TLS with sensors is
+/// terminated by reverse proxies, see the README.
+fn evebox_tls_files(context: &Context) -> Vec<(&'static str, String, &'static str)> {
+    let evebox = &context.config.evebox;
+    let mut files = vec![];
+    if let Some(tls) = evebox
+        .tls
+        .as_ref()
+        .filter(|_| evebox.agent.is_none() && !evebox.no_tls)
+    {
+        files.push(("--tls-cert", tls.cert.clone(), "server.pem"));
+        files.push(("--tls-key", tls.key.clone(), "server-key.pem"));
+    }
+    files
 }
 
 fn start_evebox_detached(context: &Context) -> Result<()> {
@@ -758,52 +3134,190 @@ fn start_evebox_detached(context: &Context) -> Result<()> {
 }
 
 fn select_interface(context: &mut Context) {
-    let interfaces = evectl::system::get_interfaces().unwrap();
-    let current_if = context.config.suricata.interfaces.first();
-    let index = interfaces
-        .iter()
-        .position(|interface| Some(&interface.name) == current_if)
-        .unwrap_or(0);
-    let selections: Vec<SelectItem> = interfaces
+    let interfaces = match netif::get_interfaces(context) {
+        Ok(interfaces) => interfaces,
+        Err(err) => {
+            error!("Failed to get network interfaces: {err}");
+            prompt::enter();
+            return;
+        }
+    };
+    let groups = &context.config.interface_groups;
+    let current = &context.config.suricata.interfaces;
+    let mut selections: Vec<SelectItem> = groups
         .iter()
-        .enumerate()
-        .map(|(i, ifname)| {
-            let address = ifname
-                .addr4
-                .first()
-                .map(|s| format!("-- {}", s.green().italic()))
-                .unwrap_or("".to_string());
+        .map(|(name, members)| {
             SelectItem::new(
-                ifname.name.to_string(),
-                format!("{}) {} {}", i + 1, ifname.name, address),
+                format!("group:{name}"),
+                format!("{} {}", name, format!("-- {}", members.join(", ")).cyan()),
             )
         })
         .collect();
-    match inquire::Select::new("Select interface", selections)
+    selections.push(SelectItem::new("[multiple]", "Select multiple interfaces"));
+    selections.extend(interfaces.iter().map(|ifname| {
+        let address = ifname
+            .addr4
+            .first()
+            .map(|s| format!("-- {}", s.green().italic()))
+            .unwrap_or("".to_string());
+        SelectItem::new(
+            ifname.name.to_string(),
+            format!("{} {}", ifname.name, address),
+        )
+    }));
+    let index = groups
+        .values()
+        .position(|members| members == current)
+        .or_else(|| {
+            interfaces
+                .iter()
+                .position(|interface| current.first() == Some(&interface.name))
+                .map(|i| i + groups.len() + 1)
+        })
+        .unwrap_or(0);
+    let selections = add_index(&selections);
+    match inquire::Select::new("Select interface or interface group", selections)
         .with_starting_cursor(index)
         .with_page_size(12)
         .prompt()
     {
         Err(_) => {}
         Ok(selection) => {
-            context.config.suricata.interfaces = vec![selection.tag.to_string()];
-            let _ = context.config.save();
+            let interfaces = if selection.tag == "[multiple]" {
+                match select_multiple_interfaces(&interfaces, current) {
+                    Some(interfaces) => interfaces,
+                    None => return,
+                }
+            } else if let Some(group) = selection.tag.strip_prefix("group:") {
+                groups[group].clone()
+            } else {
+                vec![selection.tag.to_string()]
+            };
+            if interfaces != context.config.suricata.interfaces {
+                context.config.suricata.interfaces = interfaces;
+                let _ = context.config.save();
+                if context.manager.is_running(SURICATA_CONTAINER_NAME)
+                    && context.config.suricata.live_capture()
+                {
+                    reconfigure_capture(context);
+                }
+            }
         }
     }
 }
 
-fn update(context: &Context) -> bool {
-    let mut ok = true;
-    for image in [
-        context.image_name(Container::Suricata),
-        context.image_name(Container::EveBox),
-    ] {
-        if let Err(err) = context.manager.pull(&image) {
-            error!("Failed to pull {image}: {err}");
-            ok = false;
+fn select_multiple_interfaces(
+    interfaces: &[netif::Interface],
+    current: &[String],
+) -> Option<Vec<String>> {
+    let names: Vec<String> = interfaces.iter().map(|i| i.name.clone()).collect();
+    let defaults: Vec<usize> = names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| current.contains(name))
+        .map(|(i, _)| i)
+        .collect();
+    match inquire::MultiSelect::new("Select interfaces", names)
+        .with_default(&defaults)
+        .with_page_size(12)
+        .prompt()
+    {
+        Ok(selected) if !selected.is_empty() => Some(selected),
+        _ => None,
+    }
+}
+
+/// Apply a change of capture interface to the running Suricata.
+///
+/// Suricata can't add or remove AF_PACKET interfaces over its unix
+/// socket, so unless it is already capturing on the selected interfaces
+/// only Suricata is restarted, leaving EveBox running.
+fn reconfigure_capture(context: &Context) {
+    let mut wanted = context.config.suricata.interfaces.clone();
+    wanted.sort();
+    match actions::capture_interfaces(context) {
+        Ok(mut current) => {
+            current.sort();
+            if current == wanted {
+                info!("Suricata is already capturing on {}", wanted.join(", "));
+                return;
+            }
+        }
+        Err(err) => debug!("Failed to get Suricata capture interfaces: {err}"),
+    }
+    if !confirm("Restart Suricata now to capture on the new interface?") {
+        return;
+    }
+    if stop_container(context, SURICATA_CONTAINER_NAME, None) {
+        info!("Starting Suricata");
+        if let Err(err) = start_suricata_detached(context) {
+            error!("Failed to start Suricata: {err}");
+        }
+    }
+    prompt::enter();
+}
+
+fn update(context: &Context, scan: bool) -> bool {
+    if context.config.updates.offline {
+        warn!("Offline mode is enabled, not checking for updates");
+        return true;
+    }
+
+    // Only the images of the enabled services are of interest.
+    let images: Vec<String> = [Container::Suricata, Container::EveBox]
+        .into_iter()
+        .filter(|container| context.config.services.enabled(*container))
+        .map(|container| context.image_name(container))
+        .collect();
+
+    // Pull the images concurrently, the progress lines are prefixed with
+    // the image name.
+    let mut ok = container::block_on(async {
+        let handles: Vec<_> = images
+            .iter()
+            .map(|image| {
+                let manager = context.manager.clone();
+                let image = image.clone();
+                container::spawn(async move {
+                    info!("Pulling {image}");
+                    match manager.pull(&image).await {
+                        Ok(()) => {
+                            info!("Pulled {image}");
+                            journal::record(journal::Kind::ImageUpdate, image.as_str());
+                            true
+                        }
+                        Err(err) => {
+                            let err = err.to_string();
+                            error!("Failed to pull {image}: {}", err.trim_end());
+                            journal::record(
+                                journal::Kind::ImageUpdateFailed,
+                                format!("{image}: {}", err.trim_end()),
+                            );
+                            false
+                        }
+                    }
+                })
+            })
+            .collect();
+        let mut ok = true;
+        for handle in handles {
+            ok &= handle.await.unwrap_or(false);
+        }
+        ok
+    });
+    if scan && ok {
+        match imagescan::scan(context, &images) {
+            Ok(0) => {}
+            Ok(critical) => {
+                warn!("{critical} critical vulnerabilities found, review them before restarting")
+            }
+            Err(err) => {
+                error!("{err}");
+                ok = false;
+            }
         }
     }
-    if let Err(err) = selfupdate::self_update() {
+    if let Err(err) = selfupdate::self_update(&context.config.updates) {
         error!("Failed to update Simple-IDS: {err}");
         ok = false;
     }
@@ -0,0 +1,174 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Maintenance window, limiting when disruptive operations like rule
+//! and image updates run.
+//!
+//! A window is given as optional days followed by a time range in the
+//! host's local time, for example "Sun 03:00-04:00", "Mon-Fri
+//! 01:00-02:00", "Sat,Sun 22:00-02:00" or "03:00-04:00" for every day.
+//! A window that ends before it starts runs past midnight into the next
+//! day.
+
+use anyhow::{bail, Context as _, Result};
+use chrono::{Datelike, NaiveTime, Weekday};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Window {
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl Window {
+    pub(crate) fn parse(window: &str) -> Result<Self> {
+        let (days, times) = match window.trim().rsplit_once(char::is_whitespace) {
+            Some((days, times)) => (parse_days(days.trim())?, times),
+            None => (ALL_DAYS.to_vec(), window.trim()),
+        };
+        let Some((start, end)) = times.split_once('-') else {
+            bail!("Invalid maintenance window time range: {times}");
+        };
+        Ok(Self {
+            days,
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+        })
+    }
+
+    /// Return true if the given local time is in the window.
+    pub(crate) fn contains(&self, now: chrono::NaiveDateTime) -> bool {
+        let time = now.time();
+        let today = self.days.contains(&now.weekday());
+        if self.start < self.end {
+            today && time >= self.start && time < self.end
+        } else {
+            // Wraps past midnight, the early part belongs to the window
+            // that started the day before.
+            let yesterday = self.days.contains(&now.weekday().pred());
+            (today && time >= self.start) || (yesterday && time < self.end)
+        }
+    }
+}
+
+const ALL_DAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+fn parse_days(days: &str) -> Result<Vec<Weekday>> {
+    let mut parsed = vec![];
+    for part in days.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let last = parse_day(last)?;
+                let mut day = parse_day(first)?;
+                parsed.push(day);
+                while day != last {
+                    day = day.succ();
+                    parsed.push(day);
+                }
+            }
+            None => parsed.push(parse_day(part)?),
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_day(day: &str) -> Result<Weekday> {
+    day.trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid day in maintenance window: {day}"))
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .with_context(|| format!("Invalid time in maintenance window: {time}"))
+}
+
+/// Check if a disruptive operation may run now. Without a configured
+/// window it always may.
+pub(crate) fn allowed(window: Option<&str>) -> Result<bool> {
+    match window {
+        None => Ok(true),
+        Some(window) => Ok(Window::parse(window)?.contains(chrono::Local::now().naive_local())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// A local time in the week of Sunday, 2024-10-13, by day name.
+    fn at(day: Weekday, time: &str) -> chrono::NaiveDateTime {
+        let sunday = NaiveDate::from_ymd_opt(2024, 10, 13).unwrap();
+        let date = sunday + chrono::Days::new(day.num_days_from_sunday().into());
+        date.and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+    }
+
+    #[test]
+    fn test_single_day() {
+        let window = Window::parse("Sun 03:00-04:00").unwrap();
+        assert_eq!(window.days, vec![Weekday::Sun]);
+        assert!(window.contains(at(Weekday::Sun, "03:00")));
+        assert!(window.contains(at(Weekday::Sun, "03:59")));
+        assert!(!window.contains(at(Weekday::Sun, "04:00")));
+        assert!(!window.contains(at(Weekday::Sun, "02:59")));
+        assert!(!window.contains(at(Weekday::Mon, "03:30")));
+    }
+
+    #[test]
+    fn test_every_day() {
+        let window = Window::parse("03:00-04:00").unwrap();
+        assert_eq!(window.days, ALL_DAYS.to_vec());
+        for day in ALL_DAYS {
+            assert!(window.contains(at(day, "03:30")));
+        }
+    }
+
+    #[test]
+    fn test_day_ranges() {
+        let window = Window::parse("Mon-Fri 01:00-02:00").unwrap();
+        assert_eq!(window.days, ALL_DAYS[..5].to_vec());
+        assert!(window.contains(at(Weekday::Wed, "01:30")));
+        assert!(!window.contains(at(Weekday::Sat, "01:30")));
+
+        // A range may run past the end of the week.
+        let window = Window::parse("Fri-Mon 01:00-02:00").unwrap();
+        assert_eq!(
+            window.days,
+            vec![Weekday::Fri, Weekday::Sat, Weekday::Sun, Weekday::Mon]
+        );
+
+        let window = Window::parse("Sat,Sun 01:00-02:00").unwrap();
+        assert_eq!(window.days, vec![Weekday::Sat, Weekday::Sun]);
+    }
+
+    #[test]
+    fn test_wrap_past_midnight() {
+        let window = Window::parse("Sat 22:00-02:00").unwrap();
+        assert!(window.contains(at(Weekday::Sat, "23:00")));
+        // The early hours of Sunday belong to Saturday's window.
+        assert!(window.contains(at(Weekday::Sun, "01:00")));
+        assert!(!window.contains(at(Weekday::Sun, "02:00")));
+        assert!(!window.contains(at(Weekday::Sun, "23:00")));
+        assert!(!window.contains(at(Weekday::Sat, "01:00")));
+        assert!(!window.contains(at(Weekday::Sat, "21:59")));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Window::parse("").is_err());
+        assert!(Window::parse("03:00").is_err());
+        assert!(Window::parse("03:00-25:00").is_err());
+        assert!(Window::parse("3am-4am").is_err());
+        assert!(Window::parse("Someday 03:00-04:00").is_err());
+        assert!(Window::parse("Mon-Someday 03:00-04:00").is_err());
+    }
+}
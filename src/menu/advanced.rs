@@ -1,7 +1,8 @@
 // SPDX-FileCopyrightText: (C) 2023 Jason Ish <jason@codemonkey.net>
 // SPDX-License-Identifier: MIT
 
-use crate::{container::Container, context::Context, SelectItem};
+use super::help;
+use crate::{config::Runtime, container::Container, context::Context, prompt, SelectItem};
 
 pub(crate) fn advanced_menu(context: &mut Context) {
     loop {
@@ -16,17 +17,40 @@ pub(crate) fn advanced_menu(context: &mut Context) {
                 format!("Suricata Container: {}", suricata_image_name),
             ),
             SelectItem::new("evebox", format!("EveBox Container: {}", evebox_image_name)),
+            SelectItem::new(
+                "runtime",
+                format!("Container Runtime: {}", context.config.runtime),
+            ),
+            SelectItem::new(
+                "container-timezone",
+                format!(
+                    "Container Timezone: {}",
+                    if context.config.display.container_timezone {
+                        "host"
+                    } else {
+                        "UTC"
+                    }
+                ),
+            ),
+            help::item(),
             SelectItem::new("return", "Return"),
         ];
 
-        match inquire::Select::new("Select container to configure", selections).prompt() {
+        match inquire::Select::new("Select option to configure", selections.clone()).prompt() {
             Ok(selection) => match selection.tag.as_ref() {
+                help::TAG => help::explain("advanced", &selections),
                 "suricata" => {
                     set_suricata_image(context, &suricata_image_name);
                 }
                 "evebox" => {
                     set_evebox_image(context, &evebox_image_name);
                 }
+                "runtime" => set_runtime(context),
+                "container-timezone" => {
+                    context.config.display.container_timezone =
+                        !context.config.display.container_timezone;
+                    context.config.save().unwrap();
+                }
                 "return" => return,
                 _ => unimplemented!(),
             },
@@ -35,6 +59,25 @@ pub(crate) fn advanced_menu(context: &mut Context) {
     }
 }
 
+fn set_runtime(context: &mut Context) {
+    let runtimes = vec![Runtime::Auto, Runtime::Docker, Runtime::Podman];
+    let index = runtimes
+        .iter()
+        .position(|runtime| *runtime == context.config.runtime)
+        .unwrap_or(0);
+    if let Ok(runtime) = inquire::Select::new("Select container runtime", runtimes)
+        .with_starting_cursor(index)
+        .with_help_message(help::summary("advanced.runtime"))
+        .prompt()
+    {
+        if runtime != context.config.runtime {
+            context.config.runtime = runtime;
+            context.config.save().unwrap();
+            prompt::enter_with_prefix("Restart Simple-IDS for the runtime change to take effect");
+        }
+    }
+}
+
 fn set_suricata_image(context: &mut Context, default: &str) {
     match inquire::Text::new("Enter Suricata image name")
         .with_default(default)
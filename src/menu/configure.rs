@@ -2,8 +2,17 @@
 // SPDX-License-Identifier: MIT
 
 use anyhow::Result;
+use tracing::error;
 
-use crate::{add_index, context::Context, term, SelectItem};
+use crate::{
+    add_index,
+    config::{RestartPolicy, ServicesConfig},
+    container::Container,
+    context::Context,
+    prompt, service, term,
+    tuning::Preset,
+    SelectItem,
+};
 
 /// Main configure menu.
 pub(crate) fn main(context: &mut Context) -> Result<()> {
@@ -14,6 +23,18 @@ pub(crate) fn main(context: &mut Context) -> Result<()> {
             SelectItem::new("suricata", "Suricata Configuration"),
             SelectItem::new("suricata-update", "Suricata-Update Configuration"),
             SelectItem::new("evebox", "EveBox Configuration"),
+            SelectItem::new(
+                "services",
+                format!(
+                    "Deployment Mode (Currently {})",
+                    mode_label(&context.config.services)
+                ),
+            ),
+            SelectItem::new(
+                "start-on-boot",
+                format!("Start on Boot (Currently {})", boot_label(context)),
+            ),
+            SelectItem::new("preset", "Apply Tuning Preset"),
             SelectItem::new("advanced", "Advanced"),
             SelectItem::new("return", "Return"),
         ];
@@ -24,6 +45,9 @@ pub(crate) fn main(context: &mut Context) -> Result<()> {
                 "suricata" => crate::menu::suricata::menu(context),
                 "suricata-update" => crate::menu::suricata_update::menu(context)?,
                 "evebox" => crate::menu::evebox::configure(context),
+                "services" => select_mode(context),
+                "start-on-boot" => toggle_start_on_boot(context),
+                "preset" => apply_preset(context),
                 "advanced" => crate::menu::advanced::advanced_menu(context),
                 "return" => return Ok(()),
                 _ => unimplemented!(),
@@ -34,3 +58,115 @@ pub(crate) fn main(context: &mut Context) -> Result<()> {
 
     Ok(())
 }
+
+/// Whether the enabled containers come back after a reboot, either
+/// through their restart policy or the Simple-IDS service.
+fn persists(context: &Context) -> bool {
+    [Container::Suricata, Container::EveBox]
+        .into_iter()
+        .filter(|container| context.config.services.enabled(*container))
+        .all(|container| context.config.restart_policy(container).persists())
+}
+
+fn boot_label(context: &Context) -> String {
+    if service::installed() {
+        return "enabled with the simple-ids service".to_string();
+    }
+    if !persists(context) {
+        return "disabled".to_string();
+    }
+    let unit = service::engine_boot_unit(&context.manager);
+    if context.manager.host().is_none() && !service::unit_enabled(unit) {
+        return format!("enabled, but {unit} is disabled");
+    }
+    "enabled".to_string()
+}
+
+/// Toggle the global unless-stopped restart policy, so the container
+/// engine brings the containers back after a crash or reboot.
+fn toggle_start_on_boot(context: &mut Context) {
+    if service::installed() {
+        prompt::enter_with_prefix(
+            "Simple-IDS is started on boot by its service, remove it first with: simple-ids service uninstall",
+        );
+        return;
+    }
+    if persists(context) {
+        let previous = context.config.restart.take();
+        if persists(context) {
+            context.config.restart = previous;
+            prompt::enter_with_prefix(
+                "The Suricata or EveBox restart policy is still set, change it in their menus",
+            );
+            return;
+        }
+    } else {
+        context.config.restart = Some(RestartPolicy::UnlessStopped);
+        let unit = service::engine_boot_unit(&context.manager);
+        if context.manager.host().is_none()
+            && !service::unit_enabled(unit)
+            && prompt::confirm(&format!("{unit} is not enabled, enable it now?"), None)
+        {
+            if let Err(err) = service::enable_unit(unit) {
+                error!("Failed to enable {unit}: {err}");
+            }
+        }
+    }
+    if let Err(err) = context.config.save() {
+        error!("Failed to save configuration: {err}");
+        prompt::enter();
+        return;
+    }
+    prompt::enter_with_prefix("Restart Simple-IDS to apply the restart policy");
+}
+
+fn mode_label(services: &ServicesConfig) -> &'static str {
+    match (services.suricata, services.evebox) {
+        (true, true) => "Suricata and EveBox",
+        (true, false) => "Suricata only",
+        (false, true) => "EveBox only",
+        (false, false) => "nothing enabled",
+    }
+}
+
+/// Select which containers are managed. An EveBox only deployment is a
+/// central console receiving events from remote agents.
+fn select_mode(context: &mut Context) {
+    let selections = vec![
+        SelectItem::new("both", "Suricata and EveBox"),
+        SelectItem::new("suricata", "Suricata only"),
+        SelectItem::new("evebox", "EveBox only (central console for remote agents)"),
+    ];
+    let selections = add_index(&selections);
+    let services = match inquire::Select::new("Select deployment mode", selections).prompt() {
+        Ok(selection) => ServicesConfig {
+            suricata: selection.tag != "evebox",
+            evebox: selection.tag != "suricata",
+        },
+        Err(_) => return,
+    };
+    if services == context.config.services {
+        return;
+    }
+    context.config.services = services;
+    if let Err(err) = context.config.save() {
+        error!("Failed to save configuration: {err}");
+        prompt::enter();
+        return;
+    }
+    prompt::enter_with_prefix("Restart Simple-IDS to apply the deployment mode");
+}
+
+fn apply_preset(context: &mut Context) {
+    if let Ok(preset) = inquire::Select::new("Select tuning preset", Preset::all().to_vec())
+        .with_help_message("Replaces the current tuning settings")
+        .prompt()
+    {
+        preset.apply(&mut context.config);
+        context.config.save().unwrap();
+        if preset == Preset::Performance {
+            crate::tuning::check_offloads(&context.config, true);
+        }
+        prompt::enter_with_prefix("Restart Simple-IDS to apply the preset");
+    }
+}
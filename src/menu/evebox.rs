@@ -1,11 +1,23 @@
 // SPDX-FileCopyrightText: (C) 2023 Jason Ish <jason@codemonkey.net>
 // SPDX-License-Identifier: MIT
 
+use std::{
+    io::{Read, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    process::Stdio,
+};
+
+use anyhow::Result;
+use colored::Colorize;
 use tracing::{error, info, warn};
 
+use super::help;
 use crate::{
-    actions, add_index, config::EveBoxConfig, container::Container, context::Context, prompt, term,
-    ArgBuilder, SelectItem, EVEBOX_CONTAINER_NAME,
+    actions, add_index,
+    config::{EveBoxAgentConfig, EveBoxConfig, EveBoxWebhook},
+    container::{timeouts, CommandExt, Container},
+    context::Context,
+    prompt, state, term, ArgBuilder, SelectItem, EVEBOX_CONTAINER_NAME,
 };
 
 pub(crate) fn configure(context: &mut Context) {
@@ -17,7 +29,7 @@ pub(crate) fn configure(context: &mut Context) {
         let is_running = context.manager.is_running(EVEBOX_CONTAINER_NAME);
         restart_required = is_running && original_config != context.config;
 
-        let selections = vec![
+        let mut selections = vec![
             if context.config.evebox.allow_remote {
                 SelectItem::new("disable-remote", "Disable Remote Access")
             } else {
@@ -46,6 +58,62 @@ pub(crate) fn configure(context: &mut Context) {
                 ),
             ),
             SelectItem::new("reset-password", "Reset Admin Password"),
+            SelectItem::new(
+                "sensor-mode",
+                format!(
+                    "Sensor-Only Mode (Currently {})",
+                    context
+                        .config
+                        .evebox
+                        .agent
+                        .as_ref()
+                        .map(|agent| format!("forwarding to {}", agent.server))
+                        .unwrap_or_else(|| "disabled".to_string())
+                ),
+            ),
+            SelectItem::new(
+                "config-file",
+                format!(
+                    "EveBox Configuration File (Currently {})",
+                    context
+                        .config
+                        .evebox
+                        .config_file
+                        .as_deref()
+                        .unwrap_or("none")
+                ),
+            ),
+            SelectItem::new(
+                "alerting",
+                format!(
+                    "Alerting Webhooks (Currently {})",
+                    context.config.evebox.alerting.webhooks.len()
+                ),
+            ),
+            SelectItem::new(
+                "external-url",
+                format!(
+                    "Advertised URL (Currently {})",
+                    context
+                        .config
+                        .evebox
+                        .external_url
+                        .as_deref()
+                        .unwrap_or("guessed")
+                ),
+            ),
+            SelectItem::new(
+                "restart-policy",
+                format!(
+                    "Restart Policy (Currently {})",
+                    context
+                        .config
+                        .evebox
+                        .restart
+                        .map_or("global", |policy| policy.as_str())
+                ),
+            ),
+            help::item(),
             SelectItem::new(
                 "return",
                 if restart_required {
@@ -55,14 +123,39 @@ pub(crate) fn configure(context: &mut Context) {
                 },
             ),
         ];
-        let selections = add_index(&selections);
-        if let Ok(selection) = inquire::Select::new("Select menu option", selections).prompt() {
+        if context.config.evebox.agent.is_some() {
+            // There is no local server to configure in sensor-only mode.
+            selections.retain(|item| {
+                !matches!(
+                    item.tag.as_ref(),
+                    "enable-remote"
+                        | "disable-remote"
+                        | "toggle-tls"
+                        | "toggle-auth"
+                        | "reset-password"
+                        | "alerting"
+                        | "external-url"
+                )
+            });
+        }
+        let indexed = add_index(&selections);
+        if let Ok(selection) = inquire::Select::new("Select menu option", indexed).prompt() {
             match selection.tag.as_ref() {
+                help::TAG => help::explain("evebox", &selections),
                 "toggle-tls" => toggle_tls(&mut context.config.evebox),
                 "toggle-auth" => toggle_auth(&mut context.config.evebox),
                 "reset-password" => reset_password(context),
+                "sensor-mode" => set_sensor_mode(&mut context.config.evebox),
+                "config-file" => set_config_file(&mut context.config.evebox),
+                "alerting" => alerting(&mut context.config.evebox),
+                "external-url" => set_external_url(&mut context.config.evebox),
                 "enable-remote" => enable_remote_access(context),
                 "disable-remote" => disable_remote_access(context),
+                "restart-policy" => {
+                    if let Some(policy) = super::select_restart_policy("EveBox") {
+                        context.config.evebox.restart = policy;
+                    }
+                }
                 "return" => break,
                 _ => {}
             }
@@ -93,6 +186,7 @@ fn toggle_tls(config: &mut EveBoxConfig) {
             match inquire::Confirm::new(
                 "Remote access is enabled, are you sure you want to disable TLS",
             )
+            .with_help_message(help::summary("evebox.toggle-tls"))
             .with_default(false)
             .prompt()
             {
@@ -112,6 +206,7 @@ fn toggle_auth(config: &mut EveBoxConfig) {
             match inquire::Confirm::new(
                 "Remote access is enabled, are you sure you want to disable authentication",
             )
+            .with_help_message(help::summary("evebox.toggle-auth"))
             .with_default(false)
             .prompt()
             {
@@ -135,6 +230,7 @@ fn enable_remote_access(context: &mut Context) {
     context.config.evebox.allow_remote = true;
 
     if let Ok(true) = inquire::Confirm::new("Do you wish to reset the admin password")
+        .with_help_message(help::summary("evebox.reset-password"))
         .with_default(true)
         .prompt()
     {
@@ -149,11 +245,262 @@ fn enable_remote_access(context: &mut Context) {
     prompt::enter_with_prefix("EveBox remote access has been enabled");
 }
 
+/// Set the EveBox configuration file that holds settings made outside
+/// of Simple-IDS, such as alert notification rules and webhooks.
+fn set_config_file(config: &mut EveBoxConfig) {
+    let current = config.config_file.clone().unwrap_or_default();
+    let path = match inquire::Text::new("EveBox configuration file (empty for none)")
+        .with_initial_value(&current)
+        .prompt()
+    {
+        Ok(path) => path.trim().to_string(),
+        Err(_) => return,
+    };
+
+    if path.is_empty() {
+        config.config_file = None;
+        return;
+    }
+
+    let path = match std::fs::canonicalize(&path) {
+        Ok(path) => path,
+        Err(err) => {
+            error!("Failed to find {path}: {err}");
+            prompt::enter();
+            return;
+        }
+    };
+    match std::fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|buf| Ok(serde_yaml::from_str::<serde_yaml::Value>(&buf)?))
+    {
+        Ok(_) => config.config_file = Some(path.display().to_string()),
+        Err(err) => {
+            error!(
+                "Invalid EveBox configuration file {}: {err}",
+                path.display()
+            );
+            prompt::enter();
+        }
+    }
+}
+
+/// Manage the webhooks EveBox posts alerts to. They are kept in the
+/// Simple-IDS configuration and written to the EveBox configuration
+/// file on start.
+fn alerting(config: &mut EveBoxConfig) {
+    loop {
+        term::title("Simple-IDS: EveBox Alerting");
+
+        let mut selections = vec![];
+        for (i, webhook) in config.alerting.webhooks.iter().enumerate() {
+            selections.push(SelectItem::new(
+                format!("remove-{i}"),
+                format!(
+                    "Remove {} (Minimum severity {})",
+                    webhook.url,
+                    webhook
+                        .min_severity
+                        .map_or("any".to_string(), |severity| severity.to_string())
+                ),
+            ));
+        }
+        selections.push(SelectItem::new("add", "Add Webhook"));
+        selections.push(SelectItem::new("return", "Return"));
+
+        let selection =
+            match inquire::Select::new("Select menu option", add_index(&selections)).prompt() {
+                Ok(selection) => selection,
+                Err(_) => return,
+            };
+        match selection.tag.as_ref() {
+            "add" => add_webhook(config),
+            "return" => return,
+            tag => {
+                if let Some(i) = tag
+                    .strip_prefix("remove-")
+                    .and_then(|i| i.parse::<usize>().ok())
+                {
+                    config.alerting.webhooks.remove(i);
+                }
+            }
+        }
+    }
+}
+
+fn add_webhook(config: &mut EveBoxConfig) {
+    let url = match inquire::Text::new("Webhook URL")
+        .with_help_message(
+            "May be a secret:<key> reference to a URL stored with simple-ids secrets",
+        )
+        .prompt()
+    {
+        Ok(url) => url.trim().to_string(),
+        Err(_) => return,
+    };
+    if url.is_empty() {
+        return;
+    }
+    if !url.starts_with("http://") && !url.starts_with("https://") && !url.starts_with("secret:") {
+        error!("The URL must start with http://, https:// or secret:");
+        prompt::enter();
+        return;
+    }
+    let min_severity = match inquire::CustomType::<u8>::new("Minimum severity (empty for any)")
+        .with_help_message("1 is the most severe")
+        .prompt_skippable()
+    {
+        Ok(min_severity) => min_severity,
+        Err(_) => return,
+    };
+    config
+        .alerting
+        .webhooks
+        .push(EveBoxWebhook { url, min_severity });
+}
+
+/// Set the URL EveBox is advertised at, for when the guess from the
+/// interfaces is wrong, such as behind NAT or a reverse proxy.
+fn set_external_url(config: &mut EveBoxConfig) {
+    let current = config.external_url.clone().unwrap_or_default();
+    let url = match inquire::Text::new("Advertised EveBox URL (empty to guess)")
+        .with_initial_value(&current)
+        .with_help_message(help::summary("evebox.external-url"))
+        .prompt()
+    {
+        Ok(url) => url.trim().trim_end_matches('/').to_string(),
+        Err(_) => return,
+    };
+    if url.is_empty() {
+        config.external_url = None;
+    } else if !url.starts_with("http://") && !url.starts_with("https://") {
+        error!("The URL must start with http:// or https://");
+        prompt::enter();
+    } else {
+        config.external_url = Some(url);
+    }
+}
+
+/// Configure sensor-only mode, where the EveBox agent forwards the
+/// events to a remote EveBox server instead of running a local server.
+fn set_sensor_mode(config: &mut EveBoxConfig) {
+    let current = config
+        .agent
+        .as_ref()
+        .map(|agent| agent.server.clone())
+        .unwrap_or_default();
+    let server = match inquire::Text::new("Remote EveBox server URL (empty to disable)")
+        .with_initial_value(&current)
+        .with_help_message("For example https://evebox.example.com:5636")
+        .prompt()
+    {
+        Ok(server) => server.trim().trim_end_matches('/').to_string(),
+        Err(_) => return,
+    };
+
+    if server.is_empty() {
+        config.agent = None;
+        return;
+    }
+    if !server.starts_with("http://") && !server.starts_with("https://") {
+        error!("The server URL must start with http:// or https://");
+        prompt::enter();
+        return;
+    }
+
+    let current = config
+        .agent
+        .as_ref()
+        .and_then(|agent| agent.api_key.clone())
+        .unwrap_or_default();
+    let api_key = match inquire::Text::new("API key (empty for none)")
+        .with_initial_value(&current)
+        .with_help_message("Use secret:<key> to reference a key stored with simple-ids secrets")
+        .prompt()
+    {
+        Ok(api_key) => api_key.trim().to_string(),
+        Err(_) => return,
+    };
+
+    config.agent = Some(EveBoxAgentConfig {
+        server,
+        api_key: (!api_key.is_empty()).then_some(api_key),
+    });
+}
+
 fn disable_remote_access(context: &mut Context) {
     context.config.evebox.allow_remote = false;
 }
 
-fn reset_password(context: &mut Context) {
+/// Length of generated passwords.
+const PASSWORD_LEN: usize = 20;
+
+/// Characters used in generated passwords, without the ones that are
+/// easily confused such as l, 1, O and 0.
+const PASSWORD_CHARS: &[u8] = b"abcdefghijkmnopqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// File the admin password can be saved to, readable by root only.
+const PASSWORD_FILENAME: &str = "evebox-admin-password";
+
+fn generate_password() -> Result<String> {
+    let mut urandom = std::fs::File::open("/dev/urandom")?;
+    let mut password = String::new();
+    let mut buf = [0u8; 64];
+    // Rejection sampling so each character is equally likely.
+    let limit = 256 - (256 % PASSWORD_CHARS.len());
+    while password.len() < PASSWORD_LEN {
+        urandom.read_exact(&mut buf)?;
+        for b in buf {
+            if (b as usize) < limit && password.len() < PASSWORD_LEN {
+                password.push(PASSWORD_CHARS[b as usize % PASSWORD_CHARS.len()] as char);
+            }
+        }
+    }
+    Ok(password)
+}
+
+fn save_password(password: &str) -> Result<std::path::PathBuf> {
+    let path = state::file_path(PASSWORD_FILENAME);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)?;
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    writeln!(file, "{password}")?;
+    Ok(path)
+}
+
+/// Prompt for a new admin password, or generate one. None is returned
+/// if the user cancelled.
+fn new_password() -> Option<String> {
+    let selections = vec![
+        SelectItem::new("generate", "Generate a strong password"),
+        SelectItem::new("enter", "Enter a password"),
+    ];
+    match inquire::Select::new("Admin password", selections).prompt() {
+        Ok(selection) if selection.tag == "generate" => match generate_password() {
+            Ok(password) => Some(password),
+            Err(err) => {
+                error!("Failed to generate password: {err}");
+                None
+            }
+        },
+        Ok(_) => inquire::Password::new("Password").prompt().ok(),
+        Err(_) => None,
+    }
+}
+
+/// Add the admin user, returning false if it failed, such as when the
+/// user already exists.
+///
+/// The password is written to the container on stdin so it doesn't show
+/// up in the arguments of the container runtime command.
+fn add_admin(context: &Context, password: &str, quiet: bool) -> bool {
     let image = context.image_name(Container::EveBox);
     let mut args = ArgBuilder::new();
     args.add("run");
@@ -162,26 +509,132 @@ fn reset_password(context: &mut Context) {
         args.add(volume);
     }
     args.extend(&[
-        "--rm", "-it", &image, "evebox", "config", "users", "rm", "admin",
+        "--rm",
+        "-i",
+        &image,
+        "sh",
+        "-c",
+        "read -r password && exec evebox config users add --username admin --password \"$password\"",
     ]);
-    let _ = context.manager.command().args(&args.args).status();
+    let mut command = context.manager.command();
+    command.args(&args.args);
+    if quiet {
+        command.stdout(Stdio::null()).stderr(Stdio::null());
+    }
+    command
+        .status_ok_input_timeout(format!("{password}\n").as_bytes(), timeouts().control)
+        .is_ok()
+}
+
+/// Create the admin user with a generated password the first time
+/// EveBox is started with authentication, so there is a way to log in
+/// without first finding the reset password menu. The password is
+/// shown once and saved to a file only readable by root.
+pub(crate) fn bootstrap_admin(context: &Context) {
+    let evebox = &context.config.evebox;
+    if evebox.no_auth || evebox.agent.is_some() || state::State::load().evebox_admin_created {
+        return;
+    }
+    let password = match generate_password() {
+        Ok(password) => password,
+        Err(err) => {
+            error!("Failed to generate the EveBox admin password: {err}");
+            return;
+        }
+    };
+    // Adding fails if the user already exists, such as when set up by
+    // an older version, in which case it is left alone. Other failures
+    // are retried on the next start.
+    if add_admin(context, &password, true) {
+        println!();
+        println!("Created the EveBox admin user");
+        println!("EveBox username: {}", "admin".bold());
+        println!("EveBox password: {}", password.bold());
+        println!("This password will not be shown again.");
+        match save_password(&password) {
+            Ok(path) => println!("It has been saved to {}", path.display()),
+            Err(err) => error!("Failed to save password: {err}"),
+        }
+        println!();
+    } else if admin_exists(context) {
+        info!("The EveBox admin user already exists");
+    } else {
+        error!("Failed to create the EveBox admin user, it will be tried again on the next start");
+        return;
+    }
+    if let Err(err) = state::State::update(|state| state.evebox_admin_created = true) {
+        error!("Failed to record the EveBox admin user: {err}");
+    }
+}
 
+/// Return true if the admin user is known to exist in the EveBox
+/// volume.
+fn admin_exists(context: &Context) -> bool {
+    let image = context.image_name(Container::EveBox);
     let mut args = ArgBuilder::new();
     args.add("run");
     for volume in Container::EveBox.volumes() {
         args.add("-v");
         args.add(volume);
     }
-    args.extend(&[
-        "--rm",
-        "-it",
-        &image,
-        "evebox",
-        "config",
-        "users",
-        "add",
-        "--username",
-        "admin",
-    ]);
-    let _ = context.manager.command().args(&args.args).status();
+    args.extend(&["--rm", &image, "evebox", "config", "users", "list"]);
+    match context
+        .manager
+        .command()
+        .args(&args.args)
+        .output_timeout(timeouts().control)
+    {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .any(|word| word == "admin"),
+        _ => false,
+    }
+}
+
+fn reset_password(context: &mut Context) {
+    let password = match new_password() {
+        Some(password) => password,
+        None => return,
+    };
+
+    let image = context.image_name(Container::EveBox);
+    let mut args = ArgBuilder::new();
+    args.add("run");
+    for volume in Container::EveBox.volumes() {
+        args.add("-v");
+        args.add(volume);
+    }
+    args.extend(&["--rm", &image, "evebox", "config", "users", "rm", "admin"]);
+    let _ = context
+        .manager
+        .command()
+        .args(&args.args)
+        .output_timeout(timeouts().control);
+
+    if !add_admin(context, &password, false) {
+        error!("Failed to set the EveBox admin password");
+        prompt::enter();
+        return;
+    }
+    let _ = state::State::update(|state| state.evebox_admin_created = true);
+
+    println!();
+    println!("EveBox username: {}", "admin".bold());
+    println!("EveBox password: {}", password.bold());
+    println!("This password will not be shown again.");
+    println!();
+
+    if prompt::confirm(
+        "Save the password to a file only readable by root?",
+        Some(&format!(
+            "Saved to {}",
+            state::file_path(PASSWORD_FILENAME).display()
+        )),
+    ) {
+        match save_password(&password) {
+            Ok(path) => info!("Password saved to {}", path.display()),
+            Err(err) => error!("Failed to save password: {err}"),
+        }
+    }
+    prompt::enter();
 }
@@ -0,0 +1,201 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Explanations of the menu options, kept in one place so the same
+//! option is described the same way wherever it appears.
+
+use colored::Colorize;
+
+use crate::{prompt, SelectItem};
+
+/// Tag of the menu item that explains the other items.
+pub(crate) const TAG: &str = "help";
+
+/// Width the explanations are wrapped to.
+const WIDTH: usize = 72;
+
+pub(crate) struct Topic {
+    /// The menu and item tag, such as "evebox.toggle-tls".
+    pub key: &'static str,
+    pub title: &'static str,
+    /// One line, suitable for a prompt help message.
+    pub summary: &'static str,
+    /// What the option actually does, and its security implications.
+    pub details: &'static str,
+}
+
+const TOPICS: &[Topic] = &[
+    Topic {
+        key: "evebox.enable-remote",
+        title: "Enable Remote Access",
+        summary: "Makes EveBox reachable from other hosts, TLS and authentication are turned on",
+        details: "EveBox normally only listens on localhost. Enabling remote access \
+            publishes its port on all interfaces so it can be reached from other \
+            hosts. TLS and authentication are enabled at the same time, as EveBox \
+            shows the alerts and events of your network and anyone who can reach \
+            it unauthenticated can read them. Make sure the firewall only allows \
+            the hosts that need access.",
+    },
+    Topic {
+        key: "evebox.disable-remote",
+        title: "Disable Remote Access",
+        summary: "EveBox only listens on localhost again",
+        details: "EveBox goes back to only listening on localhost, so it can only be \
+            reached from this host, or through an SSH tunnel or reverse proxy. \
+            This is the safest setting.",
+    },
+    Topic {
+        key: "evebox.toggle-tls",
+        title: "Toggle TLS",
+        summary: "Without TLS, passwords and events are sent in the clear",
+        details: "With TLS enabled EveBox serves HTTPS with a self-signed certificate \
+            unless one is provided. Disabling TLS serves plain HTTP, so logins and \
+            the event data can be read or modified by anyone on the network path. \
+            Only disable TLS when EveBox is limited to localhost, or sits behind a \
+            reverse proxy that terminates TLS.",
+    },
+    Topic {
+        key: "evebox.toggle-auth",
+        title: "Toggle Authentication",
+        summary: "Without authentication, anyone who can reach EveBox can use it",
+        details: "With authentication enabled EveBox requires a username and \
+            password. Disabling it lets anyone who can reach EveBox view the \
+            events and archive or escalate alerts. Only disable authentication \
+            when EveBox is limited to localhost, or another layer such as a \
+            reverse proxy handles authentication.",
+    },
+    Topic {
+        key: "evebox.reset-password",
+        title: "Reset Admin Password",
+        summary: "Sets a new password for the EveBox admin user",
+        details: "Sets a new password for the admin user, creating the user if it \
+            doesn't exist. The password is shown once, store it somewhere safe.",
+    },
+    Topic {
+        key: "evebox.sensor-mode",
+        title: "Sensor-Only Mode",
+        summary: "Forwards events to a remote EveBox server instead of running one",
+        details: "Runs the EveBox agent instead of the EveBox server, forwarding the \
+            events to a central EveBox server. No web interface is served from \
+            this host. The agent authenticates with an API key, which can be \
+            stored with simple-ids secrets rather than in the configuration.",
+    },
+    Topic {
+        key: "evebox.config-file",
+        title: "EveBox Configuration File",
+        summary: "Settings made outside of Simple-IDS, such as notification rules",
+        details: "A YAML configuration file mounted into the EveBox container for \
+            settings Simple-IDS doesn't manage. Settings made by Simple-IDS, such \
+            as TLS and authentication, are passed on the command line.",
+    },
+    Topic {
+        key: "evebox.alerting",
+        title: "Alerting Webhooks",
+        summary: "Webhooks EveBox posts alerts to",
+        details: "Webhooks EveBox posts alerts to, optionally only those of a minimum \
+            severity. They are kept in the Simple-IDS configuration and written to \
+            the EveBox configuration file when EveBox is started, so they survive \
+            the container being recreated. URLs containing a token can be stored \
+            with simple-ids secrets and referenced as secret:<key>.",
+    },
+    Topic {
+        key: "evebox.external-url",
+        title: "Advertised URL",
+        summary: "The URL shown for EveBox, for example https://evebox.example.com:5636",
+        details: "The URL shown in the status and after starting. By default it is \
+            guessed from the address of the interface with the default route. Set \
+            it when EveBox is reached through NAT, a reverse proxy or a DNS name. \
+            It only changes what is shown, not what EveBox listens on.",
+    },
+    Topic {
+        key: "evebox.restart-policy",
+        title: "Restart Policy",
+        summary: "When the container engine restarts EveBox",
+        details: "Whether the container engine restarts EveBox when it exits, or \
+            when the host boots. Unless set to global, this overrides the \
+            global restart policy.",
+    },
+    Topic {
+        key: "advanced.suricata",
+        title: "Suricata Container",
+        summary: "The image Suricata is run from",
+        details: "The image the Suricata container is run from. Suricata runs with \
+            the capabilities needed to capture packets on the host network, so \
+            only use images from a source you trust. Images built without \
+            features such as AF_PACKET or NFQueue limit the options available. \
+            Reset to the default to go back to the official image.",
+    },
+    Topic {
+        key: "advanced.evebox",
+        title: "EveBox Container",
+        summary: "The image EveBox is run from",
+        details: "The image the EveBox container is run from. EveBox has access to \
+            all the events and stores the user accounts, so only use images from \
+            a source you trust. Reset to the default to go back to the official \
+            image.",
+    },
+    Topic {
+        key: "advanced.runtime",
+        title: "Container Runtime",
+        summary: "Docker or Podman, Auto uses Docker if found",
+        details: "The container engine used to run the containers. Containers \
+            started with one engine are not seen by the other, so stop \
+            Simple-IDS before changing it.",
+    },
+    Topic {
+        key: "advanced.container-timezone",
+        title: "Container Timezone",
+        summary: "Whether the containers log in UTC or the host timezone",
+        details: "Runs the containers in the timezone of the host instead of UTC. \
+            This changes the timestamps in the container logs, the events \
+            record their timezone offset either way.",
+    },
+];
+
+pub(crate) fn topic(key: &str) -> Option<&'static Topic> {
+    TOPICS.iter().find(|topic| topic.key == key)
+}
+
+/// The one line summary of a topic, empty if there is none.
+pub(crate) fn summary(key: &str) -> &'static str {
+    topic(key).map(|topic| topic.summary).unwrap_or_default()
+}
+
+/// The menu item that explains the other items.
+pub(crate) fn item() -> SelectItem {
+    SelectItem::new(TAG, "Help: Explain These Options")
+}
+
+/// Explain the topics for the items of a menu, items without a topic
+/// are skipped.
+pub(crate) fn explain(menu: &str, items: &[SelectItem]) {
+    println!();
+    for item in items {
+        if let Some(topic) = topic(&format!("{menu}.{}", item.tag)) {
+            println!("{}", topic.title.bold());
+            for line in wrap(topic.details) {
+                println!("  {line}");
+            }
+            println!();
+        }
+    }
+    prompt::enter();
+}
+
+fn wrap(text: &str) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + word.len() + 1 > WIDTH {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
@@ -4,5 +4,47 @@
 pub(crate) mod advanced;
 pub(crate) mod configure;
 pub(crate) mod evebox;
+pub(crate) mod help;
 pub(crate) mod suricata;
 pub(crate) mod suricata_update;
+
+use crate::config::RestartPolicy;
+
+/// Select the restart policy of a container, returning None if
+/// cancelled and Some(None) to use the global policy.
+pub(crate) fn select_restart_policy(service: &str) -> Option<Option<RestartPolicy>> {
+    let policies = [
+        (RestartPolicy::No, "No, leave it stopped"),
+        (
+            RestartPolicy::OnFailure,
+            "On failure, restart if it exits with an error",
+        ),
+        (
+            RestartPolicy::UnlessStopped,
+            "Unless stopped, restart unless stopped by simple-ids stop",
+        ),
+        (
+            RestartPolicy::Always,
+            "Always, keep running even through simple-ids stop",
+        ),
+    ];
+    let mut selections: Vec<crate::SelectItem> = policies
+        .iter()
+        .map(|(policy, label)| crate::SelectItem::new(policy.as_str(), *label))
+        .collect();
+    selections.push(crate::SelectItem::new(
+        "global",
+        "Global, use the policy of both containers",
+    ));
+    let selections = crate::add_index(&selections);
+    let selection = inquire::Select::new(&format!("Restart policy for {service}"), selections)
+        .prompt()
+        .ok()?;
+    if selection.tag == "global" {
+        return Some(None);
+    }
+    policies
+        .iter()
+        .find(|(policy, _)| policy.as_str() == selection.tag)
+        .map(|(policy, _)| Some(*policy))
+}
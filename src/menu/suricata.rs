@@ -1,9 +1,17 @@
 // SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
 // SPDX-License-Identifier: MIT
 
-use crate::{add_index, context::Context, term, SelectItem};
+use crate::{
+    add_index,
+    buildinfo::{self, Feature},
+    config::{BridgeConfig, InterfaceSettings, IpsConfig},
+    context::Context,
+    nftables, pcaplog, prompt, term, SelectItem, CLASSIFICATION_CONFIG, REFERENCE_CONFIG,
+    SURICATA_CONTAINER_NAME, THRESHOLD_CONFIG,
+};
 
 pub(crate) fn menu(context: &mut Context) {
+    let build_info = buildinfo::load(context).unwrap_or_default();
     loop {
         term::title("Simple IDS: Configure Suricata");
 
@@ -13,16 +21,110 @@ pub(crate) fn menu(context: &mut Context) {
             "".to_string()
         };
 
-        let selections = vec![
+        let current_home_net = match &context.config.suricata.home_net {
+            Some(home_net) => format!(" [{home_net}]"),
+            None => " [Suricata default]".to_string(),
+        };
+
+        let current_ips = match &context.config.suricata.ips {
+            Some(ips) => format!("enabled on queue {}", ips.queue),
+            None => "disabled".to_string(),
+        };
+
+        let current_bridge = match &context.config.suricata.bridge {
+            Some(bridge) => format!("{} <-> {}", bridge.lan, bridge.wan),
+            None => "disabled".to_string(),
+        };
+
+        let mut selections = vec![
+            SelectItem::new("home-net", format!("HOME_NET{current_home_net}")),
             SelectItem::new("bpf-filter", format!("BPF filter{}", current_bpf)),
+            SelectItem::new(
+                "unix-socket",
+                format!(
+                    "Unix socket mode, no live capture (Currently {})",
+                    if context.config.suricata.unix_socket {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    }
+                ),
+            ),
+            SelectItem::new(
+                "ips",
+                format!("IPS mode via NFQUEUE (Currently {current_ips})"),
+            ),
+            SelectItem::new(
+                "bridge",
+                format!("Inline bridge between two interfaces (Currently {current_bridge})"),
+            ),
+            SelectItem::new(
+                "restart-policy",
+                format!(
+                    "Restart policy (Currently {})",
+                    context
+                        .config
+                        .suricata
+                        .restart
+                        .map_or("global", |policy| policy.as_str())
+                ),
+            ),
+            SelectItem::new(
+                "pcap-log",
+                format!(
+                    "Full packet capture with pcap-log (Currently {})",
+                    if context.config.suricata.pcap_log.enabled {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    }
+                ),
+            ),
+            SelectItem::new("capture-settings", "Interface Snaplen/MTU"),
+            SelectItem::new("interface-groups", "Interface Groups"),
+            SelectItem::new("classification-config", "Edit classification.config"),
+            SelectItem::new("reference-config", "Edit reference.config"),
+            SelectItem::new("threshold-config", "Edit threshold.config"),
             SelectItem::new("return", "Return"),
         ];
 
+        // Hide the modes the Suricata in the image can't run, unless
+        // already enabled so they can be disabled.
+        if !build_info.supports(Feature::NfQueue) && context.config.suricata.ips.is_none() {
+            selections.retain(|item| item.tag != "ips");
+        }
+        if !build_info.supports(Feature::AfPacket) && context.config.suricata.bridge.is_none() {
+            selections.retain(|item| item.tag != "bridge");
+        }
+
         let selections = add_index(&selections);
 
         match inquire::Select::new("Select an option", selections).prompt() {
             Ok(selection) => match selection.tag.as_ref() {
+                "home-net" => set_home_net(context),
                 "bpf-filter" => set_bpf_filter(context),
+                "capture-settings" => set_capture_settings(context),
+                "interface-groups" => interface_groups(context),
+                "classification-config" => edit_suricata_config(context, CLASSIFICATION_CONFIG),
+                "reference-config" => edit_suricata_config(context, REFERENCE_CONFIG),
+                "threshold-config" => edit_suricata_config(context, THRESHOLD_CONFIG),
+                "unix-socket" => {
+                    context.config.suricata.unix_socket = !context.config.suricata.unix_socket;
+                    if context.config.suricata.unix_socket {
+                        context.config.suricata.ips = None;
+                        context.config.suricata.bridge = None;
+                    }
+                    context.config.save().unwrap();
+                }
+                "ips" => ips_mode(context),
+                "bridge" => bridge_mode(context),
+                "pcap-log" => pcap_log(context),
+                "restart-policy" => {
+                    if let Some(policy) = super::select_restart_policy("Suricata") {
+                        context.config.suricata.restart = policy;
+                        context.config.save().unwrap();
+                    }
+                }
                 _ => return,
             },
             Err(_) => return,
@@ -30,6 +132,354 @@ pub(crate) fn menu(context: &mut Context) {
     }
 }
 
+/// Enable or disable IPS mode, setting up the rules diverting the
+/// traffic forwarded between two interfaces to NFQUEUE.
+fn ips_mode(context: &mut Context) {
+    if context.config.suricata.ips.is_some() {
+        if !prompt::confirm("Disable IPS mode and go back to af-packet capture?", None) {
+            return;
+        }
+        context.config.suricata.ips = None;
+        context.config.save().unwrap();
+        if nftables::available() && prompt::confirm("Remove the nftables divert rules?", None) {
+            if let Err(err) = nftables::disable() {
+                prompt::enter_with_prefix(&format!("Failed to remove the divert rules: {err}"));
+            }
+        }
+        restart_reminder(context);
+        return;
+    }
+
+    let interfaces: Vec<String> = match crate::netif::get_interfaces(context) {
+        Ok(interfaces) => interfaces.into_iter().map(|i| i.name).collect(),
+        Err(err) => {
+            prompt::enter_with_prefix(&format!("Failed to get network interfaces: {err}"));
+            return;
+        }
+    };
+    let lan = match inquire::Select::new("LAN side interface", interfaces.clone()).prompt() {
+        Ok(lan) => lan,
+        Err(_) => return,
+    };
+    let wan_choices: Vec<String> = interfaces.into_iter().filter(|i| *i != lan).collect();
+    let wan = match inquire::Select::new("WAN side interface", wan_choices).prompt() {
+        Ok(wan) => wan,
+        Err(_) => return,
+    };
+    let queue = match inquire::CustomType::<u16>::new("NFQUEUE number")
+        .with_default(0)
+        .prompt()
+    {
+        Ok(queue) => queue,
+        Err(_) => return,
+    };
+
+    context.config.suricata.ips = Some(IpsConfig {
+        queue,
+        lan: Some(lan.clone()),
+        wan: Some(wan.clone()),
+    });
+    context.config.suricata.unix_socket = false;
+    context.config.suricata.bridge = None;
+    context.config.save().unwrap();
+
+    if nftables::available() {
+        print!("{}", nftables::ruleset(&lan, &wan, queue));
+        if prompt::confirm("Load these nftables divert rules now?", None) {
+            let persist = prompt::confirm("Load the rules on boot?", None);
+            if let Err(err) = nftables::enable(&lan, &wan, queue, persist) {
+                prompt::enter_with_prefix(&format!("Failed to load the divert rules: {err}"));
+            }
+        }
+    } else {
+        println!("nft not found, divert the traffic with iptables instead:\n");
+        print!("{}", nftables::iptables_rules(&lan, &wan, queue));
+        println!();
+    }
+    println!(
+        "Only rules with the drop action block traffic, convert alerting rules with drop.conf\n"
+    );
+    restart_reminder(context);
+}
+
+/// Toggle pcap-log, asking for the size of the ring buffer when
+/// enabling it.
+fn pcap_log(context: &mut Context) {
+    let config = &mut context.config.suricata.pcap_log;
+    if config.enabled {
+        config.enabled = false;
+        context.config.save().unwrap();
+        println!("The existing pcaps are kept, remove them with: simple-ids pcap prune --days 0\n");
+        restart_reminder(context);
+        return;
+    }
+
+    let limit = match inquire::Text::new("Size of each pcap file")
+        .with_default(config.limit.as_deref().unwrap_or(pcaplog::DEFAULT_LIMIT))
+        .prompt()
+    {
+        Ok(limit) => limit.trim().to_string(),
+        Err(_) => return,
+    };
+    let max_files = match inquire::CustomType::<u32>::new("Number of pcap files to keep")
+        .with_default(config.max_files.unwrap_or(pcaplog::DEFAULT_MAX_FILES))
+        .prompt()
+    {
+        Ok(max_files) => max_files,
+        Err(_) => return,
+    };
+    config.enabled = true;
+    config.limit = (limit != pcaplog::DEFAULT_LIMIT).then_some(limit);
+    config.max_files = (max_files != pcaplog::DEFAULT_MAX_FILES).then_some(max_files);
+    context.config.save().unwrap();
+    restart_reminder(context);
+}
+
+/// Enable or disable the inline bridge between two interfaces.
+fn bridge_mode(context: &mut Context) {
+    if context.config.suricata.bridge.is_some() {
+        if prompt::confirm("Disable the inline bridge?", None) {
+            context.config.suricata.bridge = None;
+            context.config.save().unwrap();
+            restart_reminder(context);
+        }
+        return;
+    }
+
+    let interfaces: Vec<String> = match crate::netif::get_interfaces(context) {
+        Ok(interfaces) => interfaces.into_iter().map(|i| i.name).collect(),
+        Err(err) => {
+            prompt::enter_with_prefix(&format!("Failed to get network interfaces: {err}"));
+            return;
+        }
+    };
+    let lan =
+        match inquire::Select::new("First interface of the bridge", interfaces.clone()).prompt() {
+            Ok(lan) => lan,
+            Err(_) => return,
+        };
+    let wan_choices: Vec<String> = interfaces.into_iter().filter(|i| *i != lan).collect();
+    let wan = match inquire::Select::new("Second interface of the bridge", wan_choices).prompt() {
+        Ok(wan) => wan,
+        Err(_) => return,
+    };
+
+    context.config.suricata.bridge = Some(BridgeConfig { lan, wan });
+    context.config.suricata.unix_socket = false;
+    context.config.suricata.ips = None;
+    context.config.save().unwrap();
+    println!(
+        "The interfaces must be up without addresses, and traffic only passes while \
+         Suricata is running\n"
+    );
+    restart_reminder(context);
+}
+
+fn restart_reminder(context: &Context) {
+    if context.manager.is_running(SURICATA_CONTAINER_NAME) {
+        prompt::enter_with_prefix("Restart Suricata to apply the change");
+    } else {
+        prompt::enter();
+    }
+}
+
+/// Edit a user provided copy of a Suricata configuration file, starting
+/// with the version in the image.
+fn edit_suricata_config(context: &Context, filename: &str) {
+    super::suricata_update::edit_with_template(
+        context,
+        filename,
+        &format!("/etc/suricata/{filename}"),
+        || Ok(()),
+    );
+    if context.manager.is_running(SURICATA_CONTAINER_NAME) {
+        prompt::enter_with_prefix(&format!("Restart Suricata to use the new {filename}"));
+    }
+}
+
+fn interface_groups(context: &mut Context) {
+    loop {
+        let mut selections: Vec<SelectItem> = context
+            .config
+            .interface_groups
+            .iter()
+            .map(|(name, members)| {
+                SelectItem::new(name.clone(), format!("{name} ({})", members.join(", ")))
+            })
+            .collect();
+        selections.push(SelectItem::new("[add]", "Add group"));
+        selections.push(SelectItem::new("[return]", "Return"));
+        let selections = add_index(&selections);
+        match inquire::Select::new("Select group to edit", selections).prompt() {
+            Ok(selection) if selection.tag == "[add]" => {
+                if let Ok(name) = inquire::Text::new("Group name").prompt() {
+                    let name = name.trim().to_string();
+                    if !name.is_empty() {
+                        edit_interface_group(context, &name);
+                    }
+                }
+            }
+            Ok(selection) if selection.tag == "[return]" => return,
+            Ok(selection) => edit_interface_group(context, &selection.tag),
+            Err(_) => return,
+        }
+    }
+}
+
+/// Select the members of an interface group, selecting none removes
+/// the group.
+fn edit_interface_group(context: &mut Context, name: &str) {
+    let interfaces: Vec<String> = match crate::netif::get_interfaces(context) {
+        Ok(interfaces) => interfaces.into_iter().map(|i| i.name).collect(),
+        Err(err) => {
+            prompt::enter_with_prefix(&format!("Failed to get network interfaces: {err}"));
+            return;
+        }
+    };
+    let current = context
+        .config
+        .interface_groups
+        .get(name)
+        .cloned()
+        .unwrap_or_default();
+    let defaults: Vec<usize> = interfaces
+        .iter()
+        .enumerate()
+        .filter(|(_, interface)| current.contains(interface))
+        .map(|(i, _)| i)
+        .collect();
+    if let Ok(members) = inquire::MultiSelect::new(&format!("Interfaces in {name}"), interfaces)
+        .with_default(&defaults)
+        .with_help_message("Select none to remove the group")
+        .prompt()
+    {
+        if members.is_empty() {
+            context.config.interface_groups.remove(name);
+        } else {
+            context
+                .config
+                .interface_groups
+                .insert(name.to_string(), members);
+        }
+        context.config.save().unwrap();
+    }
+}
+
+fn set_capture_settings(context: &mut Context) {
+    let interface = match context.config.suricata.interfaces.as_slice() {
+        [] => {
+            prompt::enter_with_prefix("No interface configured");
+            return;
+        }
+        [interface] => interface.clone(),
+        interfaces => {
+            match inquire::Select::new("Select interface", interfaces.to_vec()).prompt() {
+                Ok(interface) => interface,
+                Err(_) => return,
+            }
+        }
+    };
+    let mut settings = context
+        .config
+        .suricata
+        .interface_settings
+        .get(&interface)
+        .cloned()
+        .unwrap_or_default();
+
+    for (name, value) in [
+        ("Snaplen", &mut settings.snaplen),
+        ("MTU", &mut settings.mtu),
+    ] {
+        let default = value.map(|v| v.to_string()).unwrap_or_default();
+        match inquire::Text::new(&format!("{name} for {interface} (empty for default)"))
+            .with_default(&default)
+            .prompt()
+        {
+            Ok(input) if input.trim().is_empty() => *value = None,
+            Ok(input) => match input.trim().parse() {
+                Ok(v) => *value = Some(v),
+                Err(_) => {
+                    prompt::enter_with_prefix(&format!("Invalid {name}: {input}"));
+                    return;
+                }
+            },
+            Err(_) => return,
+        }
+    }
+
+    if settings == InterfaceSettings::default() {
+        context
+            .config
+            .suricata
+            .interface_settings
+            .remove(&interface);
+    } else {
+        context
+            .config
+            .suricata
+            .interface_settings
+            .insert(interface, settings);
+    }
+    context.config.save().unwrap();
+}
+
+/// Parse a comma separated list of addresses and networks, optionally
+/// in brackets, into a Suricata address group.
+fn parse_home_net(input: &str) -> Result<String, String> {
+    let input = input.trim().trim_start_matches('[').trim_end_matches(']');
+    let mut entries = vec![];
+    for entry in input.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let addr = entry.strip_prefix('!').unwrap_or(entry);
+        let (addr, prefix) = match addr.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (addr, None),
+        };
+        let addr: std::net::IpAddr = addr
+            .parse()
+            .map_err(|_| format!("Invalid address: {entry}"))?;
+        if let Some(prefix) = prefix {
+            let max = if addr.is_ipv4() { 32 } else { 128 };
+            match prefix.parse::<u8>() {
+                Ok(prefix) if prefix <= max => {}
+                _ => return Err(format!("Invalid prefix length: {entry}")),
+            }
+        }
+        entries.push(entry);
+    }
+    if entries.is_empty() {
+        return Err("No addresses given".to_string());
+    }
+    Ok(format!("[{}]", entries.join(",")))
+}
+
+fn set_home_net(context: &mut Context) {
+    let current = context.config.suricata.home_net.clone().unwrap_or_default();
+    let prompt = inquire::Text::new("Enter HOME_NET")
+        .with_initial_value(&current)
+        .with_help_message(
+            "Comma separated networks, for example 192.168.0.0/16,10.0.0.0/8. \
+             Leave empty for the Suricata default",
+        )
+        .with_validator(|input: &str| {
+            if input.trim().is_empty() {
+                return Ok(inquire::validator::Validation::Valid);
+            }
+            Ok(match parse_home_net(input) {
+                Ok(_) => inquire::validator::Validation::Valid,
+                Err(err) => inquire::validator::Validation::Invalid(err.into()),
+            })
+        });
+    if let Ok(input) = prompt.prompt() {
+        context.config.suricata.home_net = if input.trim().is_empty() {
+            None
+        } else {
+            parse_home_net(&input).ok()
+        };
+        context.config.save().unwrap();
+    }
+}
+
 fn set_bpf_filter(context: &mut Context) {
     let default = context
         .config
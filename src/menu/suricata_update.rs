@@ -3,13 +3,16 @@
 
 use crate::{
     add_index,
-    container::{CommandExt, Container, RunCommandBuilder},
+    container::{timeouts, CommandExt, Container, RunCommandBuilder},
     context::Context,
-    prompt, term, SelectItem,
+    dropconf, editor, prompt,
+    ruleindex::RuleSource,
+    secrets::Secrets,
+    state, term, SelectItem,
 };
 use anyhow::Result;
 use colored::Colorize;
-use std::{io::Write, path::PathBuf};
+use std::{io::Write, path::Path};
 use tracing::error;
 
 /// Suricata configure menu.
@@ -21,6 +24,8 @@ pub(crate) fn menu(context: &mut Context) -> Result<()> {
             SelectItem::new("enable-conf", "Edit enable.conf"),
             SelectItem::new("disable-conf", "Edit disable.conf"),
             SelectItem::new("modify-conf", "Edit modify.conf"),
+            SelectItem::new("drop-conf", "Edit drop.conf (IPS)"),
+            SelectItem::new("update-yaml", "Edit update.yaml"),
             SelectItem::new("enable-ruleset", "Enable a Ruleset"),
             SelectItem::new("disable-ruleset", "Disable a Ruleset"),
             SelectItem::new("return", "Return"),
@@ -32,6 +37,8 @@ pub(crate) fn menu(context: &mut Context) -> Result<()> {
                 "disable-conf" => edit_file(context, "disable.conf"),
                 "enable-conf" => edit_file(context, "enable.conf"),
                 "modify-conf" => edit_file(context, "modify.conf"),
+                "drop-conf" => edit_drop_conf(context)?,
+                "update-yaml" => edit_file(context, "update.yaml"),
                 "enable-ruleset" => enable_ruleset(context).unwrap(),
                 "disable-ruleset" => disable_ruleset(context).unwrap(),
                 _ => break,
@@ -55,7 +62,10 @@ fn disable_ruleset(context: &Context) -> Result<()> {
 
     for (id, source) in &index.sources {
         if enabled.contains(id) {
-            let message = format!("{}: {}", id, source.summary.green().italic());
+            let mut message = format!("{}: {}", id, source.summary.green().italic());
+            if let Some(obsolete) = &source.obsolete {
+                message = format!("{} [{}]", message, format!("obsolete: {obsolete}").red());
+            }
             selections.push(SelectItem::new(id, message));
         }
     }
@@ -89,14 +99,24 @@ fn enable_ruleset(context: &Context) -> Result<()> {
         if source.obsolete.is_some() {
             continue;
         }
-        if source.parameters.is_some() {
-            continue;
-        }
         if enabled.contains(id) {
             continue;
         }
 
-        let message = format!("{}: {}", id, source.summary.green().italic());
+        let mut details = vec![];
+        if let Some(vendor) = &source.vendor {
+            details.push(vendor.to_string());
+        }
+        if let Some(license) = &source.license {
+            details.push(license.to_string());
+        }
+        if source.subscribe_url.is_some() || source.parameters.is_some() {
+            details.push("subscription required".to_string());
+        }
+        let mut message = format!("{}: {}", id, source.summary.green().italic());
+        if !details.is_empty() {
+            message = format!("{} [{}]", message, details.join(", ").yellow());
+        }
 
         selections.push(SelectItem::new(id, message));
     }
@@ -106,7 +126,40 @@ fn enable_ruleset(context: &Context) -> Result<()> {
             .with_page_size(16)
             .prompt()
     {
-        let _ = crate::actions::enable_ruleset(context, &selection.tag);
+        if let Some(source) = index.sources.get(&selection.tag) {
+            if source.is_non_free() {
+                let mut help = format!(
+                    "License: {}",
+                    source.license.as_deref().unwrap_or("unknown")
+                );
+                if let Some(url) = &source.subscribe_url {
+                    help = format!("{help}, subscribe at {url}");
+                }
+                if !prompt::confirm(
+                    &format!(
+                        "{} is not free, are you sure you want to enable it?",
+                        selection.tag
+                    ),
+                    Some(&help),
+                ) {
+                    return Ok(());
+                }
+            }
+        }
+
+        let parameters = match index.sources.get(&selection.tag) {
+            Some(source) => match ruleset_parameters(&selection.tag, source) {
+                Ok(Some(parameters)) => parameters,
+                Ok(None) => return Ok(()),
+                Err(err) => {
+                    error!("Failed to load ruleset parameters: {err}");
+                    prompt::enter();
+                    return Ok(());
+                }
+            },
+            None => vec![],
+        };
+        let _ = crate::actions::enable_ruleset(context, &selection.tag, &parameters);
 
         if prompt::confirm(
             "Would you like to update your rules now?",
@@ -121,24 +174,119 @@ fn enable_ruleset(context: &Context) -> Result<()> {
     Ok(())
 }
 
-fn copy_suricata_update_template(context: &Context, filename: &str) -> Result<()> {
-    let source = format!(
-        "/usr/lib/suricata/python/suricata/update/configs/{}",
-        filename
-    );
+/// Get the values for any parameters a ruleset requires, such as the
+/// secret code for ET Pro.
+///
+/// Values are kept in the secrets file so they only need to be entered
+/// once. None is returned if the user cancelled.
+fn ruleset_parameters(id: &str, source: &RuleSource) -> Result<Option<Vec<(String, String)>>> {
+    let mut values = vec![];
+    let parameters = match &source.parameters {
+        Some(parameters) => parameters,
+        None => return Ok(Some(values)),
+    };
+    let mut secrets = Secrets::load()?;
+    for (name, parameter) in parameters {
+        let key = format!("{}/{}", id, name);
+        let value = match secrets.get(&key) {
+            Some(value) => value.to_string(),
+            None => {
+                let message = parameter["prompt"].as_str().unwrap_or(name);
+                match inquire::Password::new(message)
+                    .without_confirmation()
+                    .prompt()
+                {
+                    Ok(value) => {
+                        secrets.set(&key, &value);
+                        secrets.save()?;
+                        value
+                    }
+                    Err(_) => return Ok(None),
+                }
+            }
+        };
+        values.push((name.to_string(), value));
+    }
+    Ok(Some(values))
+}
+
+/// Edit drop.conf, then show which recently firing signatures would
+/// start dropping traffic before applying it with a rule update.
+fn edit_drop_conf(context: &Context) -> Result<()> {
+    edit_file(context, dropconf::FILENAME);
+    if !state::file_path(dropconf::FILENAME).exists() {
+        return Ok(());
+    }
+
+    match dropconf::safety_report(context, 24) {
+        Ok(report) => report.print(),
+        Err(err) => error!("Unable to check recent alerts against drop.conf: {err}"),
+    }
+
+    if prompt::confirm(
+        "Would you like to update your rules now?",
+        Some("A rule update is required to apply drop.conf"),
+    ) {
+        crate::actions::update_rules(context)?;
+    }
+    prompt::enter();
+    Ok(())
+}
+
+fn copy_template(context: &Context, source: &str, path: &Path) -> Result<()> {
     let image = context.image_name(Container::Suricata);
-    let output = RunCommandBuilder::new(context.manager, image)
+    let output = RunCommandBuilder::new(context.manager.clone(), image)
         .rm()
-        .args(&["cat", &source])
+        .args(&["cat", source])
         .build()
-        .status_output()?;
-    let mut target = std::fs::File::create(filename)?;
+        .status_output_timeout(timeouts().control)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut target = std::fs::File::create(path)?;
     target.write_all(&output)?;
     Ok(())
 }
 
 fn edit_file(context: &Context, filename: &str) {
-    let path = PathBuf::from(filename);
+    let template = format!(
+        "/usr/lib/suricata/python/suricata/update/configs/{}",
+        filename
+    );
+    edit_with_template(context, filename, &template, || validate(context));
+}
+
+/// Check the suricata-update configuration files by running an
+/// offline update into a throwaway output directory.
+fn validate(context: &Context) -> Result<()> {
+    RunCommandBuilder::new(
+        context.manager.clone(),
+        context.image_name(Container::Suricata),
+    )
+    .rm()
+    .volumes(&[format!("{}:/var/lib/suricata", crate::SURICATA_VOLUME_LIB)])
+    .volumes(&crate::actions::suricata_update_volumes())
+    .args(&[
+        "suricata-update",
+        "--offline",
+        "--no-test",
+        "--no-reload",
+        "--output",
+        "/tmp/simple-ids-check",
+    ])
+    .build()
+    .status_ok_timeout(timeouts().run)
+}
+
+/// Edit a file in the state directory, offering to start with a
+/// template copied from the Suricata image if it doesn't exist.
+pub(super) fn edit_with_template(
+    context: &Context,
+    filename: &str,
+    template: &str,
+    validate: impl Fn() -> Result<()>,
+) {
+    let path = state::file_path(filename);
     if !path.exists() {
         if let Ok(true) = inquire::Confirm::new(&format!(
             "Would you like to start with a {} template",
@@ -147,7 +295,7 @@ fn edit_file(context: &Context, filename: &str) {
         .with_default(true)
         .prompt()
         {
-            if let Err(err) = copy_suricata_update_template(context, filename) {
+            if let Err(err) = copy_template(context, template, &path) {
                 error!(
                     "Sorry, an error occurred copying the template for {}: {}",
                     filename, err
@@ -156,8 +304,8 @@ fn edit_file(context: &Context, filename: &str) {
             }
         }
     }
-    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "nano".into());
-    if let Err(err) = std::process::Command::new(&editor).arg(filename).status() {
-        error!("Failed to load {} in editor {}: {}", filename, editor, err);
+    if let Err(err) = editor::edit(&path, validate) {
+        error!("{err}");
+        prompt::enter();
     }
 }
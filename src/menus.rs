@@ -1,30 +1,52 @@
 // SPDX-FileCopyrightText: (C) 2021 Jason Ish <jason@codemonkey.net>
 // SPDX-License-Identifier: MIT
 
+use tracing::error;
+
 use crate::{
-    actions, add_index, context::Context, prompt, term, SelectItem, EVEBOX_CONTAINER_NAME,
-    SURICATA_CONTAINER_NAME,
+    actions, add_index, context::Context, pcaplog, prompt, term, wsl, SelectItem,
+    EVEBOX_CONTAINER_NAME, SURICATA_CONTAINER_NAME,
 };
 
 pub(crate) fn other(context: &Context) {
     loop {
         term::title("Simple-IDS: Other Menu Items");
 
-        let selections = vec![
+        let mut selections = vec![
             SelectItem::new("rotate", "Force Log Rotation"),
+            SelectItem::new("prune-pcaps", "Prune pcap-log Files"),
             SelectItem::new("suricata-shell", "Suricata Shell"),
             SelectItem::new("evebox-shell", "EveBox Shell"),
-            SelectItem::new("return", "Return"),
         ];
+        if wsl::is_wsl2() {
+            selections.push(SelectItem::new("wsl", "WSL2 Capture Guidance"));
+        }
+        selections.push(SelectItem::new("return", "Return"));
         let selections = add_index(&selections);
         match inquire::Select::new("Select menu option", selections).prompt() {
             Err(_) => return,
             Ok(selection) => match selection.tag.as_ref() {
                 "return" => return,
+                "wsl" => {
+                    wsl::print_guidance(wsl::networking_mode().as_deref());
+                    prompt::enter();
+                }
                 "rotate" => {
                     actions::force_suricata_logrotate(context);
                     prompt::enter();
                 }
+                "prune-pcaps" => {
+                    if let Ok(days) =
+                        inquire::CustomType::<u32>::new("Remove pcaps older than days")
+                            .with_default(7)
+                            .prompt()
+                    {
+                        if let Err(err) = pcaplog::prune(context, days) {
+                            error!("{err}");
+                        }
+                        prompt::enter();
+                    }
+                }
                 "suricata-shell" => {
                     let _ = context
                         .manager
@@ -35,7 +57,10 @@ pub(crate) fn other(context: &Context) {
                             "-e",
                             "PS1=[\\u@suricata \\W]\\$ ",
                             SURICATA_CONTAINER_NAME,
-                            "bash",
+                            "sh",
+                            "-c",
+                            // Prefer bash, but not all images have it.
+                            "command -v bash > /dev/null && exec bash || exec sh",
                         ])
                         .status();
                 }
@@ -0,0 +1,267 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Network interface discovery.
+
+use std::{collections::BTreeMap, ffi::CStr, net::Ipv4Addr};
+
+use anyhow::{bail, Result};
+use tracing::{debug, warn};
+
+use crate::container::{timeouts, CommandExt, Container, ContainerManager};
+use crate::context::Context;
+
+/// Lists the interfaces from inside a container, with `ip` if the image
+/// has it, otherwise from /sys/class/net without addresses.
+const CONTAINER_SCRIPT: &str = "\
+    if command -v ip > /dev/null; then exec ip -json addr show; fi; \
+    for path in /sys/class/net/*; do echo \"${path##*/} $(cat $path/operstate)\"; done";
+
+#[derive(Debug, Clone)]
+pub(crate) struct Interface {
+    pub name: String,
+    pub addr4: Vec<String>,
+    pub status: String,
+}
+
+/// Get the network interfaces Suricata can capture on.
+pub(crate) fn get_interfaces(context: &Context) -> Result<Vec<Interface>> {
+    lookup(
+        Some(&context.manager),
+        &context.image_name(Container::Suricata),
+    )
+}
+
+/// Get the network interfaces, optionally falling back to a container
+/// from the image.
+///
+/// The `ip` command is used if available. Otherwise, or if it fails, the
+/// interfaces are listed from a short-lived container with host
+/// networking, matching what Suricata will see. Finally getifaddrs and
+/// /sys/class/net are used, for minimal distributions without iproute2.
+/// With a remote container manager only the container sees the right
+/// host.
+pub(crate) fn lookup(manager: Option<&ContainerManager>, image: &str) -> Result<Vec<Interface>> {
+    if let Some(manager) = manager.filter(|manager| manager.host().is_some()) {
+        return container_interfaces(manager, image);
+    }
+    let err = match ip_addr() {
+        Ok(interfaces) => return Ok(interfaces),
+        Err(err) => err,
+    };
+    if let Some(manager) = manager {
+        warn!("Failed to get interfaces with the ip command, using a container: {err}");
+        match container_interfaces(manager, image) {
+            Ok(interfaces) => return Ok(interfaces),
+            Err(err) => warn!("Failed to get interfaces from a container, using getifaddrs: {err}"),
+        }
+    } else {
+        warn!("Failed to get interfaces with the ip command, using getifaddrs: {err}");
+    }
+    getifaddrs()
+}
+
+/// Get the interfaces from the JSON output of `ip addr`.
+fn ip_addr() -> Result<Vec<Interface>> {
+    let output = std::process::Command::new("ip")
+        .args(["-json", "addr", "show"])
+        .output()?;
+    if !output.status.success() {
+        bail!(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    parse_ip_json(&output.stdout)
+}
+
+/// Get the interfaces from a short-lived container with host networking.
+fn container_interfaces(manager: &ContainerManager, image: &str) -> Result<Vec<Interface>> {
+    let output = manager
+        .command()
+        .args([
+            "run",
+            "--rm",
+            "--net=host",
+            "--entrypoint",
+            "sh",
+            image,
+            "-c",
+            CONTAINER_SCRIPT,
+        ])
+        .output_timeout(timeouts().control)?;
+    if !output.status.success() {
+        bail!(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    if output
+        .stdout
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .is_some_and(|b| *b == b'[')
+    {
+        return parse_ip_json(&output.stdout);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (name, status) = line.split_once(' ')?;
+            Some(Interface {
+                name: name.to_string(),
+                addr4: vec![],
+                status: status.trim().to_uppercase(),
+            })
+        })
+        .collect())
+}
+
+fn parse_ip_json(buf: &[u8]) -> Result<Vec<Interface>> {
+    let links: Vec<serde_json::Value> = serde_json::from_slice(buf)?;
+    Ok(links
+        .iter()
+        .filter_map(|link| {
+            let name = link["ifname"].as_str()?.to_string();
+            let addr4 = link["addr_info"]
+                .as_array()
+                .map(|addrs| {
+                    addrs
+                        .iter()
+                        .filter(|addr| addr["family"] == "inet")
+                        .filter_map(|addr| addr["local"].as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(Interface {
+                name,
+                addr4,
+                status: link["operstate"].as_str().unwrap_or("UNKNOWN").to_string(),
+            })
+        })
+        .collect())
+}
+
+fn getifaddrs() -> Result<Vec<Interface>> {
+    let mut interfaces: BTreeMap<String, Interface> = BTreeMap::new();
+    let mut ifap: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut ifap) } != 0 {
+        bail!("getifaddrs failed: {}", std::io::Error::last_os_error());
+    }
+    let mut ifa = ifap;
+    while !ifa.is_null() {
+        let entry = unsafe { &*ifa };
+        let name = unsafe { CStr::from_ptr(entry.ifa_name) }
+            .to_string_lossy()
+            .to_string();
+        let interface = interfaces.entry(name.clone()).or_insert_with(|| Interface {
+            status: operstate(&name),
+            name,
+            addr4: vec![],
+        });
+        if !entry.ifa_addr.is_null()
+            && unsafe { (*entry.ifa_addr).sa_family } as i32 == libc::AF_INET
+        {
+            let addr = unsafe { &*(entry.ifa_addr as *const libc::sockaddr_in) };
+            let addr = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+            interface.addr4.push(addr.to_string());
+        }
+        ifa = entry.ifa_next;
+    }
+    unsafe { libc::freeifaddrs(ifap) };
+    Ok(interfaces.into_values().collect())
+}
+
+/// Prefixes of interfaces created by container engines, hypervisors
+/// and overlay networks, which are never the address to advertise.
+const VIRTUAL_PREFIXES: &[&str] = &[
+    "docker", "br-", "veth", "virbr", "cni", "podman", "flannel", "cali", "weave", "lxc", "lxd",
+    "kube", "vxlan", "tun", "tap", "wg",
+];
+
+/// Whether an interface is virtual or a bridge, judged by its name and
+/// for local interfaces by /sys/class/net.
+pub(crate) fn is_virtual(name: &str, local: bool) -> bool {
+    VIRTUAL_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+        || (local && std::path::Path::new(&format!("/sys/class/net/{name}/bridge")).exists())
+}
+
+/// The interface carrying the IPv4 default route of this host, with
+/// the lowest metric if there are several.
+pub(crate) fn default_route_interface() -> Option<String> {
+    let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+    parse_default_route(&routes)
+}
+
+fn parse_default_route(routes: &str) -> Option<String> {
+    routes
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // Iface Destination Gateway Flags RefCnt Use Metric Mask ...
+            if fields.len() < 8 || fields[1] != "00000000" || fields[7] != "00000000" {
+                return None;
+            }
+            let metric = fields[6].parse::<u32>().unwrap_or(u32::MAX);
+            Some((metric, fields[0].to_string()))
+        })
+        .min()
+        .map(|(_, name)| name)
+}
+
+/// The interface status in the format used by `ip`, for example "UP".
+fn operstate(name: &str) -> String {
+    match std::fs::read_to_string(format!("/sys/class/net/{name}/operstate")) {
+        Ok(state) => state.trim().to_uppercase(),
+        Err(err) => {
+            debug!("Failed to read operstate for {name}: {err}");
+            "UNKNOWN".to_string()
+        }
+    }
+}
+
+/// Offloads that merge or split packets, so Suricata sees different
+/// packets than were on the wire. Short names as used by `ethtool -K`.
+pub(crate) const OFFLOADS: &[(&str, &str)] = &[
+    ("gro", "generic-receive-offload"),
+    ("lro", "large-receive-offload"),
+    ("tso", "tcp-segmentation-offload"),
+    ("gso", "generic-segmentation-offload"),
+];
+
+/// Return the short names of the offloads enabled on an interface,
+/// using `ethtool -k`.
+pub(crate) fn enabled_offloads(name: &str) -> Result<Vec<&'static str>> {
+    let output = std::process::Command::new("ethtool")
+        .args(["-k", name])
+        .output()?;
+    if !output.status.success() {
+        bail!(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(OFFLOADS
+        .iter()
+        .filter(|(_, long)| {
+            stdout.lines().any(|line| {
+                line.trim()
+                    .strip_prefix(long)
+                    .and_then(|rest| rest.strip_prefix(':'))
+                    .map(|value| value.trim().starts_with("on"))
+                    .unwrap_or(false)
+            })
+        })
+        .map(|(short, _)| *short)
+        .collect())
+}
+
+/// Disable offloads on an interface with `ethtool -K`. This does not
+/// persist across reboots.
+pub(crate) fn disable_offloads(name: &str, offloads: &[&str]) -> Result<()> {
+    let mut command = std::process::Command::new("ethtool");
+    command.args(["-K", name]);
+    for offload in offloads {
+        command.args([offload, "off"]);
+    }
+    let output = command.output()?;
+    if !output.status.success() {
+        bail!(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
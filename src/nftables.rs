@@ -0,0 +1,188 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! nftables rules to divert forwarded traffic to NFQUEUE for inline
+//! (IPS) mode.
+
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Result};
+use tracing::{info, warn};
+
+const TABLE: &str = "simple_ids";
+const PERSIST_DIR: &str = "/etc/nftables.d";
+const PERSIST_FILENAME: &str = "/etc/nftables.d/simple-ids.nft";
+const NFTABLES_CONF: &str = "/etc/nftables.conf";
+
+/// Render the ruleset queueing traffic forwarded between the LAN and
+/// WAN interfaces. The bypass flag lets traffic through if Suricata
+/// isn't running, rather than cutting off the network.
+pub(crate) fn ruleset(lan: &str, wan: &str, queue: u16) -> String {
+    format!(
+        r#"table inet {TABLE} {{
+    chain forward {{
+        type filter hook forward priority 0; policy accept;
+        iifname "{lan}" oifname "{wan}" queue num {queue} bypass
+        iifname "{wan}" oifname "{lan}" queue num {queue} bypass
+    }}
+}}
+"#
+    )
+}
+
+/// The equivalent iptables commands, for hosts without nftables.
+pub(crate) fn iptables_rules(lan: &str, wan: &str, queue: u16) -> String {
+    format!(
+        "iptables -I FORWARD -i {lan} -o {wan} -j NFQUEUE --queue-num {queue} --queue-bypass\n\
+         iptables -I FORWARD -i {wan} -o {lan} -j NFQUEUE --queue-num {queue} --queue-bypass\n"
+    )
+}
+
+/// Whether the nft command is available.
+pub(crate) fn available() -> bool {
+    Command::new("nft")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+fn nft(args: &[&str], stdin: Option<&str>) -> Result<String> {
+    let mut child = Command::new("nft")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| anyhow::anyhow!("Failed to run nft: {err}"))?;
+    if let Some(input) = stdin {
+        if let Some(mut pipe) = child.stdin.take() {
+            pipe.write_all(input.as_bytes())?;
+        }
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn table_exists() -> bool {
+    nft(&["list", "table", "inet", TABLE], None).is_ok()
+}
+
+/// Load the divert rules, replacing any previously loaded.
+pub(crate) fn enable(lan: &str, wan: &str, queue: u16, persist: bool) -> Result<()> {
+    let ruleset = ruleset(lan, wan, queue);
+    if table_exists() {
+        nft(&["delete", "table", "inet", TABLE], None)?;
+    }
+    nft(&["-f", "-"], Some(&ruleset))?;
+    info!("Loaded nftables divert rules for {lan} <-> {wan} to queue {queue}");
+
+    if !ip_forwarding_enabled() {
+        warn!("IPv4 forwarding is disabled, traffic will not be forwarded between interfaces");
+    }
+
+    if persist {
+        std::fs::create_dir_all(PERSIST_DIR)?;
+        std::fs::write(PERSIST_FILENAME, &ruleset)?;
+        add_include()?;
+        info!("Saved divert rules to {PERSIST_FILENAME}");
+    }
+    Ok(())
+}
+
+/// Remove the divert rules, and the persisted copy if any.
+pub(crate) fn disable() -> Result<()> {
+    if table_exists() {
+        nft(&["delete", "table", "inet", TABLE], None)?;
+        info!("Removed nftables divert rules");
+    } else {
+        info!("nftables divert rules are not loaded");
+    }
+    if Path::new(PERSIST_FILENAME).exists() {
+        std::fs::remove_file(PERSIST_FILENAME)?;
+        info!("Removed {PERSIST_FILENAME}");
+    }
+    remove_include()?;
+    Ok(())
+}
+
+/// Print the state of the divert rules.
+pub(crate) fn status() -> Result<bool> {
+    let mut ok = true;
+    match nft(&["list", "table", "inet", TABLE], None) {
+        Ok(table) => {
+            info!("Divert rules are loaded:");
+            print!("{table}");
+        }
+        Err(_) => {
+            warn!("Divert rules are not loaded");
+            ok = false;
+        }
+    }
+    if Path::new(PERSIST_FILENAME).exists() {
+        info!("Divert rules are persisted in {PERSIST_FILENAME}");
+    } else {
+        info!("Divert rules are not persisted");
+    }
+    if !ip_forwarding_enabled() {
+        warn!("IPv4 forwarding is disabled");
+        ok = false;
+    }
+    Ok(ok)
+}
+
+fn ip_forwarding_enabled() -> bool {
+    std::fs::read_to_string("/proc/sys/net/ipv4/ip_forward")
+        .map(|value| value.trim() == "1")
+        .unwrap_or(false)
+}
+
+fn include_line() -> String {
+    format!("include \"{PERSIST_FILENAME}\"")
+}
+
+/// Include the persisted rules from the main nftables configuration
+/// so they are loaded on boot by the nftables service.
+fn add_include() -> Result<()> {
+    let buf = match std::fs::read_to_string(NFTABLES_CONF) {
+        Ok(buf) => buf,
+        Err(_) => {
+            warn!("{NFTABLES_CONF} not found, add {} to your nftables configuration to load the rules on boot", include_line());
+            return Ok(());
+        }
+    };
+    if buf.lines().any(|line| line.trim() == include_line()) {
+        return Ok(());
+    }
+    let mut buf = buf;
+    if !buf.ends_with('\n') {
+        buf.push('\n');
+    }
+    buf.push_str(&include_line());
+    buf.push('\n');
+    std::fs::write(NFTABLES_CONF, buf)?;
+    Ok(())
+}
+
+fn remove_include() -> Result<()> {
+    if let Ok(buf) = std::fs::read_to_string(NFTABLES_CONF) {
+        if buf.lines().any(|line| line.trim() == include_line()) {
+            let buf: String = buf
+                .lines()
+                .filter(|line| line.trim() != include_line())
+                .map(|line| format!("{line}\n"))
+                .collect();
+            std::fs::write(NFTABLES_CONF, buf)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,105 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Persist the capture NIC setup, such as disabled offloads, with a
+//! systemd unit run at boot as `ethtool -K` settings are lost on reboot.
+
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use tracing::info;
+
+use crate::{config::Config, netif};
+
+const UNIT_NAME: &str = "simple-ids-nic.service";
+const UNIT_PATH: &str = "/etc/systemd/system/simple-ids-nic.service";
+
+/// Render the unit disabling the offloads on each interface.
+pub(crate) fn render(interfaces: &[String]) -> String {
+    let offloads: Vec<String> = netif::OFFLOADS
+        .iter()
+        .map(|(short, _)| format!("{short} off"))
+        .collect();
+    let mut unit = String::from(
+        "# Installed by Simple-IDS to set up the capture interfaces.\n\
+         [Unit]\n\
+         Description=Simple-IDS capture interface setup\n\
+         After=network.target\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         RemainAfterExit=yes\n",
+    );
+    for interface in interfaces {
+        unit.push_str(&format!(
+            "ExecStart=-/usr/sbin/ethtool -K {} {}\n",
+            interface,
+            offloads.join(" ")
+        ));
+    }
+    unit.push_str("\n[Install]\nWantedBy=multi-user.target\n");
+    unit
+}
+
+/// Install and enable the unit for the configured interfaces.
+pub(crate) fn install(config: &Config) -> Result<()> {
+    if !Path::new("/run/systemd/system").exists() {
+        bail!("systemd is not running, the offloads must be disabled at boot by other means");
+    }
+    let interfaces = config.suricata.capture_interfaces();
+    if interfaces.is_empty() {
+        bail!("No capture interfaces configured");
+    }
+    std::fs::write(UNIT_PATH, render(&interfaces))?;
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", UNIT_NAME])?;
+    info!("Installed {UNIT_PATH}");
+    Ok(())
+}
+
+pub(crate) fn systemctl(args: &[&str]) -> Result<()> {
+    let output = std::process::Command::new("systemctl")
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        bail!(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
+
+/// Return true if the unit has been installed.
+pub(crate) fn installed() -> bool {
+    Path::new(UNIT_PATH).exists()
+}
+
+/// Check the capture interface setup, returning a description of each
+/// problem found.
+pub(crate) fn verify(config: &Config) -> Vec<String> {
+    let mut problems = vec![];
+    let interfaces = config.suricata.capture_interfaces();
+    let installed = std::fs::read_to_string(UNIT_PATH).ok();
+    if let Some(installed) = &installed {
+        if *installed != render(&interfaces) {
+            problems.push(format!(
+                "{UNIT_PATH} does not match the configured interfaces, reinstall it"
+            ));
+        }
+    }
+    for interface in &interfaces {
+        match netif::enabled_offloads(interface) {
+            Ok(offloads) if !offloads.is_empty() => {
+                let hint = if installed.is_some() {
+                    ""
+                } else {
+                    ", not persisted with a systemd unit"
+                };
+                problems.push(format!(
+                    "{interface}: offloads {} are enabled{hint}",
+                    offloads.join(", ")
+                ));
+            }
+            _ => {}
+        }
+    }
+    problems
+}
@@ -0,0 +1,342 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Push notifications through ntfy and Gotify.
+
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::{
+    config::{AlertNotifications, NotificationsConfig, PushChannel},
+    context::Context,
+    eve::{self, EVE_FILENAME},
+    geoip::GeoIp,
+    secrets::Secrets,
+};
+
+const DEFAULT_DEDUP_MINUTES: u32 = 10;
+const DEFAULT_BURST: u32 = 5;
+
+/// The window the burst of alerts is counted over.
+const BURST_WINDOW: Duration = Duration::from_secs(60);
+
+/// How urgent a notification is, mapped to the priority of each
+/// service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Priority {
+    Default,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Service {
+    Ntfy,
+    Gotify,
+}
+
+impl Service {
+    fn name(&self) -> &'static str {
+        match self {
+            Service::Ntfy => "ntfy",
+            Service::Gotify => "Gotify",
+        }
+    }
+
+    fn send(
+        &self,
+        channel: &PushChannel,
+        title: &str,
+        message: &str,
+        priority: Priority,
+    ) -> Result<()> {
+        match self {
+            Service::Ntfy => ntfy(channel, title, message, priority),
+            Service::Gotify => gotify(channel, title, message, priority),
+        }
+    }
+}
+
+fn channels(config: &NotificationsConfig) -> Vec<(Service, &PushChannel)> {
+    let mut channels = vec![];
+    if let Some(channel) = &config.ntfy {
+        channels.push((Service::Ntfy, channel));
+    }
+    if let Some(channel) = &config.gotify {
+        channels.push((Service::Gotify, channel));
+    }
+    channels
+}
+
+pub(crate) fn is_configured(config: &NotificationsConfig) -> bool {
+    !channels(config).is_empty()
+}
+
+/// Send a notification to every configured channel, failing if any of
+/// them failed.
+pub(crate) fn send(
+    config: &NotificationsConfig,
+    title: &str,
+    message: &str,
+    priority: Priority,
+) -> Result<()> {
+    let mut failed = vec![];
+    for (service, channel) in channels(config) {
+        match service.send(channel, title, message, priority) {
+            Ok(()) => info!("Notification sent to {}", service.name()),
+            Err(err) => failed.push(format!("{}: {err}", service.name())),
+        }
+    }
+    if !failed.is_empty() {
+        bail!(failed.join(", "));
+    }
+    Ok(())
+}
+
+/// Notification settings reloaded from a changed configuration file,
+/// used by the background tasks instead of the settings they were
+/// started with.
+static RELOADED: RwLock<Option<NotificationsConfig>> = RwLock::new(None);
+
+/// Use these notification settings for the background tasks from now
+/// on.
+pub(crate) fn reload(config: &NotificationsConfig) {
+    *RELOADED.write().unwrap_or_else(|err| err.into_inner()) = Some(config.clone());
+}
+
+/// Send a notification from a background task, where a failure is only
+/// worth a warning.
+pub(crate) fn send_or_warn(
+    config: &NotificationsConfig,
+    title: &str,
+    message: &str,
+    priority: Priority,
+) {
+    let reloaded = RELOADED
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone();
+    let config = reloaded.as_ref().unwrap_or(config);
+    if is_configured(config) {
+        if let Err(err) = send(config, title, message, priority) {
+            warn!("Failed to send notification: {err}");
+        }
+    }
+}
+
+/// Rate limiting of the alerts pushed to a channel, so a port scan
+/// doesn't turn into thousands of notifications. An alert with the
+/// same signature ID and source address is only pushed once per dedup
+/// window, and at most a burst of alerts is pushed per minute.
+struct RateLimiter {
+    dedup: Duration,
+    burst: u32,
+    last_sent: HashMap<(u64, String), Instant>,
+    window_start: Instant,
+    window_count: u32,
+    /// Alerts suppressed since the last one pushed.
+    suppressed: u64,
+}
+
+impl RateLimiter {
+    fn new(config: &AlertNotifications) -> Self {
+        let minutes = config.dedup_minutes.unwrap_or(DEFAULT_DEDUP_MINUTES);
+        Self {
+            dedup: Duration::from_secs(u64::from(minutes) * 60),
+            burst: config.burst.unwrap_or(DEFAULT_BURST),
+            last_sent: HashMap::new(),
+            window_start: Instant::now(),
+            window_count: 0,
+            suppressed: 0,
+        }
+    }
+
+    /// Return true if the alert is to be pushed.
+    fn allow(&mut self, sid: u64, src: &str, now: Instant) -> bool {
+        let key = (sid, src.to_string());
+        if let Some(last) = self.last_sent.get(&key) {
+            if now.duration_since(*last) < self.dedup {
+                self.suppressed += 1;
+                return false;
+            }
+        }
+        if now.duration_since(self.window_start) >= BURST_WINDOW {
+            self.window_start = now;
+            self.window_count = 0;
+        }
+        if self.window_count >= self.burst {
+            self.suppressed += 1;
+            return false;
+        }
+        self.window_count += 1;
+        let dedup = self.dedup;
+        self.last_sent
+            .retain(|_, last| now.duration_since(*last) < dedup);
+        self.last_sent.insert(key, now);
+        true
+    }
+}
+
+/// Follow eve.json, pushing new alerts to the channels with alert
+/// notifications enabled until Suricata stops.
+pub(crate) fn alerts(context: &Context) -> Result<()> {
+    let mut channels: Vec<(Service, &PushChannel, &AlertNotifications, RateLimiter)> =
+        channels(&context.config.notifications)
+            .into_iter()
+            .filter_map(|(service, channel)| {
+                let alerts = channel.alerts.as_ref()?;
+                Some((service, channel, alerts, RateLimiter::new(alerts)))
+            })
+            .collect();
+    if channels.is_empty() {
+        bail!("No notification channels have alerts enabled");
+    }
+    let geoip = GeoIp::open(&context.config.geoip);
+    info!("Pushing new alerts, press Ctrl-C to stop");
+    let status = eve::exec_lines(context, &["tail", "-n", "0", "-F", EVE_FILENAME], |line| {
+        let event: Value = match serde_json::from_str(line) {
+            Ok(event) => event,
+            Err(_) => return,
+        };
+        if event["event_type"].as_str() != Some("alert") {
+            return;
+        }
+        let sid = event["alert"]["signature_id"].as_u64().unwrap_or(0);
+        let severity = event["alert"]["severity"].as_u64().unwrap_or(3);
+        let src = event["src_ip"].as_str().unwrap_or("");
+        let title = event["alert"]["signature"].as_str().unwrap_or("Alert");
+        let now = Instant::now();
+        for (service, channel, config, limiter) in channels.iter_mut() {
+            if let Some(max) = config.max_severity {
+                if severity > u64::from(max) {
+                    continue;
+                }
+            }
+            if !limiter.allow(sid, src, now) {
+                continue;
+            }
+            let mut message = eve::format_event(&event, geoip.as_ref());
+            let suppressed = std::mem::take(&mut limiter.suppressed);
+            if suppressed > 0 {
+                message.push_str(&format!(
+                    "\n{suppressed} alert(s) suppressed since the last notification"
+                ));
+            }
+            let priority = if severity == 1 {
+                Priority::High
+            } else {
+                Priority::Default
+            };
+            if let Err(err) = service.send(channel, title, &message, priority) {
+                warn!("Failed to push alert to {}: {err}", service.name());
+            }
+        }
+    })?;
+    if !status.success() {
+        bail!("Failed to read {EVE_FILENAME}");
+    }
+    Ok(())
+}
+
+fn token(channel: &PushChannel) -> Result<Option<String>> {
+    match &channel.token {
+        Some(token) => Ok(Some(Secrets::load()?.resolve(token)?)),
+        None => Ok(None),
+    }
+}
+
+fn ntfy(channel: &PushChannel, title: &str, message: &str, priority: Priority) -> Result<()> {
+    let mut request = reqwest::blocking::Client::new()
+        .post(&channel.url)
+        .header("Title", title)
+        .header(
+            "Priority",
+            match priority {
+                Priority::Default => "default",
+                Priority::High => "high",
+            },
+        )
+        .header("Tags", "shield")
+        .body(message.to_string());
+    if let Some(token) = token(channel)? {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send()?;
+    if !response.status().is_success() {
+        bail!("HTTP status code {}", response.status());
+    }
+    Ok(())
+}
+
+fn gotify(channel: &PushChannel, title: &str, message: &str, priority: Priority) -> Result<()> {
+    let token = match token(channel)? {
+        Some(token) => token,
+        None => bail!("an application token is required"),
+    };
+    let payload = serde_json::json!({
+        "title": title,
+        "message": message,
+        "priority": match priority {
+            Priority::Default => 5,
+            Priority::High => 8,
+        },
+    });
+    let response = reqwest::blocking::Client::new()
+        .post(format!("{}/message", channel.url.trim_end_matches('/')))
+        .header("X-Gotify-Key", token)
+        .header("Content-Type", "application/json")
+        .body(payload.to_string())
+        .send()?;
+    if !response.status().is_success() {
+        bail!("HTTP status code {}", response.status());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(dedup_minutes: u32, burst: u32) -> RateLimiter {
+        RateLimiter::new(&AlertNotifications {
+            dedup_minutes: Some(dedup_minutes),
+            burst: Some(burst),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_dedup_expiry() {
+        let mut limiter = limiter(10, 100);
+        let now = Instant::now();
+        assert!(limiter.allow(1, "10.0.0.1", now));
+        assert!(!limiter.allow(1, "10.0.0.1", now + Duration::from_secs(60)));
+        // A different signature or source isn't a duplicate.
+        assert!(limiter.allow(2, "10.0.0.1", now + Duration::from_secs(60)));
+        assert!(limiter.allow(1, "10.0.0.2", now + Duration::from_secs(60)));
+        assert!(!limiter.allow(1, "10.0.0.1", now + Duration::from_secs(599)));
+        assert!(limiter.allow(1, "10.0.0.1", now + Duration::from_secs(600)));
+        assert_eq!(limiter.suppressed, 2);
+    }
+
+    #[test]
+    fn test_burst_cap() {
+        let mut limiter = limiter(10, 5);
+        let now = Instant::now();
+        for sid in 0..5 {
+            assert!(limiter.allow(sid, "10.0.0.1", now));
+        }
+        assert!(!limiter.allow(5, "10.0.0.1", now + Duration::from_secs(30)));
+        assert!(!limiter.allow(6, "10.0.0.1", now + Duration::from_secs(59)));
+        assert_eq!(limiter.suppressed, 2);
+
+        // The cap resets once the minute is up.
+        assert!(limiter.allow(5, "10.0.0.1", now + Duration::from_secs(60)));
+    }
+}
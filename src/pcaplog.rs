@@ -0,0 +1,95 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Full packet capture with Suricata's pcap-log output, written to a
+//! dedicated volume as a ring buffer of files.
+
+use anyhow::{bail, Result};
+use tracing::info;
+
+use crate::{
+    config::PcapLogConfig,
+    container::{timeouts, CommandExt, SuricataContainer},
+    context::Context,
+    SURICATA_VOLUME_PCAP,
+};
+
+/// Where the pcap volume is mounted in the Suricata container.
+pub(crate) const PCAP_DIR: &str = "/var/log/suricata/pcap";
+
+/// Size of each pcap file, unless configured.
+pub(crate) const DEFAULT_LIMIT: &str = "100mb";
+
+/// Number of pcap files kept, unless configured.
+pub(crate) const DEFAULT_MAX_FILES: u32 = 20;
+
+pub(crate) fn volume() -> String {
+    format!("{SURICATA_VOLUME_PCAP}:{PCAP_DIR}")
+}
+
+/// The `--set` arguments enabling the pcap-log output found in the
+/// `--dump-config` output.
+pub(crate) fn set_args(config: &PcapLogConfig, dump_config: &[String]) -> Vec<String> {
+    if !config.enabled {
+        return vec![];
+    }
+    let re = regex::Regex::new(r"^(outputs\.\d+\.pcap-log)[\s.]").unwrap();
+    let mut prefixes: Vec<&str> = dump_config
+        .iter()
+        .filter_map(|line| re.captures(line).and_then(|c| c.get(1)))
+        .map(|m| m.as_str())
+        .collect();
+    prefixes.dedup();
+    let mut set_args = vec![];
+    for prefix in prefixes {
+        set_args.extend([
+            format!("{prefix}.enabled=yes"),
+            format!("{prefix}.mode=normal"),
+            format!("{prefix}.dir={PCAP_DIR}"),
+            format!(
+                "{prefix}.limit={}",
+                config.limit.as_deref().unwrap_or(DEFAULT_LIMIT)
+            ),
+            format!(
+                "{prefix}.max-files={}",
+                config.max_files.unwrap_or(DEFAULT_MAX_FILES)
+            ),
+        ]);
+    }
+    set_args
+}
+
+/// Remove the pcap files older than the given number of days.
+pub(crate) fn prune(context: &Context, days: u32) -> Result<()> {
+    let minutes = match days.checked_mul(24 * 60) {
+        Some(minutes) => minutes,
+        None => bail!("Too many days: {days}"),
+    };
+    let output = SuricataContainer::new(context.clone())
+        .run()
+        .rm()
+        .volumes(&[volume()])
+        .args(&[
+            "find",
+            PCAP_DIR,
+            "-type",
+            "f",
+            "-name",
+            "log.pcap*",
+            "-mmin",
+            &format!("+{minutes}"),
+            "-print",
+            "-delete",
+        ])
+        .build()
+        .output_timeout(timeouts().control)?;
+    if !output.status.success() {
+        bail!(
+            "Failed to prune the pcaps: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let removed = String::from_utf8_lossy(&output.stdout).lines().count();
+    info!("Removed {removed} pcap file(s) older than {days} day(s)");
+    Ok(())
+}
@@ -0,0 +1,192 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! A small certificate authority for the EveBox server certificate, and
+//! for mutual TLS between the reverse proxies in front of sensors
+//! forwarding events and the EveBox server receiving them, issuing
+//! certificates with the openssl command.
+
+use std::{
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, Context as _, Result};
+use tracing::info;
+
+use crate::state;
+
+/// Directory in the state directory holding the CA and certificates.
+const DIR: &str = "pki";
+
+const CA_CERT: &str = "ca.pem";
+const CA_KEY: &str = "ca-key.pem";
+
+/// Validity of the CA and the certificates it issues, in days.
+const CA_DAYS: u32 = 3650;
+const CERT_DAYS: u32 = 825;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Usage {
+    Server,
+    Client,
+}
+
+/// An issued certificate and its key, with the CA that signed it.
+#[derive(Debug)]
+pub(crate) struct Issued {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    pub ca: PathBuf,
+}
+
+fn dir() -> PathBuf {
+    state::file_path(DIR)
+}
+
+fn openssl(args: &[&str]) -> Result<()> {
+    let output = Command::new("openssl")
+        .args(args)
+        .output()
+        .context("Failed to run openssl, is it installed?")?;
+    if !output.status.success() {
+        bail!(
+            "openssl {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Keys are only readable by their owner.
+fn restrict(path: &Path) -> Result<()> {
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(())
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .with_context(|| format!("Invalid path {}", path.display()))
+}
+
+/// Create the CA if it doesn't exist yet, returning the path to its
+/// certificate.
+pub(crate) fn init_ca() -> Result<PathBuf> {
+    let dir = dir();
+    let cert = dir.join(CA_CERT);
+    let key = dir.join(CA_KEY);
+    if cert.exists() && key.exists() {
+        return Ok(cert);
+    }
+    std::fs::create_dir_all(&dir)?;
+    openssl(&[
+        "req",
+        "-x509",
+        "-newkey",
+        "ec",
+        "-pkeyopt",
+        "ec_paramgen_curve:prime256v1",
+        "-nodes",
+        "-keyout",
+        path_str(&key)?,
+        "-out",
+        path_str(&cert)?,
+        "-days",
+        &CA_DAYS.to_string(),
+        "-subj",
+        "/CN=Simple-IDS CA",
+        "-addext",
+        "basicConstraints=critical,CA:TRUE",
+        "-addext",
+        "keyUsage=critical,keyCertSign,cRLSign",
+    ])?;
+    restrict(&key)?;
+    info!("Created the certificate authority {}", cert.display());
+    Ok(cert)
+}
+
+/// Issue a certificate signed by the CA, creating the CA if needed.
+///
+/// Server certificates are valid for `names`, which may be host names
+/// or IP addresses. The first name is used as the common name.
+pub(crate) fn issue(usage: Usage, names: &[String]) -> Result<Issued> {
+    let Some(name) = names.first() else {
+        bail!("A name is required");
+    };
+    if name.contains('/') || name.starts_with('.') {
+        bail!("Invalid name {name}");
+    }
+    let ca = init_ca()?;
+    let ca_key = dir().join(CA_KEY);
+    let subdir = dir().join(match usage {
+        Usage::Server => "servers",
+        Usage::Client => "clients",
+    });
+    std::fs::create_dir_all(&subdir)?;
+    let cert = subdir.join(format!("{name}.pem"));
+    let key = subdir.join(format!("{name}-key.pem"));
+    let csr = subdir.join(format!("{name}.csr"));
+    let ext = subdir.join(format!("{name}.ext"));
+
+    let mut extensions = vec![
+        "basicConstraints=critical,CA:FALSE".to_string(),
+        "keyUsage=critical,digitalSignature".to_string(),
+    ];
+    match usage {
+        Usage::Server => {
+            extensions.push("extendedKeyUsage=serverAuth".to_string());
+            let alt_names: Vec<String> = names
+                .iter()
+                .map(|name| {
+                    if name.parse::<std::net::IpAddr>().is_ok() {
+                        format!("IP:{name}")
+                    } else {
+                        format!("DNS:{name}")
+                    }
+                })
+                .collect();
+            extensions.push(format!("subjectAltName={}", alt_names.join(",")));
+        }
+        Usage::Client => extensions.push("extendedKeyUsage=clientAuth".to_string()),
+    }
+    std::fs::write(&ext, extensions.join("\n") + "\n")?;
+
+    openssl(&[
+        "req",
+        "-newkey",
+        "ec",
+        "-pkeyopt",
+        "ec_paramgen_curve:prime256v1",
+        "-nodes",
+        "-keyout",
+        path_str(&key)?,
+        "-out",
+        path_str(&csr)?,
+        "-subj",
+        &format!("/CN={name}"),
+    ])?;
+    restrict(&key)?;
+    let result = openssl(&[
+        "x509",
+        "-req",
+        "-in",
+        path_str(&csr)?,
+        "-CA",
+        path_str(&ca)?,
+        "-CAkey",
+        path_str(&ca_key)?,
+        "-CAcreateserial",
+        "-out",
+        path_str(&cert)?,
+        "-days",
+        &CERT_DAYS.to_string(),
+        "-extfile",
+        path_str(&ext)?,
+    ]);
+    let _ = std::fs::remove_file(&csr);
+    let _ = std::fs::remove_file(&ext);
+    result?;
+    Ok(Issued { cert, key, ca })
+}
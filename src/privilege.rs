@@ -0,0 +1,190 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Delegation of container changing actions to a privileged copy of
+//! Simple-IDS through sudo or polkit (pkexec), so the menus can be run
+//! by an unprivileged user.
+//!
+//! Each action is a separate command line, so sudoers and polkit rules
+//! can allow them individually. The rules pin the directory the actions
+//! run in, and the privileged copy refuses a directory, configuration or
+//! state the invoking user could have written, as it controls the images
+//! and arguments of the containers run as root.
+
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Result};
+use tracing::{error, info};
+
+use crate::config::{Config, PrivilegeHelper};
+use crate::system;
+
+/// The actions that can be delegated, as command line arguments.
+pub(crate) const ACTIONS: &[&str] = &[
+    "start",
+    "stop",
+    "restart",
+    "pause",
+    "resume",
+    "update-rules --now",
+    "update --now",
+    "rotate-logs",
+];
+
+/// The helper to use if actions are to be delegated, only when not
+/// already running as root.
+pub(crate) fn helper(config: &Config) -> Option<PrivilegeHelper> {
+    if system::is_root() {
+        None
+    } else {
+        config.privileges.helper
+    }
+}
+
+/// Run an action in a privileged copy of Simple-IDS. Returns true if
+/// it was successful.
+pub(crate) fn run(helper: PrivilegeHelper, action: &str) -> bool {
+    match run_command(helper, action) {
+        Ok(true) => true,
+        Ok(false) => {
+            error!("Privileged {action} failed");
+            false
+        }
+        Err(err) => {
+            error!("Failed to run {action} with {helper}: {err}");
+            false
+        }
+    }
+}
+
+fn run_command(helper: PrivilegeHelper, action: &str) -> Result<bool> {
+    let exe = std::env::current_exe()?;
+    let mut command = match helper {
+        PrivilegeHelper::Sudo => {
+            let mut command = Command::new("sudo");
+            command.arg("--");
+            command
+        }
+        PrivilegeHelper::Pkexec => Command::new("pkexec"),
+    };
+    command
+        .arg(&exe)
+        .arg("--directory")
+        .arg(directory()?)
+        .args(action.split_whitespace());
+    info!("Running {action} with {helper}");
+    Ok(command.status()?.success())
+}
+
+/// The current directory, where the configuration is, as passed to the
+/// privileged copy. It must be usable unquoted in sudoers and polkit
+/// rules.
+fn directory() -> Result<String> {
+    let directory = std::env::current_dir()?;
+    let Some(directory) = directory.to_str() else {
+        bail!("Invalid directory {}", directory.display());
+    };
+    if !directory
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/._-".contains(c))
+    {
+        bail!("The directory {directory} can't be used in privilege rules, it may only contain letters, digits, '/', '.', '_' and '-'");
+    }
+    Ok(directory.to_string())
+}
+
+/// True if running as root on behalf of another user through sudo or
+/// pkexec.
+pub(crate) fn is_elevated() -> bool {
+    system::is_root()
+        && (std::env::var_os("SUDO_UID").is_some() || std::env::var_os("PKEXEC_UID").is_some())
+}
+
+/// Fail unless a path is owned by root and not writable by the group or
+/// others.
+fn check_owner(path: &Path) -> Result<()> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if metadata.uid() != 0 {
+        bail!("{} is not owned by root", path.display());
+    }
+    if !metadata.file_type().is_symlink() && metadata.mode() & 0o022 != 0 {
+        bail!("{} is writable by users other than root", path.display());
+    }
+    Ok(())
+}
+
+/// Check that the directory the privileged copy runs in, and the
+/// Simple-IDS files in it, can only have been written by root. Only
+/// checked for the privileged copy, an administrator running
+/// Simple-IDS with sudo directly is trusted with their own directory.
+pub(crate) fn check_directory(directory: &Path) -> Result<()> {
+    check_owner(directory)?;
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with("simple-ids")
+        {
+            check_owner(&entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// A sudoers snippet allowing the members of a group to run each
+/// action in the current directory.
+pub(crate) fn sudoers(group: &str) -> Result<String> {
+    let exe = std::env::current_exe()?;
+    let exe = exe.display();
+    let directory = directory()?;
+    check_directory(Path::new(&directory))?;
+    let mut sudoers = format!(
+        "# Allow members of {group} to control Simple-IDS, install as\n\
+         # /etc/sudoers.d/simple-ids and remove actions as needed.\n"
+    );
+    for action in ACTIONS {
+        sudoers.push_str(&format!(
+            "%{group} ALL=(root) {exe} --directory {directory} {action}\n"
+        ));
+    }
+    Ok(sudoers)
+}
+
+/// A polkit rule allowing the members of a group to run each action
+/// with pkexec in the current directory.
+pub(crate) fn polkit_rule(group: &str) -> Result<String> {
+    let exe = std::env::current_exe()?;
+    let exe = exe.display();
+    let directory = directory()?;
+    check_directory(Path::new(&directory))?;
+    let actions: Vec<String> = ACTIONS
+        .iter()
+        .map(|action| format!("        \"{action}\","))
+        .collect();
+    Ok(format!(
+        r#"// Allow members of {group} to control Simple-IDS, install as
+// /etc/polkit-1/rules.d/50-simple-ids.rules and remove actions as needed.
+polkit.addRule(function(action, subject) {{
+    var actions = [
+{actions}
+    ];
+    if (action.id != "org.freedesktop.policykit.exec" ||
+        action.lookup("program") != "{exe}" ||
+        !subject.isInGroup("{group}")) {{
+        return polkit.Result.NOT_HANDLED;
+    }}
+    var command = action.lookup("command_line");
+    for (var i = 0; i < actions.length; i++) {{
+        if (command == "{exe} --directory {directory} " + actions[i]) {{
+            return polkit.Result.AUTH_SELF_KEEP;
+        }}
+    }}
+    return polkit.Result.NOT_HANDLED;
+}});
+"#,
+        actions = actions.join("\n")
+    ))
+}
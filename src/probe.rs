@@ -0,0 +1,205 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Probe the EveBox HTTP(S) endpoint to check it is actually serving
+//! requests, as a running container doesn't mean the UI is up.
+
+use std::{
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{bail, Context as _, Result};
+use chrono::{DateTime, Utc};
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    Certificate, ClientConfig, ClientConnection, ServerName, StreamOwned,
+};
+
+use tracing::warn;
+
+use crate::{context::Context, display, state::State};
+
+pub(crate) const EVEBOX_PORT: u16 = 5636;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Warn about the EveBox certificate this many days before it expires.
+pub(crate) const CERT_WARN_DAYS: i64 = 30;
+
+#[derive(Debug)]
+pub(crate) struct Probe {
+    /// The HTTP status code of the response.
+    pub status: u16,
+    pub latency: Duration,
+    /// The expiry of the certificate presented, if TLS is in use.
+    pub cert_expires: Option<DateTime<Utc>>,
+}
+
+/// Probe EveBox on the host running the containers. Returns None if
+/// EveBox can't be reached from here, for example it only listens on
+/// localhost of a remote host.
+pub(crate) fn evebox(context: &Context) -> Option<Result<Probe>> {
+    if context.config.evebox.agent.is_some() {
+        return None;
+    }
+    let host = match context.manager.host() {
+        None => IpAddr::V4(Ipv4Addr::LOCALHOST).to_string(),
+        Some(_) if !context.config.evebox.allow_remote => return None,
+        Some(host) => hostname(host)?,
+    };
+    Some(probe(&host, EVEBOX_PORT, !context.config.evebox.no_tls))
+}
+
+/// Probe EveBox and record the expiry of its certificate in the state
+/// so it can be checked without probing again.
+pub(crate) fn record_evebox_cert(context: &Context) -> Option<Result<Probe>> {
+    let result = evebox(context)?;
+    if let Ok(probe) = &result {
+        let expires = probe.cert_expires;
+        if let Err(err) = State::update(|state| state.evebox_cert_expires = expires) {
+            warn!("Failed to save state: {err}");
+        }
+    }
+    Some(result)
+}
+
+/// A warning if the certificate has expired or expires soon.
+pub(crate) fn cert_warning(expires: Option<DateTime<Utc>>) -> Option<String> {
+    let expires = expires?;
+    let days = expires.signed_duration_since(Utc::now()).num_days();
+    if expires < Utc::now() {
+        Some(format!(
+            "EveBox TLS certificate expired {}",
+            display::format_minutes(&expires)
+        ))
+    } else if days < CERT_WARN_DAYS {
+        Some(format!(
+            "EveBox TLS certificate expires in {days} days ({})",
+            display::format_minutes(&expires)
+        ))
+    } else {
+        None
+    }
+}
+
+/// Extract the hostname from a container manager host, such as
+/// "ssh://user@host:22" or "tcp://host:2375".
+fn hostname(host: &str) -> Option<String> {
+    let host = host.split_once("://").map(|(_, rest)| rest).unwrap_or(host);
+    let host = host.rsplit_once('@').map(|(_, rest)| rest).unwrap_or(host);
+    let host = host.split('/').next()?;
+    let host = match host.strip_prefix('[') {
+        Some(v6) => v6.split(']').next()?,
+        None => host.split(':').next()?,
+    };
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_string())
+    }
+}
+
+pub(crate) fn probe(host: &str, port: u16, tls: bool) -> Result<Probe> {
+    let addr = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .with_context(|| format!("Failed to resolve {host}"))?;
+    let start = Instant::now();
+    let (response, cert_expires) = if tls {
+        let (response, cert) = request_tls(host, addr)?;
+        let expires = match cert {
+            Some(cert) => Some(certificate_expiry(&cert)?),
+            None => None,
+        };
+        (response, expires)
+    } else {
+        (request(connect(addr)?, host)?, None)
+    };
+    let latency = start.elapsed();
+    Ok(Probe {
+        status: parse_status(&response)?,
+        latency,
+        cert_expires,
+    })
+}
+
+fn connect(addr: SocketAddr) -> Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(&addr, TIMEOUT)
+        .with_context(|| format!("Failed to connect to {addr}"))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    Ok(stream)
+}
+
+fn request(mut stream: impl Read + Write, host: &str) -> Result<String> {
+    write!(
+        stream,
+        "GET / HTTP/1.0\r\nHost: {host}\r\nUser-Agent: simple-ids\r\n\r\n"
+    )?;
+    stream.flush()?;
+    // Only the status line is needed.
+    let mut buf = [0; 256];
+    let n = stream.read(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf[..n]).to_string())
+}
+
+/// Make the request over TLS, returning the certificate presented by
+/// the server. EveBox commonly uses a self-signed certificate, so it is
+/// not verified.
+fn request_tls(host: &str, addr: SocketAddr) -> Result<(String, Option<Vec<u8>>)> {
+    let verifier = Arc::new(CaptureVerifier::default());
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(verifier.clone())
+        .with_no_client_auth();
+    let server_name = match host.parse::<IpAddr>() {
+        Ok(ip) => ServerName::IpAddress(ip),
+        Err(_) => ServerName::try_from(host)?,
+    };
+    let connection = ClientConnection::new(Arc::new(config), server_name)?;
+    let stream = StreamOwned::new(connection, connect(addr)?);
+    let response = request(stream, host)?;
+    let cert = verifier.cert.lock().unwrap().take();
+    Ok((response, cert))
+}
+
+fn parse_status(response: &str) -> Result<u16> {
+    let line = response.lines().next().unwrap_or_default();
+    match line.split_whitespace().nth(1).map(str::parse) {
+        Some(Ok(status)) if line.starts_with("HTTP/") => Ok(status),
+        _ => bail!("Invalid HTTP response: {}", line),
+    }
+}
+
+/// Return the expiry (not after) time of a DER encoded certificate.
+pub(crate) fn certificate_expiry(der: &[u8]) -> Result<DateTime<Utc>> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der)
+        .map_err(|err| anyhow::anyhow!("Failed to parse certificate: {err}"))?;
+    let timestamp = cert.validity().not_after.timestamp();
+    DateTime::from_timestamp(timestamp, 0).context("Invalid certificate expiry")
+}
+
+/// A certificate "verifier" that accepts any certificate, keeping a
+/// copy of it.
+#[derive(Default)]
+struct CaptureVerifier {
+    cert: Mutex<Option<Vec<u8>>>,
+}
+
+impl ServerCertVerifier for CaptureVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        *self.cert.lock().unwrap() = Some(end_entity.0.clone());
+        Ok(ServerCertVerified::assertion())
+    }
+}
@@ -0,0 +1,77 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Run Suricata over pcap files, logging into the shared log volume so
+//! the alerts show up in EveBox next to the live traffic.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use tracing::info;
+
+use crate::{
+    container::{timeouts, CommandExt, SuricataContainer},
+    context::Context,
+};
+
+/// Where the pcaps are mounted in the container.
+pub(crate) const PCAP_DIR: &str = "/var/lib/simple-ids/pcaps";
+
+/// Volume arguments mounting the pcaps read-only into [`PCAP_DIR`],
+/// prefixed with their index so files with the same name don't collide.
+pub(crate) fn pcap_volumes(pcaps: &[PathBuf]) -> Result<Vec<String>> {
+    let mut volumes = vec![];
+    for (i, pcap) in pcaps.iter().enumerate() {
+        let path = match pcap.canonicalize() {
+            Ok(path) => path,
+            Err(err) => bail!("{}: {err}", pcap.display()),
+        };
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        volumes.push(format!("{}:{PCAP_DIR}/{i}-{filename}:ro", path.display()));
+    }
+    Ok(volumes)
+}
+
+/// Run Suricata over the pcaps with the live rules, appending to the
+/// logs of the running Suricata.
+pub(crate) fn readpcap(context: &Context, pcaps: &[PathBuf]) -> Result<()> {
+    let suricata = &context.config.suricata;
+    let mut volumes = pcap_volumes(pcaps)?;
+    let mut args = vec![];
+    if let Some(volume) = crate::suricata_config_volume(suricata)? {
+        volumes.push(volume);
+        args.extend([
+            "-c".to_string(),
+            crate::SURICATA_CONTAINER_CONFIG.to_string(),
+        ]);
+    }
+    args.extend([
+        "-k".to_string(),
+        "none".to_string(),
+        "-r".to_string(),
+        PCAP_DIR.to_string(),
+        "-l".to_string(),
+        "/var/log/suricata".to_string(),
+        // Leave the command socket to the live Suricata.
+        "--set".to_string(),
+        "unix-command.enabled=no".to_string(),
+    ]);
+    args.extend(crate::suricata_user_set_args(suricata));
+
+    info!("Reading {} pcap(s)", pcaps.len());
+    if let Err(err) = SuricataContainer::new(context.clone())
+        .run()
+        .rm()
+        .volumes(&volumes)
+        .args(&args)
+        .build()
+        .status_ok_timeout(timeouts().run)
+    {
+        bail!("Suricata failed to read the pcaps: {err}");
+    }
+    info!("Done, the events are in EveBox with the timestamps of the captured packets");
+    Ok(())
+}
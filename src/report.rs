@@ -0,0 +1,469 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+use std::{collections::HashMap, fmt::Write as _, io::Write as _, path::Path, process::Stdio};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::Value;
+use tracing::{error, info};
+
+use crate::{
+    config::{ReportSchedule, ReportsConfig},
+    context::Context,
+    display,
+    eve::{self, EVE_FILENAME},
+    geoip::GeoIp,
+    notify, status, system,
+};
+
+/// Report output formats.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// Plain text
+    Text,
+    /// A standalone HTML page with a chart of the alerts per hour
+    Html,
+    /// The HTML report converted to PDF with wkhtmltopdf or Chromium
+    Pdf,
+}
+
+const CRON_FILENAME: &str = "/etc/cron.d/simple-ids-report";
+
+const HTML_TEMPLATE: &str = include_str!("report.html");
+
+/// Counts of the alerts seen over the report period.
+#[derive(Default)]
+struct AlertSummary {
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    total: u64,
+    /// Alert counts by the hour since the start of the report.
+    hourly: Vec<u64>,
+    signatures: HashMap<String, u64>,
+    sources: HashMap<String, u64>,
+    destinations: HashMap<String, u64>,
+    countries: HashMap<String, u64>,
+    asns: HashMap<String, u64>,
+}
+
+impl AlertSummary {
+    fn add(&mut self, event: &Value, hour: usize, geoip: Option<&GeoIp>) {
+        self.total += 1;
+        if let Some(count) = self.hourly.get_mut(hour) {
+            *count += 1;
+        }
+        let signature = format!(
+            "[{}] {}",
+            event["alert"]["signature_id"],
+            event["alert"]["signature"].as_str().unwrap_or("")
+        );
+        *self.signatures.entry(signature).or_default() += 1;
+        for (key, counts) in [
+            ("src_ip", &mut self.sources),
+            ("dest_ip", &mut self.destinations),
+        ] {
+            if let Some(addr) = event[key].as_str() {
+                *counts.entry(addr.to_string()).or_default() += 1;
+            }
+        }
+
+        // Count the external side of the alert, or both sides if both
+        // are external.
+        if let Some(geoip) = geoip {
+            for key in ["src_ip", "dest_ip"] {
+                if let Some(info) = event[key].as_str().and_then(|a| geoip.lookup_str(a)) {
+                    if let Some(country) = &info.country {
+                        *self.countries.entry(country.clone()).or_default() += 1;
+                    }
+                    if let Some(asn) = info.asn_label() {
+                        *self.asns.entry(asn).or_default() += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The sections of the report, GeoIP sections only if GeoIP is
+    /// available.
+    fn sections(&self, geoip: bool) -> Vec<(&'static str, &HashMap<String, u64>)> {
+        let mut sections = vec![
+            ("Top signatures", &self.signatures),
+            ("Top sources", &self.sources),
+            ("Top destinations", &self.destinations),
+        ];
+        if geoip {
+            sections.push(("Top countries", &self.countries));
+            sections.push(("Top ASNs", &self.asns));
+        }
+        sections
+    }
+
+    fn period(&self) -> String {
+        format!(
+            "{} to {}",
+            display::format_minutes(&self.since),
+            display::format_minutes(&self.until)
+        )
+    }
+}
+
+/// Write a report of the alerts seen in the last `hours` hours, to
+/// `output` or stdout.
+pub(crate) fn report(
+    context: &Context,
+    hours: i64,
+    top: usize,
+    format: Format,
+    output: Option<&Path>,
+) -> Result<()> {
+    let report = match format {
+        Format::Text => render(context, hours, top)?,
+        Format::Html | Format::Pdf => {
+            let geoip = GeoIp::open(&context.config.geoip);
+            let summary = summarize(context, hours, geoip.as_ref())?;
+            render_html(&summary, geoip.is_some(), top)
+        }
+    };
+    match (format, output) {
+        (Format::Pdf, Some(output)) => html_to_pdf(&report, output)?,
+        (Format::Pdf, None) => bail!("PDF reports require an output file"),
+        (_, Some(output)) => std::fs::write(output, report)?,
+        (_, None) => print!("{report}"),
+    }
+    if let Some(output) = output {
+        info!("Report written to {}", output.display());
+    }
+    Ok(())
+}
+
+fn summarize(context: &Context, hours: i64, geoip: Option<&GeoIp>) -> Result<AlertSummary> {
+    let until = Utc::now();
+    let since = until - Duration::hours(hours);
+    let mut summary = AlertSummary {
+        since,
+        until,
+        hourly: vec![0; hours.max(1) as usize],
+        ..Default::default()
+    };
+
+    let status = eve::exec_lines(
+        context,
+        &["grep", "-F", "\"event_type\":\"alert\"", EVE_FILENAME],
+        |line| {
+            let event: Value = match serde_json::from_str(line) {
+                Ok(event) => event,
+                Err(_) => return,
+            };
+            let ts = match event["timestamp"].as_str().and_then(eve::parse_timestamp) {
+                Some(ts) => ts,
+                None => return,
+            };
+            if ts >= since {
+                let hour = (ts.with_timezone(&Utc) - since).num_hours() as usize;
+                summary.add(&event, hour, geoip);
+            }
+        },
+    )?;
+    if !status.success() && status.code() != Some(1) {
+        bail!("Failed to read {EVE_FILENAME}");
+    }
+    Ok(summary)
+}
+
+/// Summarize the alerts seen in the last `hours` hours as text.
+pub(crate) fn render(context: &Context, hours: i64, top: usize) -> Result<String> {
+    let geoip = GeoIp::open(&context.config.geoip);
+    let summary = summarize(context, hours, geoip.as_ref())?;
+
+    let mut out = String::new();
+    writeln!(out, "Alert summary from {}", summary.period())?;
+    writeln!(out, "Total alerts: {}", summary.total)?;
+    for (title, counts) in summary.sections(geoip.is_some()) {
+        write_top(&mut out, title, counts, top)?;
+    }
+    Ok(out)
+}
+
+fn sorted_top(counts: &HashMap<String, u64>, top: usize) -> Vec<(&String, &u64)> {
+    let mut counts: Vec<(&String, &u64)> = counts.iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    counts.truncate(top);
+    counts
+}
+
+fn write_top(
+    out: &mut String,
+    title: &str,
+    counts: &HashMap<String, u64>,
+    top: usize,
+) -> std::fmt::Result {
+    if counts.is_empty() {
+        return Ok(());
+    }
+    writeln!(out)?;
+    writeln!(out, "{title}:")?;
+    for (name, count) in sorted_top(counts, top) {
+        writeln!(out, "  {count:>8}  {name}")?;
+    }
+    Ok(())
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_html(summary: &AlertSummary, geoip: bool, top: usize) -> String {
+    let mut sections = String::new();
+    for (title, counts) in summary.sections(geoip) {
+        if counts.is_empty() {
+            continue;
+        }
+        let _ = writeln!(sections, "<h2>{title}</h2>\n<table>");
+        for (name, count) in sorted_top(counts, top) {
+            let _ = writeln!(
+                sections,
+                "<tr><td class=\"count\">{count}</td><td>{}</td></tr>",
+                escape_html(name)
+            );
+        }
+        let _ = writeln!(sections, "</table>");
+    }
+    HTML_TEMPLATE
+        .replace(
+            "{{title}}",
+            &escape_html(&format!("Alert Report: {}", hostname())),
+        )
+        .replace("{{period}}", &escape_html(&summary.period()))
+        .replace("{{total}}", &summary.total.to_string())
+        .replace("{{chart}}", &render_chart(summary))
+        .replace("{{sections}}", &sections)
+}
+
+/// An SVG bar chart of the alerts per hour.
+fn render_chart(summary: &AlertSummary) -> String {
+    const WIDTH: f64 = 720.0;
+    const HEIGHT: f64 = 160.0;
+    let max = summary.hourly.iter().copied().max().unwrap_or(0).max(1);
+    let bar = WIDTH / summary.hourly.len().max(1) as f64;
+    let mut svg = format!(
+        "<svg width=\"{WIDTH}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\">\n",
+        HEIGHT + 20.0
+    );
+    for (i, count) in summary.hourly.iter().enumerate() {
+        let height = *count as f64 * HEIGHT / max as f64;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\"><title>{count}</title></rect>",
+            i as f64 * bar,
+            HEIGHT - height,
+            (bar - 1.0).max(0.5),
+            height
+        );
+    }
+    let _ = writeln!(
+        svg,
+        "<text x=\"0\" y=\"{}\">{}</text>",
+        HEIGHT + 15.0,
+        display::format_minutes(&summary.since)
+    );
+    let _ = writeln!(
+        svg,
+        "<text x=\"{WIDTH}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+        HEIGHT + 15.0,
+        display::format_minutes(&summary.until)
+    );
+    let _ = writeln!(
+        svg,
+        "<text x=\"{WIDTH}\" y=\"12\" text-anchor=\"end\">max {max}/hour</text>"
+    );
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Convert an HTML report to PDF with the first converter found.
+fn html_to_pdf(html: &str, output: &Path) -> Result<()> {
+    let mut input = tempfile::Builder::new().suffix(".html").tempfile()?;
+    input.write_all(html.as_bytes())?;
+    input.flush()?;
+    let input = input.path();
+
+    let mut command = std::process::Command::new("wkhtmltopdf");
+    command.args(["--quiet"]).arg(input).arg(output);
+    if let Ok(status) = command.status() {
+        if status.success() {
+            return Ok(());
+        }
+        bail!("wkhtmltopdf failed with exit code {:?}", status.code());
+    }
+    for browser in ["chromium", "chromium-browser", "google-chrome"] {
+        let status = std::process::Command::new(browser)
+            .args(["--headless", "--disable-gpu", "--no-pdf-header-footer"])
+            .arg(format!("--print-to-pdf={}", output.display()))
+            .arg(format!("file://{}", input.display()))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        if let Ok(status) = status {
+            if status.success() {
+                return Ok(());
+            }
+            bail!("{browser} failed with exit code {:?}", status.code());
+        }
+    }
+    bail!("No HTML to PDF converter found, install wkhtmltopdf or Chromium");
+}
+
+/// Sensor health for the top of a sent report.
+fn health(context: &Context) -> Result<String> {
+    let status = status::status(context);
+    let mut out = String::new();
+    writeln!(out, "Sensor health:")?;
+    for container in [&status.suricata, &status.evebox] {
+        writeln!(
+            out,
+            "  {}: {}",
+            container.name,
+            container.status.as_deref().unwrap_or("not found")
+        )?;
+    }
+    let rules = status
+        .rules_last_updated
+        .map(|ts| display::format_minutes(&ts))
+        .unwrap_or_else(|| "never".to_string());
+    writeln!(out, "  rules last updated: {rules}")?;
+    if let Some(pressure) = &status.disk_pressure {
+        writeln!(out, "  disk: {pressure}")?;
+    }
+    writeln!(out)?;
+    Ok(out)
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|hostname| hostname.trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Send the report for the last `hours` hours to the configured email
+/// address and webhook.
+pub(crate) fn send(context: &Context, hours: i64, top: usize) -> Result<()> {
+    let config = &context.config.reports;
+    let push = notify::is_configured(&context.config.notifications);
+    if config.email.is_none() && config.webhook.is_none() && !push {
+        bail!("No report email, webhook or notification channel configured");
+    }
+    let subject = format!("Simple-IDS report for {}", hostname());
+    let body = health(context)? + &render(context, hours, top)?;
+
+    let mut failed = vec![];
+    if let Some(email) = &config.email {
+        match sendmail(email, &subject, &body) {
+            Ok(()) => info!("Report emailed to {email}"),
+            Err(err) => failed.push(format!("email to {email}: {err}")),
+        }
+    }
+    if let Some(webhook) = &config.webhook {
+        match post(webhook, &subject, &body) {
+            Ok(()) => info!("Report sent to webhook"),
+            Err(err) => failed.push(format!("webhook: {err}")),
+        }
+    }
+    if push {
+        // The full report is too long for a push notification, so only
+        // the health summary is sent.
+        if let Err(err) = notify::send(
+            &context.config.notifications,
+            &subject,
+            &health(context)?,
+            notify::Priority::Default,
+        ) {
+            failed.push(format!("notification: {err}"));
+        }
+    }
+    if !failed.is_empty() {
+        bail!(failed.join(", "));
+    }
+    Ok(())
+}
+
+fn sendmail(to: &str, subject: &str, body: &str) -> Result<()> {
+    let mut child = std::process::Command::new("sendmail")
+        .args(["-t"])
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        write!(stdin, "To: {to}\nSubject: {subject}\n\n{body}")?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("sendmail failed with exit code {:?}", status.code());
+    }
+    Ok(())
+}
+
+fn post(url: &str, subject: &str, body: &str) -> Result<()> {
+    let payload = serde_json::json!({
+        "subject": subject,
+        "text": body,
+    });
+    let response = reqwest::blocking::Client::new()
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(payload.to_string())
+        .send()?;
+    if !response.status().is_success() {
+        bail!("HTTP status code {}", response.status());
+    }
+    Ok(())
+}
+
+/// Install or remove the cron schedule sending the report, depending
+/// on the configuration.
+pub(crate) fn update_schedule(config: &ReportsConfig) -> Result<()> {
+    let schedule = match config.schedule {
+        Some(schedule) => schedule,
+        None => {
+            remove_schedule();
+            return Ok(());
+        }
+    };
+    if !Path::new("/etc/cron.d").is_dir() {
+        bail!("/etc/cron.d does not exist");
+    }
+    let exe = std::env::current_exe()?;
+    let cwd = std::env::current_dir()?;
+    let when = match schedule {
+        ReportSchedule::Daily => "0 7 * * *",
+        ReportSchedule::Weekly => "0 7 * * 1",
+    };
+    let entry = format!(
+        "# Installed by Simple-IDS to send the {} report.\n\
+         {} root cd {} && {} report --send --hours {} >/dev/null 2>&1\n",
+        match schedule {
+            ReportSchedule::Daily => "daily",
+            ReportSchedule::Weekly => "weekly",
+        },
+        when,
+        system::cron_quote(&cwd.to_string_lossy()),
+        system::cron_quote(&exe.to_string_lossy()),
+        schedule.hours()
+    );
+    if std::fs::read_to_string(CRON_FILENAME).ok().as_deref() == Some(entry.as_str()) {
+        return Ok(());
+    }
+    std::fs::write(CRON_FILENAME, entry)?;
+    info!("Installed report schedule {CRON_FILENAME}");
+    Ok(())
+}
+
+pub(crate) fn remove_schedule() {
+    if Path::new(CRON_FILENAME).exists() {
+        if let Err(err) = std::fs::remove_file(CRON_FILENAME) {
+            error!("Failed to remove {CRON_FILENAME}: {err}");
+        }
+    }
+}
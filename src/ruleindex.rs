@@ -16,4 +16,23 @@ pub(crate) struct RuleSource {
     pub summary: String,
     pub obsolete: Option<String>,
     pub parameters: Option<HashMap<String, serde_yaml::Value>>,
+    pub vendor: Option<String>,
+    pub license: Option<String>,
+    #[serde(rename = "subscribe-url")]
+    pub subscribe_url: Option<String>,
+    pub url: Option<String>,
+}
+
+impl RuleSource {
+    /// True if the source needs a subscription or registration, or has
+    /// a license restricting its use.
+    pub(crate) fn is_non_free(&self) -> bool {
+        self.subscribe_url.is_some()
+            || self.parameters.is_some()
+            || self
+                .license
+                .as_deref()
+                .map(|license| license.to_lowercase().contains("commercial"))
+                .unwrap_or(false)
+    }
 }
@@ -0,0 +1,82 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Read, Write},
+    os::unix::fs::PermissionsExt,
+};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+const SECRETS_FILENAME: &str = "simple-ids.secrets.toml";
+
+/// Prefix used in the main configuration to reference a secret by key.
+const SECRET_PREFIX: &str = "secret:";
+
+/// Secrets such as rule source codes, registry credentials and tokens.
+///
+/// These are kept in their own file, readable only by the owner, so the
+/// main configuration can be shared without leaking them. The main
+/// configuration references a secret with "secret:<key>".
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub(crate) struct Secrets {
+    #[serde(flatten)]
+    values: BTreeMap<String, String>,
+}
+
+impl Secrets {
+    pub(crate) fn load() -> Result<Self> {
+        let mut file = match fs::File::open(SECRETS_FILENAME) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut buf = String::new();
+        file.read_to_string(&mut buf)?;
+        Ok(toml::from_str(&buf)?)
+    }
+
+    pub(crate) fn save(&self) -> Result<()> {
+        let buf = toml::to_string(self)?;
+        let mut file = tempfile::NamedTempFile::new_in(".")?;
+        file.as_file()
+            .set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(buf.as_bytes())?;
+        file.as_file().sync_all()?;
+        file.persist(SECRETS_FILENAME)?;
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub(crate) fn set(&mut self, key: &str, value: &str) {
+        self.values.insert(key.to_string(), value.to_string());
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) -> bool {
+        self.values.remove(key).is_some()
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &String> {
+        self.values.keys()
+    }
+
+    /// Resolve a value from the main configuration.
+    ///
+    /// Values of the form "secret:<key>" are looked up in the secrets
+    /// file, anything else is returned as is.
+    pub(crate) fn resolve(&self, value: &str) -> Result<String> {
+        match value.strip_prefix(SECRET_PREFIX) {
+            Some(key) => match self.get(key) {
+                Some(secret) => Ok(secret.to_string()),
+                None => bail!("secret {} not found in {}", key, SECRETS_FILENAME),
+            },
+            None => Ok(value.to_string()),
+        }
+    }
+}
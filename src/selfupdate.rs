@@ -3,9 +3,10 @@
 
 use std::{
     env,
+    ffi::CString,
     fs::{self, File},
-    io::{self, Seek, SeekFrom},
-    os::unix::prelude::PermissionsExt,
+    io::{self, IsTerminal, Read, Seek, SeekFrom, Write},
+    os::unix::{ffi::OsStrExt, prelude::PermissionsExt},
     path::Path,
     process,
 };
@@ -14,11 +15,15 @@ use anyhow::{bail, Result};
 use sha2::{Digest, Sha256};
 use tracing::{debug, error, info, warn};
 
+use crate::config::UpdatesConfig;
+
+const DEFAULT_URL: &str = "https://evebox.org/files/simple-ids";
+
 // Ok, the return type is a bit odd as this handles a lot of the error
 // handling itself. An `Err` is an error that should be logged by the
 // caller.  Ok(true) is success, but Ok(false) is an error that was
 // logged by this function.
-pub(crate) fn self_update() -> Result<()> {
+pub(crate) fn self_update(config: &UpdatesConfig) -> Result<()> {
     // If we're running from cargo, don't self update.
     if env::var("CARGO").is_ok() {
         info!("Not self updating as we are running from Cargo");
@@ -26,7 +31,8 @@ pub(crate) fn self_update() -> Result<()> {
     }
 
     let target = env!("TARGET");
-    let url = format!("https://evebox.org/files/simple-ids/{}/simple-ids", target);
+    let base_url = config.url.as_deref().unwrap_or(DEFAULT_URL);
+    let url = format!("{}/{}/simple-ids", base_url.trim_end_matches('/'), target);
     let hash_url = format!("{}.sha256", url);
     let current_exe = if let Ok(exe) = env::current_exe() {
         exe
@@ -57,12 +63,12 @@ pub(crate) fn self_update() -> Result<()> {
     let remote_hash = response.text()?.trim().to_lowercase();
     debug!("Remote SHA256 checksum: {}", &remote_hash);
 
-    match current_hash {
+    match &current_hash {
         None => {
             info!("Failed to determine checksum of current exe, updating");
         }
         Some(checksum) => {
-            if checksum != remote_hash {
+            if *checksum != remote_hash {
                 info!("Remote checksum different than current exe, will update");
             } else {
                 info!("No update available");
@@ -71,8 +77,17 @@ pub(crate) fn self_update() -> Result<()> {
         }
     }
 
-    info!("Downloading {}", &url);
-    let mut download_exe = download_release(&url)?;
+    let delta = match &current_hash {
+        Some(current_hash) => download_delta(&url, &current_exe, current_hash, &remote_hash),
+        None => None,
+    };
+    let mut download_exe = match delta {
+        Some(file) => file,
+        None => {
+            info!("Downloading {}", &url);
+            download_release(&url)?
+        }
+    };
 
     // Verify the checksum.
     let hash = file_checksum(&mut download_exe)?;
@@ -87,29 +102,154 @@ pub(crate) fn self_update() -> Result<()> {
     }
 
     info!("Replacing current executable");
+    let size = download_exe.metadata()?.len();
+    let dir = match current_exe.parent() {
+        Some(dir) => dir,
+        None => bail!("Failed to determine directory of current executable"),
+    };
+    check_free_space(dir, size)?;
+
+    // Write the new executable next to the current one, then rename it
+    // over the current one. The rename is atomic so a crash can't leave
+    // a partially written executable behind.
+    let mut new_exe = match tempfile::NamedTempFile::new_in(dir) {
+        Ok(file) => file,
+        Err(err) => bail!("Directory {} is not writable: {}", dir.display(), err),
+    };
     download_exe.seek(SeekFrom::Start(0))?;
-    if let Err(err) = fs::remove_file(&current_exe) {
-        tracing::warn!(
-            "Failed to remove current exe: {}: {}",
-            current_exe.display(),
-            err
-        );
+    io::copy(&mut download_exe, new_exe.as_file_mut())?;
+    new_exe
+        .as_file()
+        .set_permissions(fs::Permissions::from_mode(0o0755))?;
+    new_exe.as_file().sync_all()?;
+    if let Err(err) = new_exe.persist(&current_exe) {
+        bail!("Failed to replace {}: {}", current_exe.display(), err);
     }
-    let mut final_exec = fs::File::create(&current_exe)?;
-    io::copy(&mut download_exe, &mut final_exec)?;
-    fs::set_permissions(&current_exe, fs::Permissions::from_mode(0o0755))?;
+    crate::journal::record(
+        crate::journal::Kind::SelfUpdate,
+        format!("Updated to {remote_hash}"),
+    );
     warn!("The Simple-IDS program has been updated. Please restart.");
     process::exit(0);
 }
 
+/// Make sure there is room for the new executable before writing it.
+fn check_free_space(dir: &Path, required: u64) -> Result<()> {
+    let path = CString::new(dir.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        bail!(
+            "Failed to get free space for {}: {}",
+            dir.display(),
+            io::Error::last_os_error()
+        );
+    }
+    let available = stat.f_bavail as u64 * stat.f_frsize as u64;
+    if available < required {
+        bail!(
+            "Not enough free space in {}: {} bytes required, {} available",
+            dir.display(),
+            required,
+            available
+        );
+    }
+    Ok(())
+}
+
 fn download_release(url: &str) -> Result<File> {
-    let mut response = reqwest::blocking::get(url)?;
+    let response = reqwest::blocking::get(url)?;
+    if !response.status().is_success() {
+        bail!("HTTP status code={}", response.status());
+    }
     let mut dest = tempfile::tempfile()?;
-    io::copy(&mut response, &mut dest)?;
+    copy_with_progress(response, &mut dest)?;
     dest.seek(SeekFrom::Start(0))?;
     Ok(dest)
 }
 
+/// Try to update with a binary patch from the current executable, as
+/// the full executable is large to download over metered connections.
+///
+/// Patches are published next to the executable, named by the
+/// checksum of the executable they apply to. None is returned if there
+/// is no patch or it couldn't be applied, and a full download should be
+/// done instead.
+fn download_delta(
+    url: &str,
+    current_exe: &Path,
+    current_hash: &str,
+    remote_hash: &str,
+) -> Option<File> {
+    let patch_url = format!("{}.{}.bsdiff", url, current_hash);
+    let response = match reqwest::blocking::get(&patch_url) {
+        Ok(response) if response.status().is_success() => response,
+        Ok(_) | Err(_) => {
+            debug!("No delta update available at {}", &patch_url);
+            return None;
+        }
+    };
+    info!("Downloading delta update {}", &patch_url);
+    let mut patch = vec![];
+    if let Err(err) = copy_with_progress(response, &mut patch) {
+        warn!("Failed to download delta update: {err}");
+        return None;
+    }
+
+    let apply = || -> Result<File> {
+        let source = fs::read(current_exe)?;
+        let mut dest = tempfile::tempfile()?;
+        qbsdiff::Bspatch::new(&patch)?.apply(&source, &mut dest)?;
+        dest.seek(SeekFrom::Start(0))?;
+        if file_checksum(&mut dest)? != remote_hash {
+            bail!("patched executable has invalid checksum");
+        }
+        dest.seek(SeekFrom::Start(0))?;
+        Ok(dest)
+    };
+    match apply() {
+        Ok(file) => Some(file),
+        Err(err) => {
+            warn!("Failed to apply delta update, doing a full download: {err}");
+            None
+        }
+    }
+}
+
+/// Copy a response to `dest`, showing progress on a terminal.
+fn copy_with_progress(
+    mut response: reqwest::blocking::Response,
+    dest: &mut impl Write,
+) -> Result<u64> {
+    let total = response.content_length();
+    let show = io::stderr().is_terminal();
+    let mut buf = vec![0; 64 * 1024];
+    let mut copied = 0u64;
+    loop {
+        let n = response.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        dest.write_all(&buf[..n])?;
+        copied += n as u64;
+        if show {
+            let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+            match total {
+                Some(total) if total > 0 => eprint!(
+                    "\r{:.1}/{:.1} MB ({}%)",
+                    mb(copied),
+                    mb(total),
+                    copied * 100 / total
+                ),
+                _ => eprint!("\r{:.1} MB", mb(copied)),
+            }
+        }
+    }
+    if show {
+        eprintln!();
+    }
+    Ok(copied)
+}
+
 fn file_checksum(file: &mut File) -> Result<String> {
     let mut hash = Sha256::new();
     io::copy(file, &mut hash)?;
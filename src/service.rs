@@ -0,0 +1,108 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Install Simple-IDS as a systemd service so it starts at boot.
+
+use std::{path::Path, process::Command};
+
+use anyhow::{bail, Result};
+use tracing::info;
+
+use crate::{container::ContainerManager, nicsetup::systemctl};
+
+const UNIT_NAME: &str = "simple-ids.service";
+const UNIT_PATH: &str = "/etc/systemd/system/simple-ids.service";
+
+/// Render the unit. In the foreground Simple-IDS runs for the life of
+/// the service, otherwise the service starts the detached containers
+/// and stops them when the service is stopped.
+fn render(exe: &Path, directory: &Path, foreground: bool) -> String {
+    let exe = exe.display();
+    let service = if foreground {
+        format!(
+            "Type=simple\n\
+             ExecStart={exe} start --debug\n\
+             Restart=on-failure\n\
+             RestartSec=10\n"
+        )
+    } else {
+        format!(
+            "Type=oneshot\n\
+             RemainAfterExit=yes\n\
+             ExecStart={exe} start\n\
+             ExecStop={exe} stop\n"
+        )
+    };
+    format!(
+        "# Installed by Simple-IDS.\n\
+         [Unit]\n\
+         Description=Simple-IDS\n\
+         Wants=network-online.target\n\
+         After=network-online.target docker.service podman.service simple-ids-nic.service\n\
+         \n\
+         [Service]\n\
+         WorkingDirectory={}\n\
+         {service}\
+         \n\
+         [Install]\n\
+         WantedBy=multi-user.target\n",
+        directory.display()
+    )
+}
+
+/// Return true if the service has been installed.
+pub(crate) fn installed() -> bool {
+    Path::new(UNIT_PATH).exists()
+}
+
+/// The unit that has to be enabled for the container engine to bring
+/// back the containers with a restart policy after a reboot. Podman has
+/// no daemon, so its podman-restart service does it.
+pub(crate) fn engine_boot_unit(manager: &ContainerManager) -> &'static str {
+    if manager.is_podman() {
+        "podman-restart.service"
+    } else {
+        "docker.service"
+    }
+}
+
+/// Return true if a systemd unit is enabled.
+pub(crate) fn unit_enabled(unit: &str) -> bool {
+    Command::new("systemctl")
+        .args(["is-enabled", "--quiet", unit])
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Enable and start a systemd unit.
+pub(crate) fn enable_unit(unit: &str) -> Result<()> {
+    systemctl(&["enable", "--now", unit])
+}
+
+/// Install, enable and start the service for the configuration in the
+/// current directory.
+pub(crate) fn install(foreground: bool) -> Result<()> {
+    if !Path::new("/run/systemd/system").exists() {
+        bail!("systemd is not running");
+    }
+    let exe = std::env::current_exe()?;
+    let directory = std::env::current_dir()?;
+    std::fs::write(UNIT_PATH, render(&exe, &directory, foreground))?;
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", UNIT_NAME])?;
+    info!("Installed and enabled {UNIT_PATH}");
+    Ok(())
+}
+
+/// Stop, disable and remove the service.
+pub(crate) fn uninstall() -> Result<()> {
+    if !Path::new(UNIT_PATH).exists() {
+        bail!("{UNIT_PATH} is not installed");
+    }
+    systemctl(&["disable", "--now", UNIT_NAME])?;
+    std::fs::remove_file(UNIT_PATH)?;
+    systemctl(&["daemon-reload"])?;
+    info!("Removed {UNIT_PATH}");
+    Ok(())
+}
@@ -0,0 +1,168 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Rule staging: update the rules into a staging directory, compare the
+//! alerts they generate against the live rules on sample traffic, then
+//! promote them once they look sane.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Result};
+use chrono::Utc;
+use serde_json::Value;
+use tracing::{error, info, warn};
+
+use crate::{
+    actions,
+    container::{timeouts, CommandExt, SuricataContainer},
+    context::Context,
+    journal,
+    readpcap::{pcap_volumes, PCAP_DIR},
+    state::State,
+    SURICATA_CONTAINER_NAME,
+};
+
+const LIVE_RULES: &str = "/var/lib/suricata/rules/suricata.rules";
+const STAGING_DIR: &str = "/var/lib/suricata/staging";
+const STAGED_RULES: &str = "/var/lib/suricata/staging/suricata.rules";
+
+/// Update the rules into the staging directory, leaving the live rules
+/// untouched. If pcaps are given the staged rules are evaluated against
+/// them.
+pub(crate) fn stage(context: &Context, pcaps: &[PathBuf], top: usize) -> Result<()> {
+    let container = SuricataContainer::new(context.clone());
+    container
+        .run()
+        .rm()
+        .it()
+        .volumes(&actions::suricata_update_volumes())
+        .args(&["suricata-update", "--output", STAGING_DIR])
+        .build()
+        .status_ok_timeout(timeouts().run)?;
+    info!("Rules staged in {STAGING_DIR}");
+
+    if pcaps.is_empty() {
+        info!("No pcaps given, skipping evaluation of the staged rules");
+    } else {
+        evaluate(context, pcaps, top)?;
+    }
+    info!("Run 'simple-ids rules promote' to make the staged rules live");
+    Ok(())
+}
+
+/// Compare the alerts from the live and staged rules on the pcaps.
+fn evaluate(context: &Context, pcaps: &[PathBuf], top: usize) -> Result<()> {
+    info!("Running the live rules against {} pcap(s)", pcaps.len());
+    let live = run_pcaps(context, pcaps, LIVE_RULES)?;
+    info!("Running the staged rules against {} pcap(s)", pcaps.len());
+    let staged = run_pcaps(context, pcaps, STAGED_RULES)?;
+
+    let live_total: u64 = live.values().sum();
+    let staged_total: u64 = staged.values().sum();
+    println!("Alerts with the live rules: {live_total}");
+    println!(
+        "Alerts with the staged rules: {staged_total}{}",
+        percent_change(live_total, staged_total)
+    );
+
+    let signatures: BTreeSet<&String> = live.keys().chain(staged.keys()).collect();
+    let mut changes: Vec<(&String, i64)> = signatures
+        .into_iter()
+        .map(|signature| {
+            let before = live.get(signature).copied().unwrap_or(0) as i64;
+            let after = staged.get(signature).copied().unwrap_or(0) as i64;
+            (signature, after - before)
+        })
+        .filter(|(_, delta)| *delta != 0)
+        .collect();
+    changes.sort_by(|a, b| b.1.abs().cmp(&a.1.abs()).then(a.0.cmp(b.0)));
+    if !changes.is_empty() {
+        println!();
+        println!("Largest changes:");
+        for (signature, delta) in changes.into_iter().take(top) {
+            println!("  {delta:>+8}  {signature}");
+        }
+    }
+    Ok(())
+}
+
+fn percent_change(before: u64, after: u64) -> String {
+    if before == 0 {
+        return String::new();
+    }
+    let change = (after as f64 - before as f64) * 100.0 / before as f64;
+    format!(" ({change:+.1}%)")
+}
+
+/// Run Suricata over the pcaps with a rule file, returning the alert
+/// count for each signature.
+fn run_pcaps(context: &Context, pcaps: &[PathBuf], rules: &str) -> Result<HashMap<String, u64>> {
+    let volumes = pcap_volumes(pcaps)?;
+
+    // The Suricata logs go to stderr so only the alerts are on stdout.
+    let script = format!(
+        "mkdir -p /tmp/eve && \
+         suricata -k none -r {PCAP_DIR} -l /tmp/eve -S {rules} 1>&2 || exit 1; \
+         grep -F '\"event_type\":\"alert\"' /tmp/eve/eve.json; exit 0"
+    );
+    let container = SuricataContainer::new(context.clone());
+    let output = container
+        .run()
+        .rm()
+        .volumes(&volumes)
+        .args(&["sh", "-c", &script])
+        .build()
+        .output_timeout(timeouts().run)?;
+    if !output.status.success() {
+        bail!(
+            "Suricata failed to run the pcaps with {rules}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let mut counts = HashMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Ok(event) = serde_json::from_str::<Value>(line) {
+            let signature = format!(
+                "[{}] {}",
+                event["alert"]["signature_id"],
+                event["alert"]["signature"].as_str().unwrap_or("")
+            );
+            *counts.entry(signature).or_default() += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// Replace the live rules with the staged rules, reloading the rules if
+/// Suricata is running.
+pub(crate) fn promote(context: &Context) -> Result<()> {
+    let script = format!(
+        "test -e {STAGED_RULES} || {{ echo 'No staged rules found' >&2; exit 1; }}; \
+         cp {STAGED_RULES} {LIVE_RULES}.tmp && mv {LIVE_RULES}.tmp {LIVE_RULES} && \
+         rm -rf {STAGING_DIR}"
+    );
+    let container = SuricataContainer::new(context.clone());
+    container
+        .run()
+        .rm()
+        .args(&["sh", "-c", &script])
+        .build()
+        .status_output_timeout(timeouts().control)?;
+    info!("Staged rules promoted");
+    journal::record(journal::Kind::RuleUpdate, "Staged rules promoted");
+    if let Err(err) = State::update(|state| state.last_rule_update = Some(Utc::now())) {
+        error!("Failed to record rule update time: {err}");
+    }
+
+    if context.manager.is_running(SURICATA_CONTAINER_NAME) {
+        match actions::suricatasc(context, "reload-rules") {
+            Ok(_) => info!("Suricata rules reloaded"),
+            Err(err) => warn!("Failed to reload the Suricata rules, restart Suricata: {err}"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,212 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+use std::{collections::BTreeMap, io::Write, os::fd::AsRawFd, path::PathBuf};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+const STATE_FILENAME: &str = "simple-ids.state.toml";
+
+/// Directory for files managed through Simple-IDS, such as the
+/// suricata-update configuration files.
+const STATE_DIR: &str = "simple-ids.d";
+
+/// Managed files that were kept in the current directory before the
+/// state directory existed.
+const LEGACY_FILES: &[&str] = &[
+    "enable.conf",
+    "disable.conf",
+    "modify.conf",
+    "drop.conf",
+    "update.yaml",
+    crate::CLASSIFICATION_CONFIG,
+    crate::REFERENCE_CONFIG,
+];
+
+/// Absolute path of a managed file in the state directory.
+pub(crate) fn file_path(filename: &str) -> PathBuf {
+    std::env::current_dir()
+        .unwrap_or_default()
+        .join(STATE_DIR)
+        .join(filename)
+}
+
+/// Move managed files created before the state directory existed into
+/// it from the current directory. Run once at startup.
+pub(crate) fn migrate() {
+    for filename in LEGACY_FILES {
+        let old = PathBuf::from(filename);
+        let path = file_path(filename);
+        if path.exists() || !old.exists() {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            if let Err(err) = std::fs::create_dir_all(parent) {
+                warn!("Failed to create {}: {err}", parent.display());
+                return;
+            }
+        }
+        match std::fs::rename(&old, &path) {
+            Ok(()) => info!("Moved {filename} to {}", path.display()),
+            Err(err) => warn!("Failed to move {filename} to {}: {err}", path.display()),
+        }
+    }
+}
+
+/// An exclusive lock, held until dropped.
+pub(crate) struct Lock {
+    _file: std::fs::File,
+}
+
+/// Take an exclusive lock so an operation, such as removing the data
+/// volumes, can't run more than once at the same time.
+pub(crate) fn lock(name: &str) -> Result<Lock> {
+    let file = open_lock(name)?;
+    let rc = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if rc != 0 {
+        bail!("Another {name} is already in progress");
+    }
+    Ok(Lock { _file: file })
+}
+
+/// Take an exclusive lock, waiting for it if held by another process,
+/// for short operations such as appending to a file.
+pub(crate) fn lock_wait(name: &str) -> Result<Lock> {
+    let file = open_lock(name)?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        bail!("Failed to lock {name}: {}", std::io::Error::last_os_error());
+    }
+    Ok(Lock { _file: file })
+}
+
+fn open_lock(name: &str) -> Result<std::fs::File> {
+    let path = file_path(&format!("{name}.lock"));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    Ok(std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)?)
+}
+
+/// State recorded by Simple-IDS itself, as opposed to configuration
+/// set by the user.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub(crate) struct State {
+    /// Time of the last successful rule update.
+    #[serde(rename = "last-rule-update", skip_serializing_if = "Option::is_none")]
+    pub last_rule_update: Option<DateTime<Utc>>,
+
+    /// Recent start times of each container, used to spot a container
+    /// that keeps getting restarted.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub starts: BTreeMap<String, Vec<DateTime<Utc>>>,
+
+    /// Time of the last log rotation and its error if it failed.
+    #[serde(rename = "last-logrotate", skip_serializing_if = "Option::is_none")]
+    pub last_logrotate: Option<DateTime<Utc>>,
+    #[serde(
+        rename = "last-logrotate-error",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_logrotate_error: Option<String>,
+
+    /// Set when logging has been reduced due to low disk space.
+    #[serde(rename = "reduced-logging", default)]
+    pub reduced_logging: bool,
+    #[serde(rename = "disk-pressure", skip_serializing_if = "Option::is_none")]
+    pub disk_pressure: Option<String>,
+
+    /// Expiry of the certificate EveBox was last seen serving.
+    #[serde(
+        rename = "evebox-cert-expires",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub evebox_cert_expires: Option<DateTime<Utc>>,
+
+    /// Set once the WSL2 guidance has been shown.
+    #[serde(
+        rename = "wsl-guidance-shown",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub wsl_guidance_shown: bool,
+
+    /// Set once the EveBox admin user has been created, or found to
+    /// already exist.
+    #[serde(
+        rename = "evebox-admin-created",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub evebox_admin_created: bool,
+
+    /// Number of times the watchdog has restarted each container.
+    #[serde(
+        rename = "watchdog-restarts",
+        default,
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub watchdog_restarts: BTreeMap<String, u64>,
+}
+
+impl State {
+    /// Load the state, any error results in the default state.
+    pub(crate) fn load() -> Self {
+        std::fs::read_to_string(STATE_FILENAME)
+            .ok()
+            .and_then(|buf| toml::from_str(&buf).ok())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn save(&self) -> Result<()> {
+        let buf = toml::to_string(self)?;
+        let mut file = tempfile::NamedTempFile::new_in(".")?;
+        file.write_all(buf.as_bytes())?;
+        file.persist(STATE_FILENAME)?;
+        Ok(())
+    }
+
+    /// Record a container start, dropping starts older than a day.
+    pub(crate) fn record_start(name: &str) -> Result<()> {
+        crate::journal::record(crate::journal::Kind::Start, name);
+        Self::update(|state| {
+            let now = Utc::now();
+            let starts = state.starts.entry(name.to_string()).or_default();
+            starts.retain(|ts| now.signed_duration_since(*ts) < Duration::days(1));
+            starts.push(now);
+        })
+    }
+
+    pub(crate) fn record_watchdog_restart(name: &str) -> Result<()> {
+        Self::update(|state| {
+            *state.watchdog_restarts.entry(name.to_string()).or_default() += 1;
+        })
+    }
+
+    /// Number of times a container was started in the last day.
+    pub(crate) fn starts_last_day(&self, name: &str) -> usize {
+        let now = Utc::now();
+        self.starts
+            .get(name)
+            .map(|starts| {
+                starts
+                    .iter()
+                    .filter(|ts| now.signed_duration_since(**ts) < Duration::days(1))
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Load, modify and save the state.
+    pub(crate) fn update<F: FnOnce(&mut State)>(f: F) -> Result<()> {
+        let mut state = Self::load();
+        f(&mut state);
+        state.save()
+    }
+}
@@ -0,0 +1,129 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Structured status, for `status --json`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::task::JoinHandle;
+
+use crate::{
+    container::{self, Container},
+    context::Context,
+    state::State,
+};
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Status {
+    pub suricata: ContainerStatus,
+    pub evebox: ContainerStatus,
+    pub interfaces: Vec<String>,
+    #[serde(rename = "unix-socket")]
+    pub unix_socket: bool,
+    /// The NFQUEUE number when running in IPS mode.
+    #[serde(rename = "ips-queue")]
+    pub ips_queue: Option<u16>,
+    #[serde(rename = "evebox-url")]
+    pub evebox_url: String,
+    /// How Podman networks the containers, None with Docker.
+    #[serde(rename = "podman-network", skip_serializing_if = "Option::is_none")]
+    pub podman_network: Option<String>,
+    #[serde(rename = "rules-last-updated")]
+    pub rules_last_updated: Option<DateTime<Utc>>,
+    #[serde(rename = "disk-pressure")]
+    pub disk_pressure: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ContainerStatus {
+    pub name: String,
+    pub image: String,
+    /// The container engine status, such as "running" or "exited".
+    /// None if the container doesn't exist.
+    pub status: Option<String>,
+    /// False if the service is disabled in the configuration.
+    pub enabled: bool,
+    pub running: bool,
+    #[serde(rename = "started-at")]
+    pub started_at: Option<String>,
+    #[serde(rename = "restart-required")]
+    pub restart_required: bool,
+    /// Number of times the watchdog has restarted the container.
+    #[serde(rename = "watchdog-restarts")]
+    pub watchdog_restarts: u64,
+}
+
+impl Status {
+    pub(crate) fn is_running(&self) -> bool {
+        [&self.suricata, &self.evebox]
+            .iter()
+            .all(|container| container.running || !container.enabled)
+    }
+}
+
+/// Gather the status, querying the container engine for each part at
+/// the same time.
+pub(crate) fn status(context: &Context) -> Status {
+    let state = State::load();
+    let (suricata, evebox, evebox_url, podman_network) = container::block_on(async {
+        let suricata = query(context, &state, |context, state| {
+            container_status(context, state, Container::Suricata)
+        });
+        let evebox = query(context, &state, |context, state| {
+            container_status(context, state, Container::EveBox)
+        });
+        let evebox_url = query(context, &state, |context, _| {
+            crate::guess_evebox_url(context)
+        });
+        let podman_network = query(context, &state, |context, _| {
+            context
+                .manager
+                .podman_network_info()
+                .map(|network| network.describe(&context.config.podman))
+        });
+        (
+            suricata.await.unwrap(),
+            evebox.await.unwrap(),
+            evebox_url.await.unwrap(),
+            podman_network.await.unwrap(),
+        )
+    });
+    Status {
+        suricata,
+        evebox,
+        interfaces: context.config.suricata.capture_interfaces(),
+        unix_socket: context.config.suricata.unix_socket,
+        ips_queue: context.config.suricata.ips.as_ref().map(|ips| ips.queue),
+        evebox_url,
+        podman_network,
+        rules_last_updated: state.last_rule_update,
+        disk_pressure: state.disk_pressure,
+    }
+}
+
+/// Query a part of the status, on the container runtime so the parts are
+/// queried at the same time.
+fn query<T, F>(context: &Context, state: &State, f: F) -> JoinHandle<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Context, &State) -> T + Send + 'static,
+{
+    let context = context.clone();
+    let state = state.clone();
+    container::spawn_blocking(move || f(&context, &state))
+}
+
+fn container_status(context: &Context, state: &State, container: Container) -> ContainerStatus {
+    let name = container.name();
+    let inspect = context.manager.state(name).ok();
+    ContainerStatus {
+        name: name.to_string(),
+        image: context.image_name(container),
+        status: inspect.as_ref().map(|state| state.status.clone()),
+        enabled: context.config.services.enabled(container),
+        running: inspect.as_ref().map(|state| state.running).unwrap_or(false),
+        started_at: inspect.and_then(|state| state.started_at),
+        restart_required: crate::restart_required(context, container),
+        watchdog_restarts: state.watchdog_restarts.get(name).copied().unwrap_or(0),
+    }
+}
@@ -0,0 +1,241 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! A guided tuning session for the noisiest alerts, turning what the
+//! report shows into disable.conf and threshold.config entries.
+
+use std::{collections::HashMap, io::Write};
+
+use anyhow::{bail, Result};
+use chrono::{Duration, Utc};
+use serde_json::Value;
+use tracing::{error, info};
+
+use crate::{
+    actions, add_index,
+    context::Context,
+    eve::{self, EVE_FILENAME},
+    prompt, state, SelectItem, SURICATA_CONTAINER_NAME, THRESHOLD_CONFIG,
+};
+
+/// A signature and where its alerts came from.
+#[derive(Default)]
+struct Noisy {
+    gid: u64,
+    sid: u64,
+    signature: String,
+    count: u64,
+    sources: HashMap<String, u64>,
+    destinations: HashMap<String, u64>,
+}
+
+impl Noisy {
+    fn label(&self) -> String {
+        format!(
+            "{:>8}  [{}:{}] {}",
+            self.count, self.gid, self.sid, self.signature
+        )
+    }
+}
+
+/// The address with the most alerts, and its share of all the alerts.
+fn top_address(counts: &HashMap<String, u64>, total: u64) -> Option<(&String, u64)> {
+    counts
+        .iter()
+        .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+        .map(|(addr, count)| (addr, count * 100 / total.max(1)))
+}
+
+fn collect(context: &Context, days: i64) -> Result<Vec<Noisy>> {
+    let since = Utc::now() - Duration::days(days);
+    let mut signatures: HashMap<(u64, u64), Noisy> = HashMap::new();
+    let status = eve::exec_lines(
+        context,
+        &["grep", "-F", "\"event_type\":\"alert\"", EVE_FILENAME],
+        |line| {
+            let event: Value = match serde_json::from_str(line) {
+                Ok(event) => event,
+                Err(_) => return,
+            };
+            let recent = event["timestamp"]
+                .as_str()
+                .and_then(eve::parse_timestamp)
+                .map(|ts| ts >= since)
+                .unwrap_or(false);
+            if !recent {
+                return;
+            }
+            let alert = &event["alert"];
+            let gid = alert["gid"].as_u64().unwrap_or(1);
+            let sid = match alert["signature_id"].as_u64() {
+                Some(sid) => sid,
+                None => return,
+            };
+            let noisy = signatures.entry((gid, sid)).or_insert_with(|| Noisy {
+                gid,
+                sid,
+                signature: alert["signature"].as_str().unwrap_or("").to_string(),
+                ..Default::default()
+            });
+            noisy.count += 1;
+            for (key, counts) in [
+                ("src_ip", &mut noisy.sources),
+                ("dest_ip", &mut noisy.destinations),
+            ] {
+                if let Some(addr) = event[key].as_str() {
+                    *counts.entry(addr.to_string()).or_default() += 1;
+                }
+            }
+        },
+    )?;
+    if !status.success() && status.code() != Some(1) {
+        bail!("Failed to read {EVE_FILENAME}");
+    }
+    let mut signatures: Vec<Noisy> = signatures.into_values().collect();
+    signatures.sort_by(|a, b| b.count.cmp(&a.count).then(a.sid.cmp(&b.sid)));
+    Ok(signatures)
+}
+
+/// Append lines to a file in the state directory.
+fn append(filename: &str, lines: &[String]) -> Result<()> {
+    let path = state::file_path(filename);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    for line in lines {
+        writeln!(file, "{line}")?;
+    }
+    info!("Added to {}: {}", path.display(), lines.join(" "));
+    Ok(())
+}
+
+/// Run the tuning session over the alerts of the last `days` days,
+/// offering the `top` noisiest signatures.
+pub(crate) fn session(context: &Context, days: i64, top: usize) -> Result<()> {
+    let mut signatures = collect(context, days)?;
+    signatures.truncate(top);
+    if signatures.is_empty() {
+        info!("No alerts in the last {days} day(s), nothing to tune");
+        return Ok(());
+    }
+
+    let mut disabled = false;
+    let mut thresholded = false;
+    loop {
+        let mut selections: Vec<SelectItem> = signatures
+            .iter()
+            .enumerate()
+            .map(|(i, noisy)| SelectItem::new(i.to_string(), noisy.label()))
+            .collect();
+        selections.push(SelectItem::new("done", "Done"));
+        let selections = add_index(&selections);
+        let selection = match inquire::Select::new(
+            &format!("Noisiest signatures of the last {days} day(s)"),
+            selections,
+        )
+        .prompt()
+        {
+            Ok(selection) => selection,
+            Err(_) => break,
+        };
+        let noisy = match selection.tag.parse::<usize>() {
+            Ok(i) => &signatures[i],
+            Err(_) => break,
+        };
+        match tune(noisy) {
+            Ok(Some(THRESHOLD_CONFIG)) => thresholded = true,
+            Ok(Some(_)) => disabled = true,
+            Ok(None) => {}
+            Err(err) => {
+                error!("{err}");
+                prompt::enter();
+            }
+        }
+    }
+
+    if disabled && prompt::confirm("Update the rules now to apply disable.conf?", None) {
+        actions::update_rules(context)?;
+    }
+    if thresholded && context.manager.is_running(SURICATA_CONTAINER_NAME) {
+        if prompt::confirm("Restart Suricata now to apply threshold.config?", None) {
+            crate::stop_container(context, SURICATA_CONTAINER_NAME, None);
+            crate::start_suricata_detached(context)?;
+        } else {
+            info!("Restart Suricata to apply {THRESHOLD_CONFIG}");
+        }
+    }
+    Ok(())
+}
+
+/// Offer the tuning actions for a signature, returning the file changed,
+/// if any.
+fn tune(noisy: &Noisy) -> Result<Option<&'static str>> {
+    let (gid, sid) = (noisy.gid, noisy.sid);
+    let comment = format!("# {} ({} alerts)", noisy.signature, noisy.count);
+    let mut selections = vec![SelectItem::new("disable", "Disable the rule")];
+    let source = top_address(&noisy.sources, noisy.count);
+    let destination = top_address(&noisy.destinations, noisy.count);
+    if let Some((addr, percent)) = source {
+        selections.push(SelectItem::new(
+            "suppress-src",
+            format!("Suppress for source {addr} ({percent}% of the alerts)"),
+        ));
+    }
+    if let Some((addr, percent)) = destination {
+        selections.push(SelectItem::new(
+            "suppress-dst",
+            format!("Suppress for destination {addr} ({percent}% of the alerts)"),
+        ));
+    }
+    selections.push(SelectItem::new(
+        "threshold",
+        "Limit to 1 alert per source per hour",
+    ));
+    selections.push(SelectItem::new("return", "Return"));
+    let selections = add_index(&selections);
+
+    let selection = match inquire::Select::new(&noisy.label(), selections).prompt() {
+        Ok(selection) => selection,
+        Err(_) => return Ok(None),
+    };
+    match selection.tag.as_ref() {
+        "disable" => {
+            append("disable.conf", &[comment, format!("{gid}:{sid}")])?;
+            Ok(Some("disable.conf"))
+        }
+        "suppress-src" | "suppress-dst" => {
+            let (track, addr) = if selection.tag == "suppress-src" {
+                ("by_src", source)
+            } else {
+                ("by_dst", destination)
+            };
+            let (addr, _) = addr.unwrap();
+            append(
+                THRESHOLD_CONFIG,
+                &[
+                    comment,
+                    format!("suppress gen_id {gid}, sig_id {sid}, track {track}, ip {addr}"),
+                ],
+            )?;
+            Ok(Some(THRESHOLD_CONFIG))
+        }
+        "threshold" => {
+            append(
+                THRESHOLD_CONFIG,
+                &[
+                    comment,
+                    format!(
+                        "threshold gen_id {gid}, sig_id {sid}, type limit, track by_src, \
+                         count 1, seconds 3600"
+                    ),
+                ],
+            )?;
+            Ok(Some(THRESHOLD_CONFIG))
+        }
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,26 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Host system helpers. Network interfaces are in `netif` and the
+//! container engines in `container`.
+
+/// The real user ID of the process.
+pub(crate) fn getuid() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+/// Return true if running as root.
+pub(crate) fn is_root() -> bool {
+    getuid() == 0
+}
+
+/// Quote a value for a shell command line, such as in a cron entry.
+pub(crate) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Quote a value for the command of a cron entry, where % is also
+/// special.
+pub(crate) fn cron_quote(value: &str) -> String {
+    shell_quote(value).replace('%', "\\%")
+}
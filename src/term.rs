@@ -7,9 +7,12 @@ use crossterm::{
 };
 use std::io::Write;
 
+fn no_clear() -> bool {
+    std::env::var("NO_CLEAR").map(|_| true).unwrap_or(false)
+}
+
 pub(crate) fn title(title: &str) {
-    let no_clear = std::env::var("NO_CLEAR").map(|_| true).unwrap_or(false);
-    if no_clear {
+    if no_clear() {
         println!("{}\n", title);
     } else {
         let mut stdout = std::io::stdout().lock();
@@ -23,3 +26,29 @@ pub(crate) fn title(title: &str) {
         let _ = stdout.flush();
     }
 }
+
+/// Return the current cursor row, if the screen is being managed.
+///
+/// With NO_CLEAR set output just scrolls, so lines can't be
+/// revisited and None is returned.
+pub(crate) fn cursor_row() -> Option<u16> {
+    if no_clear() {
+        return None;
+    }
+    cursor::position().ok().map(|(_, row)| row)
+}
+
+/// Replace the contents of a line on the screen, leaving the cursor
+/// where it was.
+pub(crate) fn update_line(row: u16, text: &str) {
+    let mut stdout = std::io::stdout().lock();
+    let _ = execute!(
+        stdout,
+        cursor::SavePosition,
+        cursor::MoveTo(0, row),
+        Clear(ClearType::CurrentLine),
+        style::Print(text),
+        cursor::RestorePosition
+    );
+    let _ = stdout.flush();
+}
@@ -0,0 +1,465 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Guided troubleshooting of common failures, running the checks that
+//! matter for a symptom and printing a diagnosis.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use colored::Colorize;
+
+use crate::{
+    actions, add_index,
+    container::Container,
+    context::Context,
+    doctor::{self, Check, Outcome},
+    fleet, journal, probe, prompt,
+    state::State,
+    term, SelectItem, EVEBOX_CONTAINER_NAME, SURICATA_CONTAINER_NAME,
+};
+
+/// Drop percentages above these are a warning and a failure.
+const DROP_WARN_PERCENT: f64 = 1.0;
+const DROP_FAIL_PERCENT: f64 = 5.0;
+
+/// Rules older than this many days are a warning.
+const RULES_STALE_DAYS: i64 = 7;
+
+/// Number of eve.json lines searched for alerts.
+const ALERT_LINES: usize = 20000;
+
+/// Timeout connecting to each rule source.
+const SOURCE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) fn menu(context: &Context) {
+    loop {
+        term::title("Simple-IDS: Troubleshoot");
+
+        let selections = vec![
+            SelectItem::new("no-alerts", "No Alerts Appearing"),
+            SelectItem::new("evebox-unreachable", "EveBox Unreachable"),
+            SelectItem::new("drops", "High Packet Drops"),
+            SelectItem::new("rule-update", "Rule Update Failing"),
+            SelectItem::new("return", "Return"),
+        ];
+        let selections = add_index(&selections);
+        let checks = match inquire::Select::new("What is the problem?", selections).prompt() {
+            Ok(selection) => match selection.tag.as_ref() {
+                "no-alerts" => no_alerts(context),
+                "evebox-unreachable" => evebox_unreachable(context),
+                "drops" => drops(context),
+                "rule-update" => rule_update(context),
+                _ => return,
+            },
+            Err(_) => return,
+        };
+        println!();
+        for check in &checks {
+            check.print();
+        }
+        diagnose(&checks);
+        prompt::enter();
+    }
+}
+
+/// Print the most likely cause, the first failure, or otherwise the
+/// warnings.
+fn diagnose(checks: &[Check]) {
+    println!();
+    let failure = checks.iter().find(|check| check.outcome == Outcome::Fail);
+    let warnings: Vec<&Check> = checks
+        .iter()
+        .filter(|check| check.outcome == Outcome::Warn)
+        .collect();
+    if let Some(check) = failure {
+        println!(
+            "{} {}: {}",
+            "Diagnosis:".red().bold(),
+            check.name,
+            check.detail
+        );
+        if let Some(hint) = &check.hint {
+            println!("  {hint}");
+        }
+    } else if !warnings.is_empty() {
+        println!("{} possible causes:", "Diagnosis:".yellow().bold());
+        for check in warnings {
+            println!(
+                "  {}: {}, {}",
+                check.name,
+                check.detail,
+                check.hint.as_deref().unwrap_or_default()
+            );
+        }
+    } else {
+        println!(
+            "{} no problems found. If the problem persists, check the logs \
+             with: simple-ids logs",
+            "Diagnosis:".green().bold()
+        );
+    }
+}
+
+/// Check that Suricata is running, returning false if the checks that
+/// need it should be skipped.
+fn suricata_running(context: &Context, checks: &mut Vec<Check>) -> bool {
+    let name = "suricata";
+    if !context.config.services.suricata {
+        checks.push(Check::fail(
+            name,
+            "disabled in the deployment mode",
+            "Suricata runs on the sensors, not on this EveBox only host",
+        ));
+        return false;
+    }
+    match context.manager.state(SURICATA_CONTAINER_NAME) {
+        Ok(state) if state.status == "paused" => {
+            checks.push(Check::fail(
+                name,
+                "capture is paused",
+                "Resume capture from the main menu, or with: simple-ids resume",
+            ));
+            false
+        }
+        Ok(state) if state.running => {
+            checks.push(Check::pass(name, "running"));
+            true
+        }
+        Ok(state) => {
+            checks.push(Check::fail(
+                name,
+                format!("container is {}", state.status),
+                "See why it stopped with: simple-ids logs suricata",
+            ));
+            false
+        }
+        Err(_) => {
+            checks.push(Check::fail(
+                name,
+                "not running",
+                "Start it from the main menu, or with: simple-ids start",
+            ));
+            false
+        }
+    }
+}
+
+/// The kernel packet and drop counters from the last stats.log entry.
+fn capture_counters(context: &Context) -> Option<(u64, u64)> {
+    fleet::exec(
+        context,
+        SURICATA_CONTAINER_NAME,
+        &["tail", "-n", "500", "/var/log/suricata/stats.log"],
+    )
+    .and_then(|output| fleet::parse_capture_counters(&output))
+}
+
+fn no_alerts(context: &Context) -> Vec<Check> {
+    let mut checks = doctor::check_interfaces(&context.config, Some(&context.manager));
+    if !suricata_running(context, &mut checks) {
+        return checks;
+    }
+
+    checks.push(match actions::rule_stats(context) {
+        Some(stats) if stats.loaded == 0 => Check::fail(
+            "rules",
+            "no rules loaded",
+            "Update the rules from the main menu, or with: simple-ids update-rules",
+        ),
+        Some(stats) if stats.failed > 0 => Check::warn(
+            "rules",
+            format!("{} loaded, {} failed", stats.loaded, stats.failed),
+            "See the failed rules with: simple-ids logs suricata",
+        ),
+        Some(stats) => Check::pass("rules", format!("{} loaded", stats.loaded)),
+        None => Check::warn(
+            "rules",
+            "unable to find the rule counts in the Suricata log",
+            "Suricata may still be starting, try again shortly",
+        ),
+    });
+
+    checks.push(match capture_counters(context) {
+        Some((0, _)) => Check::fail(
+            "traffic",
+            "no packets captured",
+            "Make sure the interface sees the traffic, such as from a span port or tap",
+        ),
+        Some((packets, _)) => Check::pass("traffic", format!("{packets} packets captured")),
+        None => Check::warn(
+            "traffic",
+            "no capture counters in stats.log yet",
+            "Suricata writes stats.log periodically, try again shortly",
+        ),
+    });
+
+    let issues = crate::logs::suricata_startup_issues(context);
+    let errors = issues.iter().filter(|issue| issue.error).count();
+    if errors > 0 {
+        checks.push(Check::warn(
+            "startup",
+            format!("{errors} error(s) logged while starting"),
+            "See the errors with: simple-ids logs suricata",
+        ));
+    }
+
+    let mut alerts = 0;
+    let tail = ALERT_LINES.to_string();
+    let _ = crate::eve::exec_lines(
+        context,
+        &["tail", "-n", &tail, crate::eve::EVE_FILENAME],
+        |line| {
+            if line.contains("\"event_type\":\"alert\"") {
+                alerts += 1;
+            }
+        },
+    );
+    checks.push(if alerts == 0 {
+        Check::warn(
+            "alerts",
+            "none in the recent events",
+            "Traffic may simply not match any rules, test with: curl http://testmynids.org/uid/index.html",
+        )
+    } else {
+        Check::pass("alerts", format!("{alerts} in the recent events"))
+    });
+    checks
+}
+
+fn evebox_unreachable(context: &Context) -> Vec<Check> {
+    let name = "evebox";
+    let mut checks = vec![];
+    if !context.config.services.evebox {
+        checks.push(Check::fail(
+            name,
+            "disabled in the deployment mode",
+            "Enable EveBox in the deployment mode of the configure menu",
+        ));
+        return checks;
+    }
+    if let Some(agent) = &context.config.evebox.agent {
+        checks.push(Check::fail(
+            name,
+            format!("running as an agent forwarding to {}", agent.server),
+            "In sensor-only mode EveBox is served by the central server",
+        ));
+        return checks;
+    }
+    match context.manager.state(EVEBOX_CONTAINER_NAME) {
+        Ok(state) if state.running => checks.push(Check::pass(name, "running")),
+        Ok(state) => {
+            checks.push(Check::fail(
+                name,
+                format!("container is {}", state.status),
+                "See why it stopped with: simple-ids logs evebox",
+            ));
+            return checks;
+        }
+        Err(_) => {
+            checks.push(doctor::check_port(&context.config, Some(&context.manager)));
+            checks.push(Check::fail(
+                name,
+                "not running",
+                "Start it from the main menu, or with: simple-ids start",
+            ));
+            return checks;
+        }
+    }
+
+    checks.push(match probe::evebox(context) {
+        Some(Ok(probe)) if probe.status < 500 => Check::pass(
+            "http",
+            format!("HTTP {} in {}ms", probe.status, probe.latency.as_millis()),
+        ),
+        Some(Ok(probe)) => Check::fail(
+            "http",
+            format!("HTTP {}", probe.status),
+            "EveBox is running but failing, see: simple-ids logs evebox",
+        ),
+        Some(Err(err)) => Check::fail(
+            "http",
+            format!("no response: {err}"),
+            "EveBox may still be starting, otherwise see: simple-ids logs evebox",
+        ),
+        None => Check::warn(
+            "http",
+            "unable to probe EveBox on a remote container host",
+            "Check the URL shown by: simple-ids status",
+        ),
+    });
+
+    let evebox = &context.config.evebox;
+    checks.push(if evebox.allow_remote {
+        Check::pass(
+            "remote access",
+            format!("enabled, listening on {}", crate::guess_evebox_url(context)),
+        )
+    } else {
+        Check::warn(
+            "remote access",
+            "disabled, EveBox only listens on localhost",
+            "Enable remote access in the EveBox menu, or use an SSH tunnel",
+        )
+    });
+    if !evebox.no_tls {
+        checks.push(Check::pass(
+            "tls",
+            "enabled, use https:// and accept the self-signed certificate",
+        ));
+    }
+    checks
+}
+
+fn drops(context: &Context) -> Vec<Check> {
+    let mut checks = doctor::check_interfaces(&context.config, Some(&context.manager));
+    if !suricata_running(context, &mut checks) {
+        return checks;
+    }
+
+    checks.push(match capture_counters(context) {
+        Some((packets, drops)) => {
+            let percent = fleet::drop_percent(packets, drops);
+            let detail = format!("{percent:.2}% of packets dropped ({drops} of {packets})");
+            let hint = "Apply the high throughput tuning preset from the configure menu";
+            if percent > DROP_FAIL_PERCENT {
+                Check::fail("drops", detail, hint)
+            } else if percent > DROP_WARN_PERCENT {
+                Check::warn("drops", detail, hint)
+            } else {
+                Check::pass("drops", detail)
+            }
+        }
+        None => Check::warn(
+            "drops",
+            "no capture counters in stats.log yet",
+            "Suricata writes stats.log periodically, try again shortly",
+        ),
+    });
+
+    if let Some(cpus) = &context.config.suricata.cpus {
+        checks.push(Check::warn(
+            "cpu limit",
+            format!("Suricata is limited to {cpus} CPUs"),
+            "Remove the limit if Suricata can't keep up",
+        ));
+    }
+    if let Some(pressure) = State::load().disk_pressure {
+        checks.push(Check::warn(
+            "disk",
+            pressure,
+            "Logging is reduced, free up space on the log volume",
+        ));
+    }
+    checks
+}
+
+/// Check that the server of each rule source URL answers, through the
+/// proxy set in the environment if any. Only the scheme and host are
+/// used, so URLs with suricata-update templates or secret codes work.
+fn check_sources(urls: &[String]) -> Vec<Check> {
+    let name = "download";
+    let client = match reqwest::blocking::Client::builder()
+        .timeout(SOURCE_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(err) => {
+            return vec![Check::warn(
+                name,
+                format!("unable to check the rule sources: {err}"),
+                "Check that rules can be downloaded with: simple-ids update-rules",
+            )]
+        }
+    };
+    let re = regex::Regex::new(r"^(https?://[^/]+)").unwrap();
+    let mut servers: Vec<&str> = urls
+        .iter()
+        .filter_map(|url| re.captures(url))
+        .filter_map(|caps| caps.get(1))
+        .map(|server| server.as_str())
+        .collect();
+    servers.sort_unstable();
+    servers.dedup();
+    servers
+        .into_iter()
+        .map(|server| match client.head(server).send() {
+            // Any response means the server was reached.
+            Ok(_) => Check::pass(name, format!("{server} is reachable")),
+            Err(err) => Check::fail(
+                name,
+                format!("unable to reach {server}: {err}"),
+                "Check the DNS, proxy and firewall settings of this host",
+            ),
+        })
+        .collect()
+}
+
+fn rule_update(context: &Context) -> Vec<Check> {
+    let mut checks = vec![];
+
+    let last = journal::read().ok().and_then(|entries| {
+        entries.into_iter().rev().find(|entry| {
+            matches!(
+                entry.kind,
+                journal::Kind::RuleUpdate | journal::Kind::RuleUpdateFailed
+            )
+        })
+    });
+    checks.push(match last {
+        Some(entry) if entry.kind == journal::Kind::RuleUpdateFailed => Check::fail(
+            "last update",
+            format!(
+                "failed {}: {}",
+                crate::display::format_minutes(&entry.timestamp),
+                entry.message.trim()
+            ),
+            "Fix the error above and update the rules again",
+        ),
+        Some(entry) => Check::pass(
+            "last update",
+            format!(
+                "succeeded {}",
+                crate::display::format_minutes(&entry.timestamp)
+            ),
+        ),
+        None => Check::warn(
+            "last update",
+            "no rule update recorded",
+            "Update the rules from the main menu, or with: simple-ids update-rules",
+        ),
+    });
+
+    if let Some(updated) = State::load().last_rule_update {
+        let days = Utc::now().signed_duration_since(updated).num_days();
+        if days > RULES_STALE_DAYS {
+            checks.push(Check::warn(
+                "rule age",
+                format!("rules last updated {days} days ago"),
+                "Update the rules regularly, such as daily with: simple-ids update-rules",
+            ));
+        }
+    }
+
+    match actions::source_urls(context) {
+        Ok(urls) => checks.extend(check_sources(&urls)),
+        Err(err) => checks.push(Check::warn(
+            "download",
+            format!("unable to read the enabled rule sources: {err}"),
+            "Check the rule sources from the Suricata-Update menu",
+        )),
+    }
+
+    if !context
+        .manager
+        .has_image(&context.image_name(Container::Suricata))
+    {
+        checks.push(Check::fail(
+            "image",
+            "the Suricata image is not present",
+            "The rule update runs in the Suricata image, pull it with: simple-ids update",
+        ));
+    }
+    checks.push(doctor::check_disk(&context.manager));
+    checks
+}
@@ -0,0 +1,168 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Tuning presets, curated bundles of settings for common sensor
+//! types.
+
+use clap::ValueEnum;
+
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{Config, TuningConfig},
+    diskpolicy, netif, nicsetup, prompt,
+};
+
+/// EVE types kept in alerts only mode. Stats are left to the stats
+/// configuration.
+const ALERT_TYPES: &[&str] = &["alert", "anomaly", "drop", "stats"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Preset {
+    /// Raspberry Pi class sensors: a single thread, small buffers and
+    /// only alerts logged.
+    Low,
+    /// 1G+ links: the workers runmode with a thread per CPU, pinned to
+    /// CPUs, with large buffers.
+    Performance,
+    /// Traffic accounting: no rules loaded, only flow records logged.
+    FlowOnly,
+}
+
+impl Preset {
+    pub(crate) fn all() -> &'static [Preset] {
+        &[Preset::Low, Preset::Performance, Preset::FlowOnly]
+    }
+
+    pub(crate) fn description(&self) -> &'static str {
+        match self {
+            Preset::Low => "Low resource (Raspberry Pi class sensors)",
+            Preset::Performance => "High throughput (1G+ links)",
+            Preset::FlowOnly => "Flow only (traffic accounting, no alerts)",
+        }
+    }
+
+    /// Apply the preset to the configuration, replacing any previous
+    /// tuning.
+    pub(crate) fn apply(&self, config: &mut Config) {
+        let suricata = &mut config.suricata;
+        match self {
+            Preset::Low => {
+                suricata.tuning = TuningConfig {
+                    runmode: Some("workers".to_string()),
+                    threads: Some(1),
+                    ring_size: Some(1024),
+                    alerts_only: true,
+                    rotate_interval: Some(60),
+                    ..Default::default()
+                };
+                suricata.stats.interval = Some(60);
+                suricata.stats.eve_stats = Some(false);
+            }
+            Preset::Performance => {
+                suricata.tuning = TuningConfig {
+                    runmode: Some("workers".to_string()),
+                    threads: Some(performance_threads(suricata.capture_interfaces().len())),
+                    cpu_affinity: true,
+                    ring_size: Some(32768),
+                    ..Default::default()
+                };
+                suricata.stats.interval = Some(30);
+                suricata.stats.stats_log = Some(true);
+                suricata.stats.eve_stats = Some(true);
+            }
+            Preset::FlowOnly => {
+                suricata.tuning = TuningConfig {
+                    no_rules: true,
+                    eve_types: vec!["flow".to_string(), "netflow".to_string()],
+                    ..Default::default()
+                };
+                suricata.stats.eve_stats = Some(false);
+            }
+        }
+    }
+}
+
+/// Capture threads per interface for the performance preset, leaving a
+/// CPU for the management threads and EveBox.
+fn performance_threads(interfaces: usize) -> u32 {
+    let cpus = std::thread::available_parallelism()
+        .map(|cpus| cpus.get())
+        .unwrap_or(1);
+    (cpus.saturating_sub(1) / interfaces.max(1)).max(1) as u32
+}
+
+/// Check the capture interfaces for offloads that should be disabled
+/// for high throughput capture, offering to disable them if
+/// interactive.
+pub(crate) fn check_offloads(config: &Config, interactive: bool) {
+    let mut disabled = false;
+    for interface in &config.suricata.capture_interfaces() {
+        let offloads = match netif::enabled_offloads(interface) {
+            Ok(offloads) => offloads,
+            Err(err) => {
+                warn!("Failed to check the offloads of {interface}: {err}");
+                continue;
+            }
+        };
+        if offloads.is_empty() {
+            continue;
+        }
+        warn!(
+            "Offloads {} are enabled on {interface}, Suricata may see merged packets",
+            offloads.join(", ")
+        );
+        if interactive
+            && prompt::confirm(
+                &format!("Disable {} on {interface}?", offloads.join(", ")),
+                Some("Not persistent, they will be enabled again on reboot"),
+            )
+        {
+            match netif::disable_offloads(interface, &offloads) {
+                Ok(()) => {
+                    info!("Disabled offloads on {interface}");
+                    disabled = true;
+                }
+                Err(err) => error!("Failed to disable offloads on {interface}: {err}"),
+            }
+        }
+    }
+    if disabled && prompt::confirm("Disable the offloads on boot with a systemd unit?", None) {
+        if let Err(err) = nicsetup::install(config) {
+            error!("Failed to install the capture interface unit: {err}");
+        }
+    }
+}
+
+impl std::fmt::Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+/// Suricata `--set` arguments to enable and disable EVE types for the
+/// tuning, the type indexes are found in the `--dump-config` output.
+pub(crate) fn eve_set_args(
+    tuning: &crate::config::TuningConfig,
+    dump_config: &[String],
+) -> Vec<String> {
+    if tuning.alerts_only {
+        diskpolicy::disable_eve_types_args(dump_config, |name| !ALERT_TYPES.contains(&name))
+    } else if !tuning.eve_types.is_empty() {
+        let mut args = diskpolicy::disable_eve_types_args(dump_config, |name| {
+            name != "stats" && !tuning.eve_types.iter().any(|kept| kept == name)
+        });
+        let names: Vec<&str> = tuning.eve_types.iter().map(String::as_str).collect();
+        args.extend(diskpolicy::enable_eve_types_args(dump_config, &names));
+        args
+    } else if !tuning.disabled_eve_types.is_empty() {
+        diskpolicy::disable_eve_types_args(dump_config, |name| {
+            tuning
+                .disabled_eve_types
+                .iter()
+                .any(|disabled| disabled == name)
+        })
+    } else {
+        vec![]
+    }
+}
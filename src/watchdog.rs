@@ -0,0 +1,183 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! A watchdog for the detached containers, restarting a container that
+//! has died, or an EveBox that stops answering HTTP, with backoff.
+//!
+//! A container that doesn't exist was stopped on purpose and is left
+//! alone, as is a paused container.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    configwatch, container::Container, context::Context, journal, notify, probe, state::State,
+};
+
+const BACKOFF_MIN: Duration = Duration::from_secs(10);
+const BACKOFF_MAX: Duration = Duration::from_secs(600);
+
+/// Consecutive failed HTTP probes before EveBox is restarted.
+const PROBE_FAILURES: u32 = 3;
+
+/// Restart tracking for one container.
+struct Watch {
+    container: Container,
+    backoff: Duration,
+    /// No restart is attempted before this time.
+    next_attempt: Option<Instant>,
+    last_restart: Option<Instant>,
+    probe_failures: u32,
+}
+
+impl Watch {
+    fn new(container: Container) -> Self {
+        Self {
+            container,
+            backoff: BACKOFF_MIN,
+            next_attempt: None,
+            last_restart: None,
+            probe_failures: 0,
+        }
+    }
+
+    /// Restart the container unless still backing off from the last
+    /// restart.
+    fn restart(&mut self, context: &Context, reason: &str) {
+        let now = Instant::now();
+        if self.next_attempt.is_some_and(|next| now < next) {
+            debug!("{}: backing off before restarting", self.container.name());
+            return;
+        }
+        // Only back off if the container keeps failing soon after being
+        // restarted.
+        if self
+            .last_restart
+            .is_some_and(|last| now.duration_since(last) > BACKOFF_MAX)
+        {
+            self.backoff = BACKOFF_MIN;
+        }
+
+        let name = self.container.name();
+        warn!("{name} {reason}, restarting it");
+        let result = match self.container {
+            Container::Suricata => crate::start_suricata_detached(context),
+            Container::EveBox => crate::start_evebox_detached(context),
+        };
+        let message = match &result {
+            Ok(()) => format!("{name} {reason}, restarted by the watchdog"),
+            Err(err) => format!("{name} {reason}, failed to restart: {err}"),
+        };
+        match &result {
+            Ok(()) => info!("{message}"),
+            Err(_) => error!("{message}"),
+        }
+        journal::record(journal::Kind::WatchdogRestart, &message);
+        if let Err(err) = State::record_watchdog_restart(name) {
+            error!("Failed to record watchdog restart of {name}: {err}");
+        }
+        notify::send_or_warn(
+            &context.config.notifications,
+            &format!("{name} restarted"),
+            &message,
+            notify::Priority::High,
+        );
+
+        self.probe_failures = 0;
+        self.last_restart = Some(now);
+        self.next_attempt = Some(now + self.backoff);
+        self.backoff = (self.backoff * 2).min(BACKOFF_MAX);
+    }
+
+    fn check(&mut self, context: &Context) {
+        let name = self.container.name();
+        let state = match context.manager.state(name) {
+            Ok(state) => state,
+            Err(_) => {
+                debug!("{name}: no container, not watching");
+                self.probe_failures = 0;
+                return;
+            }
+        };
+        if state.status == "paused" {
+            return;
+        }
+        if !state.running {
+            self.restart(context, &format!("is {}", state.status));
+            return;
+        }
+        if self.container == Container::EveBox {
+            self.check_http(context);
+        }
+    }
+
+    fn check_http(&mut self, context: &Context) {
+        let failure = match probe::evebox(context) {
+            // Can't be probed from here, such as in sensor mode.
+            None => return,
+            Some(Ok(probe)) if probe.status < 500 => None,
+            Some(Ok(probe)) => Some(format!("HTTP {}", probe.status)),
+            Some(Err(err)) => Some(err.to_string()),
+        };
+        match failure {
+            None => self.probe_failures = 0,
+            Some(failure) => {
+                self.probe_failures += 1;
+                warn!(
+                    "EveBox health check failed ({}/{}): {failure}",
+                    self.probe_failures, PROBE_FAILURES
+                );
+                if self.probe_failures >= PROBE_FAILURES {
+                    self.restart(context, "is not responding");
+                }
+            }
+        }
+    }
+}
+
+/// Check the containers every `interval` seconds until interrupted,
+/// applying changes to the configuration file as they are made.
+pub(crate) fn run(context: &Context, interval: u64) -> Result<()> {
+    let mut context = context.clone();
+    let mut watches: Vec<Watch> = [Container::Suricata, Container::EveBox]
+        .into_iter()
+        .filter(|container| context.config.services.enabled(*container))
+        .map(Watch::new)
+        .collect();
+    if watches.is_empty() {
+        bail!("No services are enabled in the configuration");
+    }
+    let interval = Duration::from_secs(interval.max(1));
+    info!(
+        "Watching {} every {}s",
+        watches
+            .iter()
+            .map(|watch| watch.container.name())
+            .collect::<Vec<_>>()
+            .join(" and "),
+        interval.as_secs()
+    );
+    let changes = match configwatch::watch() {
+        Ok(changes) => Some(changes),
+        Err(err) => {
+            warn!("Not watching the configuration file: {err}");
+            None
+        }
+    };
+    loop {
+        for watch in &mut watches {
+            watch.check(&context);
+        }
+        match &changes {
+            Some(changes) => {
+                if changes.recv_timeout(interval).is_ok() {
+                    configwatch::settle(changes);
+                    configwatch::apply_detached(&mut context);
+                }
+            }
+            None => std::thread::sleep(interval),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// SPDX-FileCopyrightText: (C) 2024 Jason Ish <jason@codemonkey.net>
+// SPDX-License-Identifier: MIT
+
+//! Windows Subsystem for Linux (WSL2) detection and guidance.
+//!
+//! Live capture under WSL2 only sees the traffic of the Windows host,
+//! and only when WSL is using mirrored networking. In the default NAT
+//! mode Suricata only sees the traffic of the WSL virtual machine.
+
+use colored::Colorize;
+
+use crate::{context::Context, state::State, SelectItem};
+
+pub(crate) fn is_wsl2() -> bool {
+    if std::env::var("WSL_DISTRO_NAME").is_ok() {
+        return true;
+    }
+    std::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|release| release.to_lowercase().contains("microsoft"))
+        .unwrap_or(false)
+}
+
+/// The WSL networking mode, such as "nat" or "mirrored", if it can be
+/// determined. Needs the `wslinfo` tool from newer WSL releases.
+pub(crate) fn networking_mode() -> Option<String> {
+    let output = std::process::Command::new("wslinfo")
+        .arg("--networking-mode")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let mode = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if mode.is_empty() {
+        None
+    } else {
+        Some(mode)
+    }
+}
+
+pub(crate) fn print_guidance(mode: Option<&str>) {
+    println!("{}", "Simple-IDS is running under WSL2".bold());
+    println!();
+    match mode {
+        Some("mirrored") => {
+            println!("WSL is using mirrored networking, the Windows network interfaces");
+            println!("are available with Linux names, such as eth0 and eth1.");
+        }
+        Some(mode) => {
+            println!(
+                "WSL is using {} networking, Suricata will only see the traffic of",
+                mode.yellow()
+            );
+            println!("the WSL virtual machine, not the Windows host or network.");
+        }
+        None => {
+            println!("The WSL networking mode could not be determined. Unless mirrored");
+            println!("networking is enabled, Suricata will only see the traffic of the");
+            println!("WSL virtual machine.");
+        }
+    }
+    println!();
+    println!("To monitor the Windows host (Windows 11 22H2 or newer):");
+    println!("  1. Add the following to %UserProfile%\\.wslconfig:");
+    println!("       [wsl2]");
+    println!("       networkingMode=mirrored");
+    println!("  2. Run \"wsl --shutdown\" from Windows, then start WSL again.");
+    println!("  3. Select the interface with the Windows host address.");
+    println!();
+    println!("Limitations:");
+    println!("  - Only the traffic of the Windows host is seen, not other devices,");
+    println!("    as the network adapter is not put into promiscuous mode.");
+    println!("  - WSL may be stopped when idle, stopping the capture with it.");
+    println!();
+    println!("If live capture isn't possible, pcap analysis mode runs Suricata");
+    println!("without capture, for processing pcap files.");
+    println!();
+}
+
+/// Show the guidance once, offering pcap analysis mode if live capture
+/// isn't possible.
+pub(crate) fn wizard(context: &mut Context) {
+    if !is_wsl2() || State::load().wsl_guidance_shown {
+        return;
+    }
+    print_guidance(networking_mode().as_deref());
+    let selections = vec![
+        SelectItem::new("live", "Continue with live capture"),
+        SelectItem::new("pcap", "Use pcap analysis mode, no live capture"),
+    ];
+    if let Ok(selection) = inquire::Select::new("Select capture mode", selections).prompt() {
+        if selection.tag == "pcap" {
+            context.config.suricata.unix_socket = true;
+            let _ = context.config.save();
+        }
+        let _ = State::update(|state| state.wsl_guidance_shown = true);
+    }
+}